@@ -0,0 +1,9 @@
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    // 使用内置的 protoc，构建时不依赖系统安装
+    std::env::set_var("PROTOC", protoc_bin_vendored::protoc_bin_path()?);
+    tonic_build::configure()
+        .build_server(false)
+        .bytes(["."])
+        .compile(&["../proto/fdb.proto"], &["../proto"])?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use bytes::Bytes;
+use pb::kv_client::KvClient;
+use pb::{batch_op, BatchOp, BatchRequest, DeleteRequest, GetRequest, PutRequest, ScanRequest};
+use tonic::codegen::StdError;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Code, Status, Streaming};
+
+pub use pb::KeyValue;
+
+/// 由 proto/fdb.proto 生成的消息和客户端代码
+pub mod pb {
+    tonic::include_proto!("fdb");
+}
+
+impl BatchOp {
+    /// 批量操作中的写入
+    pub fn put(key: impl Into<Bytes>, value: impl Into<Bytes>) -> Self {
+        BatchOp {
+            op: Some(batch_op::Op::Put(PutRequest {
+                key: key.into(),
+                value: value.into(),
+            })),
+        }
+    }
+
+    /// 批量操作中的删除
+    pub fn delete(key: impl Into<Bytes>) -> Self {
+        BatchOp {
+            op: Some(batch_op::Op::Delete(DeleteRequest { key: key.into() })),
+        }
+    }
+}
+
+/// fdb 客户端，内部的连接可以廉价地 clone
+#[derive(Clone)]
+pub struct Client {
+    inner: KvClient<Channel>,
+}
+
+impl Client {
+    /// 连接到服务端，例如 `http://127.0.0.1:50051`
+    pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+    where
+        D: TryInto<Endpoint>,
+        D::Error: Into<StdError>,
+    {
+        let inner = KvClient::connect(dst).await?;
+        Ok(Self { inner })
+    }
+
+    /// 读取key对应的value，key不存在时返回 None
+    pub async fn get(&mut self, key: impl Into<Bytes>) -> Result<Option<Bytes>, Status> {
+        let request = GetRequest { key: key.into() };
+        match self.inner.get(request).await {
+            Ok(response) => Ok(Some(response.into_inner().value)),
+            Err(status) if status.code() == Code::NotFound => Ok(None),
+            Err(status) => Err(status),
+        }
+    }
+
+    pub async fn put(
+        &mut self,
+        key: impl Into<Bytes>,
+        value: impl Into<Bytes>,
+    ) -> Result<(), Status> {
+        let request = PutRequest {
+            key: key.into(),
+            value: value.into(),
+        };
+        self.inner.put(request).await?;
+        Ok(())
+    }
+
+    pub async fn delete(&mut self, key: impl Into<Bytes>) -> Result<(), Status> {
+        let request = DeleteRequest { key: key.into() };
+        self.inner.delete(request).await?;
+        Ok(())
+    }
+
+    /// 按前缀扫描，limit 为 0 时不限制数量
+    pub async fn scan(
+        &mut self,
+        prefix: impl Into<Bytes>,
+        reverse: bool,
+        limit: u32,
+    ) -> Result<Streaming<KeyValue>, Status> {
+        let request = ScanRequest {
+            prefix: prefix.into(),
+            reverse,
+            limit,
+        };
+        Ok(self.inner.scan(request).await?.into_inner())
+    }
+
+    /// 依次执行一组操作，返回执行的操作数量
+    pub async fn batch(&mut self, ops: impl IntoIterator<Item = BatchOp>) -> Result<u32, Status> {
+        let request = BatchRequest {
+            ops: ops.into_iter().collect(),
+        };
+        Ok(self.inner.batch(request).await?.into_inner().applied)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use fdb::db::Engine;
+    use fdb::grpc::server::KvService;
+    use fdb::options::Options;
+    use std::fs;
+    use std::sync::Arc;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_client() {
+        let dir_path = std::env::temp_dir().join("fdb-grpc-client");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(KvService::new(Arc::new(engine)).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+
+        let mut client = Client::connect(format!("http://{}", addr)).await.unwrap();
+        assert_eq!(client.get("name").await.unwrap(), None);
+        client.put("name", "fdb").await.unwrap();
+        assert_eq!(client.get("name").await.unwrap(), Some(Bytes::from("fdb")));
+        client.delete("name").await.unwrap();
+        assert_eq!(client.get("name").await.unwrap(), None);
+
+        let applied = client
+            .batch([
+                BatchOp::put("a1", "1"),
+                BatchOp::put("a2", "2"),
+                BatchOp::put("b1", "3"),
+                BatchOp::delete("a2"),
+            ])
+            .await
+            .unwrap();
+        assert_eq!(applied, 4);
+
+        let items: Vec<KeyValue> = client
+            .scan("a", false, 0)
+            .await
+            .unwrap()
+            .map(|item| item.unwrap())
+            .collect()
+            .await;
+        assert_eq!(
+            items,
+            vec![KeyValue {
+                key: Bytes::from("a1"),
+                value: Bytes::from("1"),
+            }]
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
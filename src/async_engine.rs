@@ -0,0 +1,188 @@
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::options::{IteratorOptions, Options};
+use bytes::Bytes;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+
+// 扫描结果的缓冲数量，消费较慢时扫描会暂停
+const SCAN_CHANNEL_SIZE: usize = 64;
+
+/// 存储引擎的异步接口
+///
+/// 引擎的读写和 sync 都是阻塞的，统一放到 tokio 的阻塞线程池中执行，避免阻塞异步运行时。
+/// 取消（drop）返回的 future 不会中断已经开始的操作：写入会在后台完整执行，
+/// 因此取消后数据可能已经写入，但不会出现写了一半的记录或者与索引不一致的状态。
+#[derive(Clone)]
+pub struct AsyncEngine {
+    engine: Arc<Engine>,
+}
+
+impl AsyncEngine {
+    pub fn new(engine: Arc<Engine>) -> Self {
+        Self { engine }
+    }
+
+    /// 在阻塞线程池中打开存储引擎
+    pub async fn open(opts: Options) -> Result<Self> {
+        let engine = tokio::task::spawn_blocking(move || Engine::open(opts))
+            .await
+            .map_err(|_| Errors::AsyncTaskFailed)??;
+        Ok(Self::new(Arc::new(engine)))
+    }
+
+    /// 对应的同步存储引擎
+    pub fn engine(&self) -> &Arc<Engine> {
+        &self.engine
+    }
+
+    pub async fn get(&self, key: Bytes) -> Result<Bytes> {
+        self.blocking(move |engine| engine.get(key)).await
+    }
+
+    pub async fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.blocking(move |engine| engine.put(key, value)).await
+    }
+
+    pub async fn delete(&self, key: Bytes) -> Result<()> {
+        self.blocking(move |engine| engine.delete(key)).await
+    }
+
+    pub async fn sync(&self) -> Result<()> {
+        self.blocking(|engine| engine.sync()).await
+    }
+
+    /// 按迭代器配置扫描数据，返回的 Stream 被 drop 后后台扫描随之停止
+    ///
+    /// 读取数据出错时 Stream 返回这个错误之后结束。
+    pub fn iter(&self, options: IteratorOptions) -> ReceiverStream<Result<(Bytes, Bytes)>> {
+        let (sender, receiver) = mpsc::channel(SCAN_CHANNEL_SIZE);
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || {
+            let iter = engine.iter(options);
+            loop {
+                let item = match iter.try_next() {
+                    Ok(Some(item)) => Ok(item),
+                    Ok(None) => break,
+                    Err(e) => Err(e),
+                };
+                let failed = item.is_err();
+                // 接收端已经关闭，或者读取出错之后停止扫描
+                if sender.blocking_send(item).is_err() || failed {
+                    break;
+                }
+            }
+        });
+        ReceiverStream::new(receiver)
+    }
+
+    // 在阻塞线程池中执行引擎操作
+    async fn blocking<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Engine) -> Result<T> + Send + 'static,
+    {
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || f(&engine))
+            .await
+            .map_err(|_| Errors::AsyncTaskFailed)?
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tokio_stream::StreamExt;
+
+    #[tokio::test]
+    async fn test_async_engine() {
+        let dir_path = std::env::temp_dir().join("fdb-async-engine");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = AsyncEngine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+        for i in 0..100 {
+            let key = Bytes::from(format!("key-{:03}", i));
+            engine.put(key, Bytes::from("value")).await.unwrap();
+        }
+        assert_eq!(
+            engine.get(Bytes::from("key-000")).await.unwrap(),
+            Bytes::from("value")
+        );
+        engine.delete(Bytes::from("key-000")).await.unwrap();
+        assert_eq!(
+            engine.get(Bytes::from("key-000")).await,
+            Err(Errors::KeyNotFound)
+        );
+        engine.sync().await.unwrap();
+
+        let items: Vec<_> = engine
+            .iter(IteratorOptions {
+                prefix: b"key-09".to_vec(),
+                reverse: true,
+            })
+            .collect()
+            .await;
+        let keys: Vec<_> = items.into_iter().map(|item| item.unwrap().0).collect();
+        assert_eq!(keys.len(), 10);
+        assert_eq!(keys[0], Bytes::from("key-099"));
+
+        // 提前结束扫描
+        let first = engine.iter(IteratorOptions::default()).next().await;
+        assert_eq!(first.unwrap().unwrap().0, Bytes::from("key-001"));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_async_engine_cancel() {
+        let dir_path = std::env::temp_dir().join("fdb-async-engine-cancel");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = AsyncEngine::open(opts.clone()).await.unwrap();
+
+        // 启动后立即取消的写入仍然会完整执行
+        {
+            let put = engine.put(Bytes::from("k"), Bytes::from("v"));
+            tokio::pin!(put);
+            tokio::select! {
+                biased;
+                _ = &mut put => {}
+                _ = async {} => {}
+            }
+        }
+        let mut value = engine.get(Bytes::from("k")).await;
+        while value.is_err() {
+            tokio::task::yield_now().await;
+            value = engine.get(Bytes::from("k")).await;
+        }
+        assert_eq!(value.unwrap(), Bytes::from("v"));
+        engine
+            .put(Bytes::from("k2"), Bytes::from("v2"))
+            .await
+            .unwrap();
+
+        // 重新打开之后数据完整
+        drop(engine);
+        let engine = AsyncEngine::open(opts).await.unwrap();
+        assert_eq!(
+            engine.get(Bytes::from("k")).await.unwrap(),
+            Bytes::from("v")
+        );
+        assert_eq!(
+            engine.get(Bytes::from("k2")).await.unwrap(),
+            Bytes::from("v2")
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,225 @@
+use crate::column_family::DEFAULT_CF_ID;
+use crate::data::log_record::LogRecord;
+use crate::data::log_record::LogRecordType::DELETE;
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::options::Compression;
+use bytes::Bytes;
+
+/// 批量写入，commit 时所有操作作为一个整体写入
+///
+/// 整批记录连续写入数据文件，崩溃时没有写完的批量在重新打开时被丢弃，不会只留下其中一部分操作。
+/// 同一个 key 的多次操作以最后一次为准。
+pub struct WriteBatch<'a> {
+    engine: &'a Engine,
+    // 等待提交的操作，value 为 None 表示删除
+    pending: Vec<(Bytes, Option<Bytes>)>,
+}
+
+impl Engine {
+    pub fn write_batch(&self) -> WriteBatch<'_> {
+        WriteBatch {
+            engine: self,
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl WriteBatch<'_> {
+    pub fn put(&mut self, key: Bytes, value: Bytes) -> Result<()> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.pending.push((key, Some(value)));
+        Ok(())
+    }
+
+    pub fn delete(&mut self, key: Bytes) -> Result<()> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.pending.push((key, None));
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.pending.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.pending.is_empty()
+    }
+
+    /// 提交所有操作，提交之后批量被清空，可以继续使用
+    pub fn commit(&mut self) -> Result<()> {
+        if self.engine.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        let _guard = self.engine.lock_writes();
+        self.commit_locked()
+    }
+
+    // 提交所有操作，调用方需要持有写锁，用于在同一个写锁内先读取再写入
+    pub(crate) fn commit_locked(&mut self) -> Result<()> {
+        let engine = self.engine;
+        let mut records = Vec::with_capacity(self.pending.len());
+        let mut blob_refs = Vec::with_capacity(self.pending.len());
+        for (key, value) in self.pending.iter() {
+            let (record, blob_ref) = match value {
+                Some(value) => engine.new_put_record(DEFAULT_CF_ID, key, value, 0)?,
+                None => {
+                    let record = LogRecord {
+                        key: key.to_vec(),
+                        value: Default::default(),
+                        rec_type: DELETE,
+                        compression: Compression::None,
+                        seq: 0,
+                        cf: DEFAULT_CF_ID,
+                        expire: 0,
+                    };
+                    (record, None)
+                }
+            };
+            records.push(record);
+            blob_refs.push(blob_ref);
+        }
+        let positions = engine.append_batch(&mut records)?;
+
+        // 整批写入之后再按顺序更新内存索引
+        for ((record, pos), blob_ref) in records.into_iter().zip(positions).zip(blob_refs) {
+            engine.blobs.track(DEFAULT_CF_ID, &record.key, blob_ref);
+            if record.rec_type == DELETE {
+                engine.index.delete(record.key);
+            } else if !engine.index.put(record.key, pos) {
+                return Err(Errors::IndexUpdateFailed);
+            }
+        }
+        self.pending.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+
+    #[test]
+    fn test_write_batch() {
+        let dir_path = std::env::temp_dir().join("fdb-write-batch");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            blob_threshold: 64,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(Bytes::from("a"), Bytes::from("old")).unwrap();
+        engine.put(Bytes::from("b"), Bytes::from("old")).unwrap();
+
+        let mut batch = engine.write_batch();
+        batch.put(Bytes::from("a"), Bytes::from("new")).unwrap();
+        batch.delete(Bytes::from("b")).unwrap();
+        batch
+            .put(Bytes::from("c"), Bytes::from(vec![7u8; 100]))
+            .unwrap();
+        batch.put(Bytes::from("c"), Bytes::from("last")).unwrap();
+        assert_eq!(
+            batch.put(Bytes::new(), Bytes::new()),
+            Err(Errors::KeyIsEmpty)
+        );
+        assert_eq!(batch.len(), 4);
+        // 提交之前不可见
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "old");
+        batch.commit().unwrap();
+        assert!(batch.is_empty());
+
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "new");
+        assert_eq!(engine.get(Bytes::from("b")), Err(Errors::KeyNotFound));
+        assert_eq!(engine.get(Bytes::from("c")).unwrap(), "last");
+        let stat = engine.blob_stats()[0].clone();
+        assert_eq!(stat.garbage_size, stat.total_size);
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "new");
+        assert_eq!(engine.get(Bytes::from("b")), Err(Errors::KeyNotFound));
+        assert_eq!(engine.get(Bytes::from("c")).unwrap(), "last");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_incomplete() {
+        let dir_path = std::env::temp_dir().join("fdb-write-batch-incomplete");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(Bytes::from("before"), Bytes::from("v")).unwrap();
+        let mut batch = engine.write_batch();
+        for i in 0..10 {
+            batch
+                .put(Bytes::from(format!("key-{}", i)), Bytes::from("v"))
+                .unwrap();
+        }
+        batch.commit().unwrap();
+        drop(engine);
+
+        // 模拟写入批量时崩溃，只有前面一部分记录写入了数据文件
+        let file_name = dir_path.join(format!("{:09}.data", 0));
+        let file = fs::OpenOptions::new().write(true).open(&file_name).unwrap();
+        let size = file.metadata().unwrap().len();
+        file.set_len(size - 30).unwrap();
+        drop(file);
+
+        let engine = Engine::open(opts.clone()).unwrap();
+        assert_eq!(engine.get(Bytes::from("before")).unwrap(), "v");
+        assert_eq!(engine.list_keys().unwrap().len(), 1);
+        engine.put(Bytes::from("after"), Bytes::from("v")).unwrap();
+        drop(engine);
+
+        // 截断之后的写入在重新打开之后仍然可见
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.list_keys().unwrap().len(), 2);
+        assert_eq!(engine.get(Bytes::from("after")).unwrap(), "v");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_write_batch_corrupted_older_file() {
+        let dir_path = std::env::temp_dir().join("fdb-write-batch-corrupted");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 256,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        let mut batch = engine.write_batch();
+        for i in 0..5 {
+            batch
+                .put(Bytes::from(format!("key-{}", i)), Bytes::from("v"))
+                .unwrap();
+        }
+        batch.commit().unwrap();
+        engine
+            .put(Bytes::from("large"), Bytes::from(vec![1u8; 300]))
+            .unwrap();
+        assert_eq!(engine.write_pos().file_id(), 1);
+        drop(engine);
+
+        // 旧数据文件中批量写入的最后一条记录损坏，不能当作没有写完的批量丢弃
+        let file_name = dir_path.join(format!("{:09}.data", 0));
+        let mut data = fs::read(&file_name).unwrap();
+        *data.last_mut().unwrap() ^= 0xff;
+        fs::write(&file_name, &data).unwrap();
+        assert_eq!(Engine::open(opts).err(), Some(Errors::InvalidLogRecordCrc));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
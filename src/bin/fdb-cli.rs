@@ -0,0 +1,127 @@
+use fdb::db::Engine;
+use fdb::options::Options;
+use fdb::structures::RedisDataStructure;
+use fdb::transfer::{Format, DEFAULT_IMPORT_BATCH_SIZE};
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const USAGE: &str = "usage: fdb-cli export --dir <database dir> [--format jsonl|csv] [--prefix <prefix>] [--output <file>]
+       fdb-cli import --dir <database dir> [--format jsonl|csv] [--batch-size <n>] [--input <file>]
+       fdb-cli import-rdb --dir <database dir> [--input <dump.rdb>]";
+
+struct Args {
+    command: String,
+    dir: Option<PathBuf>,
+    format: Format,
+    prefix: String,
+    file: Option<PathBuf>,
+    batch_size: usize,
+}
+
+fn main() {
+    env_logger::init();
+
+    let args = parse_args().unwrap_or_else(|| {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    });
+    let Some(dir_path) = args.dir.clone() else {
+        eprintln!("{}", USAGE);
+        std::process::exit(2);
+    };
+    // 导出时只读打开，可以与正在运行的服务同时访问同一个目录
+    let opts = Options {
+        dir_path,
+        read_only: args.command == "export",
+        ..Default::default()
+    };
+    let engine = match Engine::open(opts) {
+        Ok(engine) => engine,
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let res = match args.command.as_str() {
+        "export" => export(&engine, &args),
+        "import-rdb" => import_rdb(engine, &args),
+        _ => import(&engine, &args),
+    };
+    if let Err(e) = res {
+        eprintln!("{} failed: {}", args.command, e);
+        std::process::exit(1);
+    }
+}
+
+fn parse_args() -> Option<Args> {
+    let mut iter = std::env::args().skip(1);
+    let command = iter
+        .next()
+        .filter(|c| matches!(c.as_str(), "export" | "import" | "import-rdb"))?;
+    let mut args = Args {
+        command,
+        dir: None,
+        format: Format::JsonLines,
+        prefix: String::new(),
+        file: None,
+        batch_size: DEFAULT_IMPORT_BATCH_SIZE,
+    };
+    while let Some(arg) = iter.next() {
+        let value = iter.next()?;
+        match (arg.as_str(), args.command.as_str()) {
+            ("--dir", _) => args.dir = Some(PathBuf::from(value)),
+            ("--format", "export" | "import") => args.format = value.parse().ok()?,
+            ("--prefix", "export") => args.prefix = value,
+            ("--output", "export") | ("--input", "import" | "import-rdb") => {
+                args.file = Some(PathBuf::from(value))
+            }
+            ("--batch-size", "import") => args.batch_size = value.parse().ok()?,
+            _ => return None,
+        }
+    }
+    Some(args)
+}
+
+fn export(engine: &Engine, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let writer: Box<dyn Write> = match &args.file {
+        Some(path) => Box::new(File::create(path)?),
+        None => Box::new(io::stdout().lock()),
+    };
+    let count = engine.export(writer, args.format, args.prefix.as_bytes())?;
+    eprintln!("exported {} records", count);
+    Ok(())
+}
+
+fn import(engine: &Engine, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let reader: Box<dyn Read> = match &args.file {
+        Some(path) => Box::new(File::open(path)?),
+        None => Box::new(io::stdin().lock()),
+    };
+    let count = engine.import_with_progress(reader, args.format, args.batch_size, |n| {
+        eprint!("\rimported {} records", n);
+    })?;
+    eprintln!("\rimported {} records", count);
+    Ok(())
+}
+
+// RDB 中的数据写入为 Redis 数据结构，导入之后可以通过 fdb-redis 访问
+fn import_rdb(engine: Engine, args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let rds = RedisDataStructure::new(Arc::new(engine));
+    // 导入之前需要先读一遍文件检查校验和，标准输入不能重新读取，先读到内存中
+    let stat = match &args.file {
+        Some(path) => rds.import_rdb(File::open(path)?)?,
+        None => {
+            let mut buf = Vec::new();
+            io::stdin().lock().read_to_end(&mut buf)?;
+            rds.import_rdb(io::Cursor::new(buf))?
+        }
+    };
+    eprintln!(
+        "imported {} keys, skipped {} expired keys",
+        stat.keys, stat.expired
+    );
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use fdb::db::Engine;
+use fdb::grpc::server::KvService;
+use fdb::options::Options;
+use std::net::SocketAddr;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const USAGE: &str = "usage: fdb-grpc [--addr 127.0.0.1:50051] [--dir <database dir>]";
+
+#[tokio::main]
+async fn main() {
+    env_logger::init();
+
+    let mut addr = "127.0.0.1:50051".to_string();
+    let mut opts = Options {
+        dir_path: std::env::temp_dir().join("fdb-grpc"),
+        ..Default::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--addr", Some(v)) => addr = v,
+            ("--dir", Some(v)) => opts.dir_path = PathBuf::from(v),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+    let addr: SocketAddr = match addr.parse() {
+        Ok(addr) => addr,
+        Err(e) => {
+            eprintln!("invalid address {}: {}", addr, e);
+            std::process::exit(2);
+        }
+    };
+
+    let engine = match Engine::open(opts) {
+        Ok(engine) => Arc::new(engine),
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    log::info!("grpc server listening on {}", addr);
+    let res = tonic::transport::Server::builder()
+        .add_service(KvService::new(engine).into_server())
+        .serve(addr)
+        .await;
+    if let Err(e) = res {
+        eprintln!("grpc server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,47 @@
+use fdb::db::Engine;
+use fdb::memcached::server::MemcachedServer;
+use fdb::options::Options;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+const USAGE: &str = "usage: fdb-memcached [--addr 127.0.0.1:11211] [--dir <database dir>]";
+
+fn main() {
+    env_logger::init();
+
+    let mut addr = "127.0.0.1:11211".to_string();
+    let mut opts = Options {
+        dir_path: std::env::temp_dir().join("fdb-memcached"),
+        ..Default::default()
+    };
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--addr", Some(v)) => addr = v,
+            ("--dir", Some(v)) => opts.dir_path = PathBuf::from(v),
+            _ => {
+                eprintln!("{}", USAGE);
+                std::process::exit(2);
+            }
+        }
+    }
+
+    let engine = match Engine::open(opts) {
+        Ok(engine) => Arc::new(engine),
+        Err(e) => {
+            eprintln!("failed to open database: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let server = match MemcachedServer::bind(addr.as_str(), engine) {
+        Ok(server) => server,
+        Err(e) => {
+            eprintln!("failed to listen on {}: {}", addr, e);
+            std::process::exit(1);
+        }
+    };
+    if let Err(e) = server.serve() {
+        eprintln!("memcached server stopped: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,223 @@
+use crate::data::blob_file::{BlobFile, BlobRef, BLOB_FILE_NAME_SUFFIX};
+use crate::errors::{Errors, Result};
+use crate::options::KeyProvider;
+use bytes::Bytes;
+use parking_lot::{Mutex, RwLock};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// 单个 blob 文件的空间统计
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlobFileStat {
+    pub file_id: u32,
+    pub total_size: u64,   // 文件中写入的字节数
+    pub garbage_size: u64, // 已被覆盖、删除或者没有被引用的字节数
+}
+
+/// 管理数据目录中的 blob 文件，以及每个 blob 文件的垃圾统计
+///
+/// 每次打开引擎时，第一次写入大 value 才创建新的 blob 文件，未使用时不会创建任何文件。
+pub(crate) struct BlobStore {
+    dir_path: PathBuf,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    file_size: u64,
+    read_only: bool,
+    active: RwLock<Option<BlobFile>>,
+    older: RwLock<HashMap<u32, BlobFile>>,
+    next_file_id: Mutex<u32>,
+    // 引用了 blob 的 key 及其当前的 BlobRef，用于 key 被覆盖或删除时统计垃圾
+    refs: Mutex<HashMap<(u32, Vec<u8>), BlobRef>>,
+    // 每个 blob 文件仍被引用的字节数
+    live_sizes: Mutex<HashMap<u32, u64>>,
+}
+
+impl BlobStore {
+    pub(crate) fn open(
+        dir_path: PathBuf,
+        key_provider: Option<&Arc<dyn KeyProvider>>,
+        file_size: u64,
+        read_only: bool,
+    ) -> Result<Self> {
+        let dir = fs::read_dir(&dir_path).map_err(|_| Errors::FailedToReadDatabaseDir)?;
+        let mut older = HashMap::new();
+        let mut next_file_id = 0;
+        for entry in dir.flatten() {
+            let file_name = entry.file_name();
+            let Some(name) = file_name.to_str() else {
+                continue;
+            };
+            let Some(id) = name.strip_suffix(BLOB_FILE_NAME_SUFFIX) else {
+                continue;
+            };
+            let file_id = id
+                .parse::<u32>()
+                .map_err(|_| Errors::DataDirectoryCorrupted)?;
+            let blob_file = BlobFile::new(dir_path.clone(), file_id, key_provider, read_only)?;
+            older.insert(file_id, blob_file);
+            next_file_id = next_file_id.max(file_id + 1);
+        }
+
+        Ok(Self {
+            dir_path,
+            key_provider: key_provider.cloned(),
+            file_size,
+            read_only,
+            active: RwLock::new(None),
+            older: RwLock::new(older),
+            next_file_id: Mutex::new(next_file_id),
+            refs: Mutex::new(HashMap::new()),
+            live_sizes: Mutex::new(HashMap::new()),
+        })
+    }
+
+    // 写入 value，活跃 blob 文件写满时切换到新的文件
+    pub(crate) fn write(&self, value: &[u8]) -> Result<BlobRef> {
+        let mut active = self.active.write();
+        self.prepare_active(&mut active, value.len() as u64)?;
+        active.as_mut().unwrap().write(value)
+    }
+
+    // 从 reader 中流式写入 len 个字节的 value
+    pub(crate) fn write_stream(&self, reader: &mut dyn Read, len: u64) -> Result<BlobRef> {
+        let mut active = self.active.write();
+        self.prepare_active(&mut active, len)?;
+        active.as_mut().unwrap().write_stream(reader, len)
+    }
+
+    // 确保活跃 blob 文件存在，并且能够写入 len 个字节，写满时切换到新的文件
+    fn prepare_active(&self, active: &mut Option<BlobFile>, len: u64) -> Result<()> {
+        let need_new = match active.as_ref() {
+            Some(blob_file) => {
+                blob_file.get_write_off() > 0 && blob_file.get_write_off() + len > self.file_size
+            }
+            None => true,
+        };
+        if need_new {
+            let mut next_file_id = self.next_file_id.lock();
+            let new_file = BlobFile::new(
+                self.dir_path.clone(),
+                *next_file_id,
+                self.key_provider.as_ref(),
+                false,
+            )?;
+            *next_file_id += 1;
+            if let Some(mut old_file) = active.replace(new_file) {
+                old_file.sync()?;
+                self.older.write().insert(old_file.get_file_id(), old_file);
+            }
+        }
+        Ok(())
+    }
+
+    // 读取 BlobRef 对应的 value 并校验
+    pub(crate) fn read(&self, blob_ref: &BlobRef) -> Result<Bytes> {
+        self.with_file(blob_ref.file_id, |blob_file| {
+            blob_file.read(blob_ref).map(Bytes::from)
+        })
+    }
+
+    // 从 blob 文件的offset处读取原始数据，不做校验
+    pub(crate) fn read_at(&self, file_id: u32, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.with_file(file_id, |blob_file| blob_file.read_at(buf, offset))
+    }
+
+    // 对 file_id 对应的 blob 文件执行 f，只读模式下会按需打开写入进程新创建的 blob 文件
+    fn with_file<T, F>(&self, file_id: u32, f: F) -> Result<T>
+    where
+        F: FnOnce(&BlobFile) -> Result<T>,
+    {
+        if let Some(blob_file) = self.active.read().as_ref() {
+            if blob_file.get_file_id() == file_id {
+                return f(blob_file);
+            }
+        }
+        if let Some(blob_file) = self.older.read().get(&file_id) {
+            return f(blob_file);
+        }
+
+        let blob_file = BlobFile::new(
+            self.dir_path.clone(),
+            file_id,
+            self.key_provider.as_ref(),
+            true,
+        )?;
+        let res = f(&blob_file);
+        if self.read_only {
+            self.older.write().insert(file_id, blob_file);
+        }
+        res
+    }
+
+    pub(crate) fn sync(&self) -> Result<()> {
+        match self.active.write().as_mut() {
+            Some(blob_file) => blob_file.sync(),
+            None => Ok(()),
+        }
+    }
+
+    // 记录列族 cf 中 key 当前引用的 blob，key 之前引用的 blob 成为垃圾，blob_ref 为 None 表示不再引用
+    pub(crate) fn track(&self, cf: u32, key: &[u8], blob_ref: Option<BlobRef>) {
+        let mut refs = self.refs.lock();
+        let old = match blob_ref {
+            Some(blob_ref) => refs.insert((cf, key.to_vec()), blob_ref),
+            None => refs.remove(&(cf, key.to_vec())),
+        };
+        // 没有引用过 blob 的 key，不需要更新统计
+        if old.is_none() && blob_ref.is_none() {
+            return;
+        }
+        let mut live_sizes = self.live_sizes.lock();
+        if let Some(old) = old {
+            if let Some(size) = live_sizes.get_mut(&old.file_id) {
+                *size = size.saturating_sub(old.len);
+            }
+        }
+        if let Some(blob_ref) = blob_ref {
+            *live_sizes.entry(blob_ref.file_id).or_default() += blob_ref.len;
+        }
+    }
+
+    // 列族被删除之后，其中的 key 引用的 blob 全部成为垃圾
+    pub(crate) fn untrack_cf(&self, cf: u32) {
+        let mut refs = self.refs.lock();
+        let mut live_sizes = self.live_sizes.lock();
+        refs.retain(|(id, _), blob_ref| {
+            if *id != cf {
+                return true;
+            }
+            if let Some(size) = live_sizes.get_mut(&blob_ref.file_id) {
+                *size = size.saturating_sub(blob_ref.len);
+            }
+            false
+        });
+    }
+
+    pub(crate) fn stats(&self) -> Vec<BlobFileStat> {
+        let mut total_sizes: Vec<(u32, u64)> = self
+            .older
+            .read()
+            .values()
+            .map(|blob_file| (blob_file.get_file_id(), blob_file.get_write_off()))
+            .collect();
+        if let Some(blob_file) = self.active.read().as_ref() {
+            total_sizes.push((blob_file.get_file_id(), blob_file.get_write_off()));
+        }
+        total_sizes.sort();
+
+        let live_sizes = self.live_sizes.lock();
+        total_sizes
+            .into_iter()
+            .map(|(file_id, total_size)| {
+                let live_size = live_sizes.get(&file_id).copied().unwrap_or(0);
+                BlobFileStat {
+                    file_id,
+                    total_size,
+                    garbage_size: total_size.saturating_sub(live_size),
+                }
+            })
+            .collect()
+    }
+}
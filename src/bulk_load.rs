@@ -0,0 +1,463 @@
+use crate::column_family::DEFAULT_CF_ID;
+use crate::data::compression::maybe_compress;
+use crate::data::data_file::{get_data_file_name, DataFile};
+use crate::data::log_record::LogRecordType::NORMAL;
+use crate::data::log_record::{LogRecord, LogRecordPos};
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::warn;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// 批量导入时写入数据文件的临时目录，位于数据目录中
+const BULK_LOAD_DIR_NAME: &str = "bulk-load";
+// 接入清单，写入之后临时目录中的数据文件一定会被接入数据库
+const ATTACH_MANIFEST_NAME: &str = "ATTACH";
+// 写缓冲区的大小，缓冲区满了之后一次写入数据文件
+const WRITE_BUFFER_SIZE: usize = 4 * 1024 * 1024; // 4MB
+                                                  // 为导入期间的其他写入预留的序列号数量，导入的数据使用这之后的序列号
+const BULK_LOAD_SEQ_GAP: u64 = 1 << 32;
+
+/// 批量导入按 key 升序排列的数据，通过 Engine::bulk_loader 创建
+///
+/// 数据先写入临时目录中的数据文件，不经过活跃文件也不更新索引；finish 时把这些文件整体接入数据库，
+/// 再一次性建立索引。finish 之前导入的数据都不可见，没有调用 finish 就 drop 时丢弃导入的数据。
+/// value 按配置压缩，但不会单独存放到 blob 文件中。
+///
+/// 导入的数据在创建时预留的序列号范围之后编号，因此版本号大于导入期间其他写入的版本号；
+/// 导入期间的其他写入超过 2^32 次时 finish 返回 BulkLoadSeqExhausted。
+pub struct BulkLoader<'a> {
+    engine: &'a Engine,
+    staging_path: PathBuf,
+    // 当前写入的数据文件，临时目录中的文件 id 从 0 开始
+    data_file: DataFile,
+    buf: Vec<u8>,
+    // 最后导入的 key 以及已导入的数量，索引在 finish 时从数据文件中读取建立
+    last_key: Option<Vec<u8>>,
+    key_num: usize,
+    // 导入的第一条数据的序列号，以及最后分配的序列号
+    first_seq: u64,
+    last_seq: u64,
+    finished: bool,
+}
+
+impl Engine {
+    /// 创建批量导入器，同一时间只能有一个批量导入
+    pub fn bulk_loader(&self) -> Result<BulkLoader<'_>> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        let staging_path = self.options().dir_path.join(BULK_LOAD_DIR_NAME);
+        if let Err(e) = fs::create_dir(&staging_path) {
+            return Err(match e.kind() {
+                std::io::ErrorKind::AlreadyExists => Errors::BulkLoadInProgress,
+                _ => {
+                    warn!("create bulk load directory err:{}", e);
+                    Errors::FailedToCreateDatabaseDir
+                }
+            });
+        }
+        let key_provider = self.options().key_provider.as_ref();
+        let data_file = match DataFile::new(staging_path.clone(), 0, key_provider) {
+            Ok(data_file) => data_file,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&staging_path);
+                return Err(e);
+            }
+        };
+        let last_seq = self.current_seq() + BULK_LOAD_SEQ_GAP;
+        Ok(BulkLoader {
+            engine: self,
+            staging_path,
+            data_file,
+            buf: Vec::with_capacity(WRITE_BUFFER_SIZE),
+            last_key: None,
+            key_num: 0,
+            first_seq: last_seq + 1,
+            last_seq,
+            finished: false,
+        })
+    }
+}
+
+impl BulkLoader<'_> {
+    /// 导入一条数据，key 必须严格大于之前导入的 key
+    pub fn add(&mut self, key: Bytes, value: Bytes) -> Result<()> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        if let Some(last_key) = self.last_key.as_ref() {
+            if key.as_ref() <= last_key.as_slice() {
+                return Err(Errors::BulkLoadKeyNotSorted);
+            }
+        }
+
+        let options = self.engine.options();
+        let (value, compression) =
+            maybe_compress(options.compression, options.compression_threshold, &value)?;
+        let record = LogRecord {
+            key: key.to_vec(),
+            value,
+            rec_type: NORMAL,
+            compression,
+            seq: self.last_seq + 1,
+            cf: DEFAULT_CF_ID,
+            expire: 0,
+        };
+        let enc_record = record.encode();
+
+        // 当前文件写满之后切换到新的文件
+        let offset = self.data_file.get_write_off() + self.buf.len() as u64;
+        if offset > 0 && offset + enc_record.len() as u64 > options.data_file_size {
+            self.flush()?;
+            self.data_file.sync()?;
+            let file_id = self.data_file.get_file_id() + 1;
+            let key_provider = options.key_provider.as_ref();
+            self.data_file = DataFile::new(self.staging_path.clone(), file_id, key_provider)?;
+        }
+        self.buf.extend_from_slice(&enc_record);
+        self.last_seq = record.seq;
+        self.last_key = Some(record.key);
+        self.key_num += 1;
+        if self.buf.len() >= WRITE_BUFFER_SIZE {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// 依次导入 iter 中的所有数据
+    pub fn add_all<I>(&mut self, iter: I) -> Result<()>
+    where
+        I: IntoIterator<Item = (Bytes, Bytes)>,
+    {
+        for (key, value) in iter {
+            self.add(key, value)?;
+        }
+        Ok(())
+    }
+
+    /// 把导入的数据文件接入数据库并建立索引，返回导入的 key 的数量
+    ///
+    /// 接入之后导入的数据覆盖数据库中相同 key 的旧值，接入过程中崩溃时，重新打开数据库会完成接入。
+    pub fn finish(mut self) -> Result<usize> {
+        if self.key_num == 0 {
+            return Ok(0);
+        }
+        self.flush()?;
+        self.data_file.sync()?;
+
+        let engine = self.engine;
+        let count = self.data_file.get_file_id() + 1;
+        let _guard = engine.lock_writes();
+        // 导入期间其他写入的序列号已经用到了预留给导入数据的范围
+        if engine.current_seq() >= self.first_seq {
+            return Err(Errors::BulkLoadSeqExhausted);
+        }
+        engine.skip_seq(self.last_seq);
+        let first_id = engine.attach_data_files(count, |first_id| {
+            let dir_path = &engine.options().dir_path;
+            write_manifest(&self.staging_path, first_id, count)?;
+            // 清单写入之后不能再丢弃临时目录，接入失败时由重新打开完成
+            self.finished = true;
+            install_data_files(&self.staging_path, dir_path, first_id, count)
+        })?;
+        if let Err(e) = fs::remove_dir_all(&self.staging_path) {
+            warn!("remove bulk load directory err:{}", e);
+        }
+
+        // 顺序读取接入的数据文件，按 key 的顺序建立索引
+        let mut seq = self.first_seq;
+        for file_id in first_id..first_id + count {
+            let mut offset = 0;
+            loop {
+                let header = match engine.with_data_file(file_id, |data_file| {
+                    data_file.read_log_record_header(offset)
+                }) {
+                    Ok(header) => header,
+                    Err(Errors::ReadDataFileEOF) => break,
+                    Err(e) => return Err(e),
+                };
+                engine.blobs.track(DEFAULT_CF_ID, &header.key, None);
+                let pos = LogRecordPos::with_seq(file_id, offset, seq);
+                offset = header.value_offset + header.value_size as u64 + 4;
+                seq += 1;
+                if !engine.index.put(header.key, pos) {
+                    return Err(Errors::IndexUpdateFailed);
+                }
+            }
+        }
+        Ok(self.key_num)
+    }
+
+    // 把缓冲区中的数据写入当前的数据文件
+    fn flush(&mut self) -> Result<()> {
+        if !self.buf.is_empty() {
+            self.data_file.write(&self.buf)?;
+            self.buf.clear();
+        }
+        Ok(())
+    }
+}
+
+impl Drop for BulkLoader<'_> {
+    fn drop(&mut self) {
+        // 没有完成的导入直接丢弃
+        if !self.finished {
+            let _ = fs::remove_dir_all(&self.staging_path);
+        }
+    }
+}
+
+// 打开数据库时处理上次遗留的临时目录，已经写入清单的继续完成接入，否则丢弃
+pub(crate) fn recover(dir_path: &Path) -> Result<()> {
+    let staging_path = dir_path.join(BULK_LOAD_DIR_NAME);
+    if !staging_path.is_dir() {
+        return Ok(());
+    }
+    if let Some((first_id, count)) = read_manifest(&staging_path)? {
+        install_data_files(&staging_path, dir_path, first_id, count)?;
+    }
+    if let Err(e) = fs::remove_dir_all(&staging_path) {
+        warn!("remove bulk load directory err:{}", e);
+        return Err(Errors::FailedToAttachDataFiles);
+    }
+    Ok(())
+}
+
+// 清单中记录接入后的第一个文件 id 以及文件的数量，先写临时文件再改名，保证清单是完整的
+fn write_manifest(staging_path: &Path, first_id: u32, count: u32) -> Result<()> {
+    let mut buf = BytesMut::with_capacity(8);
+    buf.put_u32(first_id);
+    buf.put_u32(count);
+    let tmp_path = staging_path.join(format!("{}.tmp", ATTACH_MANIFEST_NAME));
+    let res = fs::write(&tmp_path, &buf)
+        .and_then(|_| fs::File::open(&tmp_path)?.sync_all())
+        .and_then(|_| fs::rename(&tmp_path, staging_path.join(ATTACH_MANIFEST_NAME)));
+    if let Err(e) = res {
+        warn!("write bulk load manifest err:{}", e);
+        return Err(Errors::FailedToAttachDataFiles);
+    }
+    Ok(())
+}
+
+fn read_manifest(staging_path: &Path) -> Result<Option<(u32, u32)>> {
+    let Ok(data) = fs::read(staging_path.join(ATTACH_MANIFEST_NAME)) else {
+        return Ok(None);
+    };
+    if data.len() != 8 {
+        return Err(Errors::DataDirectoryCorrupted);
+    }
+    let mut buf = data.as_slice();
+    Ok(Some((buf.get_u32(), buf.get_u32())))
+}
+
+// 把临时目录中的数据文件改名为 first_id 开始的数据文件，已经改名的文件跳过
+fn install_data_files(
+    staging_path: &Path,
+    dir_path: &Path,
+    first_id: u32,
+    count: u32,
+) -> Result<()> {
+    for i in 0..count {
+        let from = get_data_file_name(staging_path.to_path_buf(), i);
+        if !from.is_file() {
+            continue;
+        }
+        let to = get_data_file_name(dir_path.to_path_buf(), first_id + i);
+        if let Err(e) = fs::rename(from, to) {
+            warn!("attach bulk loaded data file err:{}", e);
+            return Err(Errors::FailedToAttachDataFiles);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+
+    fn test_key(i: usize) -> Bytes {
+        Bytes::from(format!("key-{:05}", i))
+    }
+
+    #[test]
+    fn test_bulk_loader() {
+        let dir_path = std::env::temp_dir().join("fdb-bulk-loader");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 8 * 1024,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(test_key(1), Bytes::from("old")).unwrap();
+        engine
+            .put(Bytes::from("other"), Bytes::from("kept"))
+            .unwrap();
+
+        let mut loader = engine.bulk_loader().unwrap();
+        assert!(matches!(
+            engine.bulk_loader(),
+            Err(Errors::BulkLoadInProgress)
+        ));
+        loader
+            .add_all((0..1000).map(|i| (test_key(i), Bytes::from(format!("value-{}", i)))))
+            .unwrap();
+        // 导入完成之前数据不可见，其他写入不受影响
+        assert_eq!(engine.get(test_key(1)).unwrap(), "old");
+        assert_eq!(engine.get(test_key(2)), Err(Errors::KeyNotFound));
+        engine
+            .put(Bytes::from("during"), Bytes::from("load"))
+            .unwrap();
+        assert_eq!(loader.finish().unwrap(), 1000);
+        assert!(!dir_path.join(BULK_LOAD_DIR_NAME).exists());
+
+        assert_eq!(engine.get(test_key(1)).unwrap(), "value-1");
+        assert_eq!(engine.get(test_key(999)).unwrap(), "value-999");
+        assert_eq!(engine.get(Bytes::from("during")).unwrap(), "load");
+        assert!(engine.stat().unwrap().data_file_num > 3);
+        // 导入之后的写入覆盖导入的数据
+        engine.put(test_key(2), Bytes::from("new")).unwrap();
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.stat().unwrap().key_num, 1002);
+        assert_eq!(engine.get(test_key(1)).unwrap(), "value-1");
+        assert_eq!(engine.get(test_key(2)).unwrap(), "new");
+        assert_eq!(engine.get(test_key(500)).unwrap(), "value-500");
+        assert_eq!(engine.get(Bytes::from("other")).unwrap(), "kept");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_loader_version() {
+        let dir_path = std::env::temp_dir().join("fdb-bulk-loader-version");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+
+        // 导入期间写入相同的 key，导入完成后导入的数据覆盖它，版本号也更大
+        let mut loader = engine.bulk_loader().unwrap();
+        loader.add(test_key(1), Bytes::from("loaded")).unwrap();
+        let during = engine
+            .put_if_version(test_key(1), Bytes::from("during"), 0)
+            .unwrap();
+        assert_eq!(loader.finish().unwrap(), 1);
+        let (value, version) = engine.get_with_version(test_key(1)).unwrap();
+        assert_eq!(value, "loaded");
+        assert!(version > during);
+        assert_eq!(
+            engine.put_if_version(test_key(1), Bytes::from("stale"), during),
+            Err(Errors::VersionConflict)
+        );
+        let after = engine
+            .put_if_version(test_key(1), Bytes::from("after"), version)
+            .unwrap();
+        assert!(after > version);
+        drop(engine);
+
+        // 重新打开之后新的写入的版本号仍然更大
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(
+            engine.get_with_version(test_key(1)).unwrap(),
+            (Bytes::from("after"), after)
+        );
+        let version = engine
+            .put_if_version(test_key(1), Bytes::from("new"), after)
+            .unwrap();
+        assert!(version > after);
+
+        // 导入期间其他写入用完了预留的序列号
+        let mut loader = engine.bulk_loader().unwrap();
+        loader.add(test_key(2), Bytes::from("loaded")).unwrap();
+        engine.skip_seq(loader.first_seq);
+        assert_eq!(loader.finish(), Err(Errors::BulkLoadSeqExhausted));
+        assert!(!dir_path.join(BULK_LOAD_DIR_NAME).exists());
+        assert_eq!(engine.get(test_key(2)), Err(Errors::KeyNotFound));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_loader_unsorted_and_abort() {
+        let dir_path = std::env::temp_dir().join("fdb-bulk-loader-abort");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut loader = engine.bulk_loader().unwrap();
+        loader.add(test_key(2), Bytes::from("a")).unwrap();
+        assert_eq!(
+            loader.add(test_key(1), Bytes::from("b")),
+            Err(Errors::BulkLoadKeyNotSorted)
+        );
+        assert_eq!(
+            loader.add(test_key(2), Bytes::from("b")),
+            Err(Errors::BulkLoadKeyNotSorted)
+        );
+        // 没有 finish 时丢弃导入的数据
+        drop(loader);
+        assert!(!dir_path.join(BULK_LOAD_DIR_NAME).exists());
+        assert_eq!(engine.get(test_key(2)), Err(Errors::KeyNotFound));
+
+        let loader = engine.bulk_loader().unwrap();
+        assert_eq!(loader.finish().unwrap(), 0);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_bulk_load_recover() {
+        let dir_path = std::env::temp_dir().join("fdb-bulk-load-recover");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 4 * 1024,
+            ..Default::default()
+        };
+
+        // 写入清单之后崩溃，重新打开时完成接入
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(test_key(0), Bytes::from("old")).unwrap();
+        let mut loader = engine.bulk_loader().unwrap();
+        loader
+            .add_all((0..200).map(|i| (test_key(i), Bytes::from("loaded"))))
+            .unwrap();
+        loader.flush().unwrap();
+        let count = loader.data_file.get_file_id() + 1;
+        assert!(count > 1);
+        write_manifest(&loader.staging_path, 1, count).unwrap();
+        // 只接入了一部分文件
+        install_data_files(&loader.staging_path, &dir_path, 1, 1).unwrap();
+        std::mem::forget(loader);
+        drop(engine);
+
+        let engine = Engine::open(opts.clone()).unwrap();
+        assert!(!dir_path.join(BULK_LOAD_DIR_NAME).exists());
+        assert_eq!(engine.stat().unwrap().key_num, 200);
+        assert_eq!(engine.get(test_key(0)).unwrap(), "loaded");
+        assert_eq!(engine.get(test_key(199)).unwrap(), "loaded");
+
+        // 没有写入清单时丢弃临时目录
+        let mut loader = engine.bulk_loader().unwrap();
+        loader.add(test_key(300), Bytes::from("lost")).unwrap();
+        loader.flush().unwrap();
+        std::mem::forget(loader);
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        assert!(!dir_path.join(BULK_LOAD_DIR_NAME).exists());
+        assert_eq!(engine.get(test_key(300)), Err(Errors::KeyNotFound));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
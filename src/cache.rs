@@ -0,0 +1,127 @@
+use crate::data::log_record::LogRecordPos;
+use bytes::Bytes;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// value 缓存，按照数据位置缓存解码后的 value
+/// 数据文件只追加写，同一个位置的数据永远不会改变，因此缓存不需要主动失效
+pub struct ValueCache {
+    inner: Mutex<CacheInner>,
+    capacity: usize, // 缓存value的总字节数上限
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CacheInner {
+    lru: LruCache<LogRecordPos, Bytes>,
+    size: usize, // 当前缓存value的总字节数
+}
+
+/// 缓存的统计信息
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+    pub size: usize,
+}
+
+impl ValueCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Mutex::new(CacheInner {
+                lru: LruCache::unbounded(),
+                size: 0,
+            }),
+            capacity,
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub fn get(&self, pos: &LogRecordPos) -> Option<Bytes> {
+        let value = self.inner.lock().lru.get(pos).cloned();
+        match value.is_some() {
+            true => self.hits.fetch_add(1, Ordering::Relaxed),
+            false => self.misses.fetch_add(1, Ordering::Relaxed),
+        };
+        value
+    }
+
+    pub fn put(&self, pos: LogRecordPos, value: Bytes) {
+        // 超过容量的value不缓存，避免把其他数据全部淘汰
+        if value.len() > self.capacity {
+            return;
+        }
+        let mut inner = self.inner.lock();
+        inner.size += value.len();
+        if let Some(old) = inner.lru.put(pos, value) {
+            inner.size -= old.len();
+        }
+        // 超出容量时淘汰最久未使用的数据
+        while inner.size > self.capacity {
+            match inner.lru.pop_lru() {
+                Some((_, v)) => inner.size -= v.len(),
+                None => break,
+            }
+        }
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let inner = self.inner.lock();
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: inner.lru.len(),
+            size: inner.size,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pos(file_id: u32, offset: u64) -> LogRecordPos {
+        LogRecordPos::new(file_id, offset)
+    }
+
+    #[test]
+    fn test_value_cache_get_put() {
+        let cache = ValueCache::new(1024);
+        assert!(cache.get(&pos(1, 0)).is_none());
+
+        cache.put(pos(1, 0), Bytes::from("value-a"));
+        cache.put(pos(1, 20), Bytes::from("value-b"));
+        assert_eq!(cache.get(&pos(1, 0)), Some(Bytes::from("value-a")));
+        assert_eq!(cache.get(&pos(1, 20)), Some(Bytes::from("value-b")));
+        assert!(cache.get(&pos(2, 0)).is_none());
+
+        let stats = cache.stats();
+        assert_eq!(stats.hits, 2);
+        assert_eq!(stats.misses, 2);
+        assert_eq!(stats.entries, 2);
+        assert_eq!(stats.size, 14);
+    }
+
+    #[test]
+    fn test_value_cache_evict() {
+        let cache = ValueCache::new(10);
+        cache.put(pos(1, 0), Bytes::from("aaaa"));
+        cache.put(pos(1, 4), Bytes::from("bbbb"));
+        // 访问之后a变为最近使用
+        assert!(cache.get(&pos(1, 0)).is_some());
+
+        cache.put(pos(1, 8), Bytes::from("cccc"));
+        assert!(cache.get(&pos(1, 4)).is_none());
+        assert!(cache.get(&pos(1, 0)).is_some());
+        assert!(cache.get(&pos(1, 8)).is_some());
+        assert_eq!(cache.stats().size, 8);
+
+        // 超过容量的value不缓存
+        cache.put(pos(1, 12), Bytes::from("too-large-value"));
+        assert!(cache.get(&pos(1, 12)).is_none());
+        assert_eq!(cache.stats().entries, 2);
+    }
+}
@@ -0,0 +1,305 @@
+use crate::column_family::{decode_cf_meta, DEFAULT_CF_ID, SYSTEM_CF_ID};
+use crate::data::log_record::{LogRecord, LogRecordPos, LogRecordType};
+use crate::db::{pos_covers, Engine};
+use crate::errors::{Errors, Result};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+// 阻塞迭代以及 Watcher::recv 每次等待的时间
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// 变更所属的对象
+#[derive(Clone, Debug, PartialEq)]
+pub enum ChangeTarget {
+    Default,              // 默认列族中的 key
+    ColumnFamily(String), // 指定名称的列族中的 key
+    Catalog,              // 列族的创建和删除，key 为列族名称，创建时 value 为列族的元数据
+}
+
+/// 一次数据变更
+#[derive(Clone, Debug, PartialEq)]
+pub struct ChangeEvent {
+    pub target: ChangeTarget,
+    pub key: Bytes,
+    pub value: Option<Bytes>, // 删除操作为 None
+    pub expire: u64,          // 过期时间，毫秒时间戳，0 表示不过期
+    pub pos: LogRecordPos,    // 该记录在数据文件中的位置
+    pub next: LogRecordPos,   // 该记录之后的位置，保存该位置即可在重启后继续订阅
+}
+
+/// 变更订阅，按写入顺序返回已写入数据文件的变更
+///
+/// 从指定位置开始订阅时，先读取数据文件中已有的记录，追上之后等待新的写入。
+pub struct Subscription<'a> {
+    engine: &'a Engine,
+    pos: LogRecordPos,
+    // 列族 id 到名称的映射，读到列族的创建和删除记录时更新
+    families: HashMap<u32, String>,
+}
+
+impl Engine {
+    /// 从当前的写入位置开始订阅之后的变更
+    pub fn subscribe(&self) -> Subscription<'_> {
+        Subscription {
+            engine: self,
+            pos: self.write_pos(),
+            families: self.families.read().names(),
+        }
+    }
+
+    /// 从指定位置开始订阅，位置通常来自上一次消费的 ChangeEvent::next
+    pub fn subscribe_from(&self, pos: LogRecordPos) -> Result<Subscription<'_>> {
+        if !pos_covers(&self.write_pos(), &pos) {
+            return Err(Errors::InvalidLogRecordPos);
+        }
+        Ok(Subscription {
+            engine: self,
+            pos,
+            families: self.families.read().names(),
+        })
+    }
+}
+
+impl Subscription<'_> {
+    /// 下一条要读取的位置
+    pub fn position(&self) -> LogRecordPos {
+        self.pos
+    }
+
+    /// 读取下一条变更，超时仍没有新的写入时返回 None
+    pub fn next_timeout(&mut self, timeout: Duration) -> Result<Option<ChangeEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            // 只读取已完整写入的记录，流式写入时活跃文件末尾可能有写了一半的记录
+            let read = match self.pos == self.engine.write_pos() {
+                true => Err(Errors::ReadDataFileEOF),
+                false => self.engine.read_log_record_at(&self.pos),
+            };
+            let read = match read {
+                Ok(read) => read,
+                Err(Errors::ReadDataFileEOF) => {
+                    // 当前文件已读完，继续读取下一个文件
+                    if let Some(file_id) = self.engine.next_file_id(self.pos.file_id) {
+                        self.pos = LogRecordPos::new(file_id, 0);
+                        continue;
+                    }
+                    // 已经追上最新的写入，等待新数据
+                    let now = Instant::now();
+                    if now >= deadline {
+                        return Ok(None);
+                    }
+                    self.engine.wait_for_write(self.pos, deadline - now);
+                    continue;
+                }
+                Err(e) => return Err(e),
+            };
+
+            // 跳过批量写入的标记，以及已经被删除的列族中的记录
+            let target = match read.record.rec_type {
+                LogRecordType::BATCH => None,
+                _ => self.change_target(&read.record)?,
+            };
+            let Some(target) = target else {
+                self.pos.offset += read.size as u64;
+                continue;
+            };
+            let value = match read.record.rec_type {
+                LogRecordType::NORMAL => Some(Bytes::from(read.record.value)),
+                LogRecordType::BLOB => Some(self.engine.read_blob(&read.record.value)?),
+                LogRecordType::DELETE | LogRecordType::BATCH => None,
+                // 返回合并之后的完整值
+                LogRecordType::MERGE => Some(self.engine.get_value_by_position(&self.pos)?),
+            };
+            let pos = self.pos;
+            self.pos.offset += read.size as u64;
+            return Ok(Some(ChangeEvent {
+                target,
+                key: Bytes::from(read.record.key),
+                value,
+                expire: read.record.expire,
+                pos,
+                next: self.pos,
+            }));
+        }
+    }
+
+    // 记录所属的对象，列族已经被删除时返回 None
+    fn change_target(&mut self, record: &LogRecord) -> Result<Option<ChangeTarget>> {
+        let target = match record.cf {
+            DEFAULT_CF_ID => ChangeTarget::Default,
+            SYSTEM_CF_ID => {
+                let name = String::from_utf8(record.key.clone())
+                    .map_err(|_| Errors::InvalidColumnFamilyRecord)?;
+                match record.rec_type {
+                    LogRecordType::DELETE => self.families.retain(|_, n| *n != name),
+                    _ => {
+                        let (id, _) = decode_cf_meta(&record.value)?;
+                        self.families.insert(id, name);
+                    }
+                }
+                ChangeTarget::Catalog
+            }
+            id => match self.families.get(&id) {
+                Some(name) => ChangeTarget::ColumnFamily(name.clone()),
+                None => return Ok(None),
+            },
+        };
+        Ok(Some(target))
+    }
+}
+
+/// 阻塞迭代，没有新的写入时一直等待，读取出错时返回错误
+impl Iterator for Subscription<'_> {
+    type Item = Result<ChangeEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.next_timeout(POLL_INTERVAL) {
+                Ok(Some(event)) => return Some(Ok(event)),
+                Ok(None) => continue,
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{ColumnFamilyOptions, Options};
+    use std::fs;
+
+    #[test]
+    fn test_subscribe_live() {
+        let dir_path = std::env::temp_dir().join("fdb-cdc-live");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        engine.put(Bytes::from("old"), Bytes::from("1")).unwrap();
+
+        let mut sub = engine.subscribe();
+        assert_eq!(sub.next_timeout(Duration::from_millis(10)), Ok(None));
+
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                engine.put(Bytes::from("a"), Bytes::from("1")).unwrap();
+                engine.delete(Bytes::from("a")).unwrap();
+            });
+            let event = sub.next().unwrap().unwrap();
+            assert_eq!(event.key, Bytes::from("a"));
+            assert_eq!(event.value, Some(Bytes::from("1")));
+            let event = sub.next().unwrap().unwrap();
+            assert_eq!(event.key, Bytes::from("a"));
+            assert_eq!(event.value, None);
+        });
+        assert_eq!(sub.position(), engine.write_pos());
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_subscribe_from_position() {
+        let dir_path = std::env::temp_dir().join("fdb-cdc-tail");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 128,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        for i in 0..20 {
+            let key = Bytes::from(format!("key-{:02}", i));
+            engine.put(key, Bytes::from("value")).unwrap();
+        }
+        assert!(engine.write_pos().file_id() > 0);
+
+        // 读取历史数据，并记录消费到的位置
+        let mut sub = engine.subscribe_from(LogRecordPos::default()).unwrap();
+        let mut checkpoint = LogRecordPos::default();
+        for i in 0..10 {
+            let event = sub.next_timeout(Duration::ZERO).unwrap().unwrap();
+            assert_eq!(event.key, Bytes::from(format!("key-{:02}", i)));
+            // 跨越数据文件时，下一条记录位于下一个文件的开头
+            assert!(event.pos == checkpoint || event.pos.offset() == 0);
+            checkpoint = event.next;
+        }
+        drop(engine);
+
+        // 重启之后从检查点继续，读完历史数据之后接收新的写入
+        let engine = Engine::open(opts).unwrap();
+        let checkpoint = LogRecordPos::new(checkpoint.file_id(), checkpoint.offset());
+        let mut sub = engine.subscribe_from(checkpoint).unwrap();
+        engine.delete(Bytes::from("key-00")).unwrap();
+        for i in 10..20 {
+            let event = sub.next_timeout(Duration::ZERO).unwrap().unwrap();
+            assert_eq!(event.key, Bytes::from(format!("key-{:02}", i)));
+        }
+        let event = sub.next_timeout(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(event.key, Bytes::from("key-00"));
+        assert_eq!(event.value, None);
+        assert_eq!(sub.next_timeout(Duration::ZERO), Ok(None));
+
+        // 不能从尚未写入的位置开始订阅
+        let write_pos = engine.write_pos();
+        let beyond = LogRecordPos::new(write_pos.file_id(), write_pos.offset() + 1);
+        assert!(matches!(
+            engine.subscribe_from(beyond),
+            Err(Errors::InvalidLogRecordPos)
+        ));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_subscribe_column_family() {
+        let dir_path = std::env::temp_dir().join("fdb-cdc-column-family");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let start = engine.write_pos();
+        engine
+            .create_cf("users", ColumnFamilyOptions::default())
+            .unwrap();
+        let created = engine.write_pos();
+        let users = engine.cf("users").unwrap();
+        users.put(Bytes::from("k"), Bytes::from("v")).unwrap();
+        engine
+            .put(Bytes::from("k"), Bytes::from("default"))
+            .unwrap();
+        engine.drop_cf("users").unwrap();
+
+        let mut sub = engine.subscribe_from(start).unwrap();
+        let mut next = || sub.next_timeout(Duration::ZERO).unwrap().unwrap();
+        let event = next();
+        assert_eq!(event.target, ChangeTarget::Catalog);
+        assert_eq!(event.key, Bytes::from("users"));
+        assert!(event.value.is_some());
+        let event = next();
+        assert_eq!(
+            event.target,
+            ChangeTarget::ColumnFamily("users".to_string())
+        );
+        assert_eq!(event.value, Some(Bytes::from("v")));
+        let event = next();
+        assert_eq!(event.target, ChangeTarget::Default);
+        assert_eq!(event.value, Some(Bytes::from("default")));
+        let event = next();
+        assert_eq!(event.target, ChangeTarget::Catalog);
+        assert_eq!(event.value, None);
+
+        // 从创建记录之后开始订阅时，已经被删除的列族中的记录被跳过
+        let mut sub = engine.subscribe_from(created).unwrap();
+        let event = sub.next_timeout(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(event.target, ChangeTarget::Default);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
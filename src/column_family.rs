@@ -0,0 +1,506 @@
+use crate::data::log_record::LogRecordType::{DELETE, NORMAL};
+use crate::data::log_record::{LogRecord, LogRecordPos};
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::index::{self, Indexer};
+use crate::options::{ColumnFamilyOptions, Compression, IndexType};
+use crate::structures::now_millis;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+// 默认列族的 id，Engine 上的读写操作都作用于默认列族
+pub(crate) const DEFAULT_CF_ID: u32 = 0;
+// 记录列族创建和删除的系统列族，key 为列族名称
+pub(crate) const SYSTEM_CF_ID: u32 = u32::MAX;
+// 默认列族的名称，不能用于创建列族
+const DEFAULT_CF_NAME: &str = "default";
+// 列族元数据编码后的长度：id、索引类型、默认过期时间
+const CF_META_SIZE: usize = 4 + 1 + 8;
+
+pub(crate) struct Family {
+    id: u32,
+    name: String,
+    options: ColumnFamilyOptions,
+    pub(crate) index: Box<dyn Indexer>,
+}
+
+/// 已创建的列族
+#[derive(Default)]
+pub(crate) struct Families {
+    by_id: HashMap<u32, Arc<Family>>,
+    by_name: HashMap<String, u32>,
+    // 已经分配过的最大 id，删除的列族 id 不会重新使用
+    max_id: u32,
+}
+
+impl Families {
+    pub(crate) fn get(&self, id: u32) -> Option<Arc<Family>> {
+        self.by_id.get(&id).cloned()
+    }
+
+    // 列族 id 到名称的映射
+    pub(crate) fn names(&self) -> HashMap<u32, String> {
+        self.by_id
+            .iter()
+            .map(|(id, family)| (*id, family.name.clone()))
+            .collect()
+    }
+
+    fn get_by_name(&self, name: &str) -> Option<Arc<Family>> {
+        self.by_name.get(name).and_then(|id| self.get(*id))
+    }
+
+    fn insert(&mut self, family: Family) {
+        self.max_id = self.max_id.max(family.id);
+        // 同名的列族被重新创建时，旧的列族不再可见
+        if let Some(old_id) = self.by_name.insert(family.name.clone(), family.id) {
+            self.by_id.remove(&old_id);
+        }
+        self.by_id.insert(family.id, Arc::new(family));
+    }
+
+    fn remove(&mut self, name: &str) -> Option<Arc<Family>> {
+        let id = self.by_name.remove(name)?;
+        self.by_id.remove(&id)
+    }
+
+    // 判断 family 是否仍然存在，没有被删除或者被同名的列族替换
+    fn contains(&self, family: &Arc<Family>) -> bool {
+        self.by_id
+            .get(&family.id)
+            .is_some_and(|f| Arc::ptr_eq(f, family))
+    }
+}
+
+/// 列族的句柄，通过 Engine::cf 获取
+///
+/// 列族与默认列族共享数据文件和写入路径，每个列族有独立的索引，不同列族中的 key 互不影响。
+pub struct ColumnFamily<'a> {
+    engine: &'a Engine,
+    family: Arc<Family>,
+}
+
+impl Engine {
+    /// 创建列族，列族的元数据作为一条记录写入数据文件
+    pub fn create_cf(&self, name: &str, options: ColumnFamilyOptions) -> Result<()> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        if name.is_empty() || name == DEFAULT_CF_NAME {
+            return Err(Errors::InvalidColumnFamilyName);
+        }
+        // 先检查索引类型再写入元数据，否则重新打开时重放这条记录也会失败
+        index::check_index_type(&options.index_type)?;
+
+        let _guard = self.lock_writes();
+        if self.families.read().get_by_name(name).is_some() {
+            return Err(Errors::ColumnFamilyExists);
+        }
+        let id = self.families.read().max_id + 1;
+        let mut record = LogRecord {
+            key: name.as_bytes().to_vec(),
+            value: encode_cf_meta(id, &options),
+            rec_type: NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: SYSTEM_CF_ID,
+            expire: 0,
+        };
+        self.append_log_record(&mut record)?;
+        self.families.write().insert(Family {
+            id,
+            name: name.to_string(),
+            index: Box::new(index::new_indexer(options.index_type.clone())),
+            options,
+        });
+        Ok(())
+    }
+
+    /// 删除列族，只写入一条删除记录并丢弃列族的索引，列族中的数据不再可见
+    pub fn drop_cf(&self, name: &str) -> Result<()> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+
+        let _guard = self.lock_writes();
+        if self.families.read().get_by_name(name).is_none() {
+            return Err(Errors::ColumnFamilyNotFound);
+        }
+        let mut record = LogRecord {
+            key: name.as_bytes().to_vec(),
+            value: Default::default(),
+            rec_type: DELETE,
+            compression: Compression::None,
+            seq: 0,
+            cf: SYSTEM_CF_ID,
+            expire: 0,
+        };
+        self.append_log_record(&mut record)?;
+        if let Some(family) = self.families.write().remove(name) {
+            self.blobs.untrack_cf(family.id);
+        }
+        Ok(())
+    }
+
+    /// 获取列族的句柄，列族不存在时返回 ColumnFamilyNotFound
+    pub fn cf(&self, name: &str) -> Result<ColumnFamily<'_>> {
+        let family = self
+            .families
+            .read()
+            .get_by_name(name)
+            .ok_or(Errors::ColumnFamilyNotFound)?;
+        Ok(ColumnFamily {
+            engine: self,
+            family,
+        })
+    }
+
+    /// 所有列族的名称，不包含默认列族
+    pub fn list_cfs(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.families.read().by_name.keys().cloned().collect();
+        names.sort();
+        names
+    }
+
+    // 加载系统列族中的记录，重放列族的创建和删除
+    pub(crate) fn apply_cf_record(&self, record: &LogRecord) -> Result<()> {
+        let name =
+            String::from_utf8(record.key.clone()).map_err(|_| Errors::InvalidColumnFamilyRecord)?;
+        if matches!(record.rec_type, DELETE) {
+            if let Some(family) = self.families.write().remove(&name) {
+                self.blobs.untrack_cf(family.id);
+            }
+            return Ok(());
+        }
+
+        let (id, options) = decode_cf_meta(&record.value)?;
+        index::check_index_type(&options.index_type)?;
+        self.families.write().insert(Family {
+            id,
+            name,
+            index: Box::new(index::new_indexer(options.index_type.clone())),
+            options,
+        });
+        Ok(())
+    }
+}
+
+impl ColumnFamily<'_> {
+    pub fn name(&self) -> &str {
+        &self.family.name
+    }
+
+    /// 写入 key，列族配置了默认过期时间时，key 在过期之后不再可见
+    pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        let expire = self
+            .family
+            .options
+            .default_ttl
+            .map_or(0, |ttl| now_millis() + ttl.as_millis() as u64);
+        self.put_with_expire(key, value, expire)
+    }
+
+    // 写入 key 并使用指定的过期时间，复制时按照 leader 记录中的过期时间写入
+    pub(crate) fn put_with_expire(&self, key: Bytes, value: Bytes, expire: u64) -> Result<()> {
+        self.check_writable(&key)?;
+        let _guard = self.engine.lock_writes();
+        self.check_exists()?;
+        let family = &self.family;
+        self.engine
+            .put_entry(family.id, &*family.index, &key, &value, expire)
+    }
+
+    pub fn get(&self, key: Bytes) -> Result<Bytes> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.check_exists()?;
+        let pos = self
+            .family
+            .index
+            .get(key.to_vec())
+            .ok_or(Errors::KeyNotFound)?;
+        self.engine.get_value_by_position(&pos)
+    }
+
+    pub fn delete(&self, key: Bytes) -> Result<()> {
+        self.check_writable(&key)?;
+        let _guard = self.engine.lock_writes();
+        self.check_exists()?;
+        let family = &self.family;
+        self.engine.delete_entry(family.id, &*family.index, &key)
+    }
+
+    /// 列族中所有的 key，不包含已经过期的 key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        self.check_exists()?;
+        let keys = self.family.index.list_keys();
+        if self.family.options.default_ttl.is_none() {
+            return Ok(keys.into_iter().map(Bytes::from).collect());
+        }
+        let mut live_keys = Vec::with_capacity(keys.len());
+        for key in keys {
+            let Some(pos) = self.family.index.get(key.clone()) else {
+                continue;
+            };
+            if !self.is_expired(&pos)? {
+                live_keys.push(Bytes::from(key));
+            }
+        }
+        Ok(live_keys)
+    }
+
+    fn is_expired(&self, pos: &LogRecordPos) -> Result<bool> {
+        let expire = self.engine.read_log_record_at(pos)?.record.expire;
+        Ok(expire != 0 && expire <= now_millis())
+    }
+
+    fn check_writable(&self, key: &Bytes) -> Result<()> {
+        if self.engine.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        Ok(())
+    }
+
+    fn check_exists(&self) -> Result<()> {
+        match self.engine.families.read().contains(&self.family) {
+            true => Ok(()),
+            false => Err(Errors::ColumnFamilyNotFound),
+        }
+    }
+}
+
+// 列族元数据编码为 id | 索引类型 | 默认过期时间的毫秒数，0 表示不过期
+fn encode_cf_meta(id: u32, options: &ColumnFamilyOptions) -> Vec<u8> {
+    let mut buf = BytesMut::with_capacity(CF_META_SIZE);
+    buf.put_u32(id);
+    buf.put_u8(match options.index_type {
+        IndexType::Btree => 0,
+        IndexType::SkipList => 1,
+    });
+    buf.put_u64(options.default_ttl.map_or(0, |ttl| ttl.as_millis() as u64));
+    buf.to_vec()
+}
+
+pub(crate) fn decode_cf_meta(mut buf: &[u8]) -> Result<(u32, ColumnFamilyOptions)> {
+    if buf.len() != CF_META_SIZE {
+        return Err(Errors::InvalidColumnFamilyRecord);
+    }
+    let id = buf.get_u32();
+    let index_type = match buf.get_u8() {
+        0 => IndexType::Btree,
+        1 => IndexType::SkipList,
+        _ => return Err(Errors::InvalidColumnFamilyRecord),
+    };
+    let default_ttl = match buf.get_u64() {
+        0 => None,
+        ttl => Some(Duration::from_millis(ttl)),
+    };
+    Ok((
+        id,
+        ColumnFamilyOptions {
+            index_type,
+            default_ttl,
+        },
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+
+    #[test]
+    fn test_column_family() {
+        let dir_path = std::env::temp_dir().join("fdb-column-family");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine
+            .create_cf("users", ColumnFamilyOptions::default())
+            .unwrap();
+        engine
+            .create_cf("orders", ColumnFamilyOptions::default())
+            .unwrap();
+        assert_eq!(
+            engine.create_cf("users", ColumnFamilyOptions::default()),
+            Err(Errors::ColumnFamilyExists)
+        );
+        assert_eq!(
+            engine.create_cf("default", ColumnFamilyOptions::default()),
+            Err(Errors::InvalidColumnFamilyName)
+        );
+        assert_eq!(engine.list_cfs(), vec!["orders", "users"]);
+        assert!(matches!(
+            engine.cf("missing"),
+            Err(Errors::ColumnFamilyNotFound)
+        ));
+
+        // 相同的 key 在不同的列族中互不影响
+        let key = Bytes::from("id-1");
+        engine.put(key.clone(), Bytes::from("default")).unwrap();
+        let users = engine.cf("users").unwrap();
+        let orders = engine.cf("orders").unwrap();
+        users.put(key.clone(), Bytes::from("alice")).unwrap();
+        orders.put(key.clone(), Bytes::from("book")).unwrap();
+        orders.put(Bytes::from("id-2"), Bytes::from("pen")).unwrap();
+        assert_eq!(engine.get(key.clone()).unwrap(), "default");
+        assert_eq!(users.get(key.clone()).unwrap(), "alice");
+        assert_eq!(orders.get(key.clone()).unwrap(), "book");
+        assert_eq!(engine.list_keys().unwrap().len(), 1);
+        assert_eq!(orders.list_keys().unwrap().len(), 2);
+
+        users.delete(key.clone()).unwrap();
+        assert_eq!(users.get(key.clone()), Err(Errors::KeyNotFound));
+        assert_eq!(orders.get(key.clone()).unwrap(), "book");
+        drop(engine);
+
+        // 重新打开之后列族和其中的数据都还在
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.list_cfs(), vec!["orders", "users"]);
+        let users = engine.cf("users").unwrap();
+        let orders = engine.cf("orders").unwrap();
+        assert_eq!(users.get(key.clone()), Err(Errors::KeyNotFound));
+        assert_eq!(orders.get(key.clone()).unwrap(), "book");
+        assert_eq!(orders.get(Bytes::from("id-2")).unwrap(), "pen");
+        assert_eq!(engine.get(key).unwrap(), "default");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_column_family_ttl() {
+        let dir_path = std::env::temp_dir().join("fdb-column-family-ttl");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            value_cache_size: 1024,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        let cf_opts = ColumnFamilyOptions {
+            default_ttl: Some(Duration::from_millis(100)),
+            ..Default::default()
+        };
+        engine.create_cf("sessions", cf_opts).unwrap();
+        let sessions = engine.cf("sessions").unwrap();
+        sessions
+            .put(Bytes::from("token"), Bytes::from("alice"))
+            .unwrap();
+        assert_eq!(sessions.get(Bytes::from("token")).unwrap(), "alice");
+        assert_eq!(sessions.list_keys().unwrap().len(), 1);
+
+        std::thread::sleep(Duration::from_millis(200));
+        assert_eq!(sessions.get(Bytes::from("token")), Err(Errors::KeyNotFound));
+        assert!(sessions.list_keys().unwrap().is_empty());
+        // 删除已经过期的 key 之后重新打开
+        sessions.delete(Bytes::from("token")).unwrap();
+        drop(sessions);
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        let sessions = engine.cf("sessions").unwrap();
+        assert_eq!(sessions.get(Bytes::from("token")), Err(Errors::KeyNotFound));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_drop_column_family() {
+        let dir_path = std::env::temp_dir().join("fdb-drop-column-family");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            blob_threshold: 64,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine
+            .create_cf("logs", ColumnFamilyOptions::default())
+            .unwrap();
+        let logs = engine.cf("logs").unwrap();
+        logs.put(Bytes::from("a"), Bytes::from(vec![1u8; 128]))
+            .unwrap();
+        logs.put(Bytes::from("b"), Bytes::from("small")).unwrap();
+        assert_eq!(engine.blob_stats()[0].garbage_size, 0);
+
+        // 删除列族之后其中的 blob 都成为垃圾
+        engine.drop_cf("logs").unwrap();
+        let stat = engine.blob_stats()[0].clone();
+        assert_eq!(stat.garbage_size, stat.total_size);
+        assert!(engine.list_cfs().is_empty());
+        assert!(matches!(
+            engine.cf("logs"),
+            Err(Errors::ColumnFamilyNotFound)
+        ));
+        assert_eq!(engine.drop_cf("logs"), Err(Errors::ColumnFamilyNotFound));
+        // 删除之后旧的句柄不能再读写
+        assert_eq!(
+            logs.get(Bytes::from("b")),
+            Err(Errors::ColumnFamilyNotFound)
+        );
+        assert_eq!(
+            logs.put(Bytes::from("c"), Bytes::from("c")),
+            Err(Errors::ColumnFamilyNotFound)
+        );
+
+        // 重新创建同名的列族是空的
+        engine
+            .create_cf("logs", ColumnFamilyOptions::default())
+            .unwrap();
+        let logs = engine.cf("logs").unwrap();
+        assert_eq!(logs.get(Bytes::from("b")), Err(Errors::KeyNotFound));
+        logs.put(Bytes::from("c"), Bytes::from("new")).unwrap();
+        drop(logs);
+        drop(engine);
+
+        // 重新打开之后被删除的数据不会恢复
+        let engine = Engine::open(opts).unwrap();
+        let logs = engine.cf("logs").unwrap();
+        assert_eq!(logs.get(Bytes::from("b")), Err(Errors::KeyNotFound));
+        assert_eq!(logs.get(Bytes::from("c")).unwrap(), "new");
+        let stat = engine.blob_stats()[0].clone();
+        assert_eq!(stat.garbage_size, stat.total_size);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_create_column_family_unsupported_index() {
+        let dir_path = std::env::temp_dir().join("fdb-column-family-unsupported-index");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        let cf_opts = ColumnFamilyOptions {
+            index_type: IndexType::SkipList,
+            ..Default::default()
+        };
+        assert_eq!(
+            engine.create_cf("skiplist", cf_opts),
+            Err(Errors::UnsupportedIndexType)
+        );
+        assert!(engine.list_cfs().is_empty());
+        engine
+            .create_cf("users", ColumnFamilyOptions::default())
+            .unwrap();
+        drop(engine);
+
+        // 没有写入元数据，重新打开不受影响
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.list_cfs(), vec!["users"]);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
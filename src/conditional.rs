@@ -0,0 +1,252 @@
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use bytes::Bytes;
+
+impl Engine {
+    /// 当 key 当前的值等于 expected 时写入 new，返回是否写入
+    ///
+    /// expected 为 None 表示要求 key 不存在，new 为 None 表示删除 key。
+    /// 检查和写入在写锁内完成，与其他写操作之间是线性一致的。
+    pub fn compare_and_swap(
+        &self,
+        key: Bytes,
+        expected: Option<Bytes>,
+        new: Option<Bytes>,
+    ) -> Result<bool> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        let _guard = self.lock_writes();
+        let current = match self.index.get(key.to_vec()) {
+            Some(pos) => match self.get_value_by_position(&pos) {
+                Ok(value) => Some(value),
+                Err(Errors::KeyNotFound) => None,
+                Err(e) => return Err(e),
+            },
+            None => None,
+        };
+        if current != expected {
+            return Ok(false);
+        }
+        match new {
+            Some(value) => self.put_value(&key, &value)?,
+            None => self.delete_value(&key)?,
+        }
+        Ok(true)
+    }
+
+    /// 获取 key 的值以及版本号，版本号为最后一次写入的序列号，旧格式写入的 key 版本号为 0
+    pub fn get_with_version(&self, key: Bytes) -> Result<(Bytes, u64)> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Err(Errors::KeyNotFound);
+        };
+        let value = self.get_value_by_position(&pos)?;
+        Ok((value, pos.seq))
+    }
+
+    /// key 当前的版本号等于 expected_version 时写入，返回写入后的版本号
+    ///
+    /// key 不存在时版本号为 0，版本号已经变化时返回 VersionConflict。
+    pub fn put_if_version(&self, key: Bytes, value: Bytes, expected_version: u64) -> Result<u64> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+
+        let _guard = self.lock_writes();
+        let version = self.index.get(key.to_vec()).map_or(0, |pos| pos.seq);
+        if version != expected_version {
+            return Err(Errors::VersionConflict);
+        }
+        self.put_value(&key, &value)?;
+        let pos = self
+            .index
+            .get(key.to_vec())
+            .ok_or(Errors::IndexUpdateFailed)?;
+        Ok(pos.seq)
+    }
+
+    /// key 不存在时写入，返回是否写入
+    pub fn put_if_absent(&self, key: Bytes, value: Bytes) -> Result<bool> {
+        self.compare_and_swap(key, None, Some(value))
+    }
+
+    /// key 当前的值等于 expected 时删除，返回是否删除
+    pub fn delete_if_equals(&self, key: Bytes, expected: Bytes) -> Result<bool> {
+        self.compare_and_swap(key, Some(expected), None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_compare_and_swap() {
+        let dir_path = std::env::temp_dir().join("fdb-compare-and-swap");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let key = Bytes::from("lock");
+
+        assert!(engine.put_if_absent(key.clone(), Bytes::from("a")).unwrap());
+        assert!(!engine.put_if_absent(key.clone(), Bytes::from("b")).unwrap());
+        assert_eq!(engine.get(key.clone()).unwrap(), "a");
+
+        // 值不相等时不修改
+        let swapped = engine
+            .compare_and_swap(key.clone(), Some(Bytes::from("b")), Some(Bytes::from("c")))
+            .unwrap();
+        assert!(!swapped);
+        let swapped = engine
+            .compare_and_swap(key.clone(), Some(Bytes::from("a")), Some(Bytes::from("c")))
+            .unwrap();
+        assert!(swapped);
+        assert_eq!(engine.get(key.clone()).unwrap(), "c");
+
+        assert!(!engine
+            .delete_if_equals(key.clone(), Bytes::from("a"))
+            .unwrap());
+        assert!(engine
+            .delete_if_equals(key.clone(), Bytes::from("c"))
+            .unwrap());
+        assert_eq!(engine.get(key.clone()), Err(Errors::KeyNotFound));
+        // 删除之后可以重新获取
+        assert!(engine.put_if_absent(key.clone(), Bytes::from("d")).unwrap());
+        assert_eq!(
+            engine.compare_and_swap(Bytes::new(), None, None),
+            Err(Errors::KeyIsEmpty)
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_compare_and_swap_concurrent() {
+        let dir_path = std::env::temp_dir().join("fdb-compare-and-swap-concurrent");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // 同时抢锁只有一个成功
+        let winners = AtomicUsize::new(0);
+        std::thread::scope(|s| {
+            for i in 0..8 {
+                let (engine, winners) = (&engine, &winners);
+                s.spawn(move || {
+                    let owner = Bytes::from(format!("node-{}", i));
+                    if engine.put_if_absent(Bytes::from("leader"), owner).unwrap() {
+                        winners.fetch_add(1, Ordering::SeqCst);
+                    }
+                });
+            }
+        });
+        assert_eq!(winners.load(Ordering::SeqCst), 1);
+
+        // 基于 CAS 的计数器不会丢失更新
+        engine
+            .put(Bytes::from("counter"), Bytes::from("0"))
+            .unwrap();
+        std::thread::scope(|s| {
+            for _ in 0..4 {
+                let engine = &engine;
+                s.spawn(move || {
+                    for _ in 0..50 {
+                        loop {
+                            let current = engine.get(Bytes::from("counter")).unwrap();
+                            let n: u64 = std::str::from_utf8(&current).unwrap().parse().unwrap();
+                            let new = Bytes::from((n + 1).to_string());
+                            let key = Bytes::from("counter");
+                            if engine
+                                .compare_and_swap(key, Some(current), Some(new))
+                                .unwrap()
+                            {
+                                break;
+                            }
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(engine.get(Bytes::from("counter")).unwrap(), "200");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_put_if_version() {
+        let dir_path = std::env::temp_dir().join("fdb-put-if-version");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        let key = Bytes::from("doc");
+
+        // 不存在的 key 版本号为 0
+        let v1 = engine
+            .put_if_version(key.clone(), Bytes::from("a"), 0)
+            .unwrap();
+        assert!(v1 > 0);
+        assert_eq!(
+            engine.get_with_version(key.clone()).unwrap(),
+            (Bytes::from("a"), v1)
+        );
+        assert_eq!(
+            engine.put_if_version(key.clone(), Bytes::from("b"), 0),
+            Err(Errors::VersionConflict)
+        );
+
+        // 其他写入之后版本号递增，旧版本号冲突
+        engine.put(Bytes::from("other"), Bytes::from("x")).unwrap();
+        engine.put(key.clone(), Bytes::from("b")).unwrap();
+        let (_, v2) = engine.get_with_version(key.clone()).unwrap();
+        assert!(v2 > v1);
+        assert_eq!(
+            engine.put_if_version(key.clone(), Bytes::from("c"), v1),
+            Err(Errors::VersionConflict)
+        );
+        let v3 = engine
+            .put_if_version(key.clone(), Bytes::from("c"), v2)
+            .unwrap();
+        assert!(v3 > v2);
+        drop(engine);
+
+        // 重新打开之后版本号不变，新的写入继续递增
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(
+            engine.get_with_version(key.clone()).unwrap(),
+            (Bytes::from("c"), v3)
+        );
+        engine.delete(key.clone()).unwrap();
+        assert_eq!(
+            engine.get_with_version(key.clone()),
+            Err(Errors::KeyNotFound)
+        );
+        let v4 = engine
+            .put_if_version(key.clone(), Bytes::from("d"), 0)
+            .unwrap();
+        assert!(v4 > v3 + 1);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,199 @@
+use crate::data::log_record::{read_value_chunk, VALUE_CHUNK_SIZE};
+use crate::errors::{Errors, Result};
+use crate::fio::encrypted_io::ENCRYPTED_FILE_HEADER_SIZE;
+use crate::fio::{new_io_manager, IOManager};
+use crate::options::KeyProvider;
+use bytes::{Buf, BufMut, BytesMut};
+use std::io::Read;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+pub const BLOB_FILE_NAME_SUFFIX: &str = ".blob";
+// BlobRef 编码后的长度
+pub const BLOB_REF_SIZE: usize = 4 + 8 + 8 + 4;
+
+/// 大 value 在 blob 文件中的位置，作为 BLOB 类型日志记录的 value 存储在数据文件中
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BlobRef {
+    pub(crate) file_id: u32,
+    pub(crate) offset: u64,
+    pub(crate) len: u64,
+    pub(crate) crc: u32, // value 的 CRC 校验值
+}
+
+impl BlobRef {
+    //  +-------------+-------------+-------------+-------------+
+    //  |   file id   |    offset   |    length   |     crc     |
+    //  +-------------+-------------+-------------+-------------+
+    //      4字节          8字节          8字节         4字节
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::with_capacity(BLOB_REF_SIZE);
+        buf.put_u32(self.file_id);
+        buf.put_u64(self.offset);
+        buf.put_u64(self.len);
+        buf.put_u32(self.crc);
+        buf.to_vec()
+    }
+
+    pub fn decode(mut buf: &[u8]) -> Result<Self> {
+        if buf.len() != BLOB_REF_SIZE {
+            return Err(Errors::InvalidBlobRef);
+        }
+        Ok(BlobRef {
+            file_id: buf.get_u32(),
+            offset: buf.get_u64(),
+            len: buf.get_u64(),
+            crc: buf.get_u32(),
+        })
+    }
+}
+
+/// blob 文件，只追加写入 value 的原始内容，不包含任何 header
+pub struct BlobFile {
+    file_id: u32,
+    write_off: u64,
+    unsynced: bool,
+    io_manager: Box<dyn IOManager>,
+}
+
+impl BlobFile {
+    // 打开或创建 blob 文件，read_only 为 true 时文件必须已经存在
+    pub fn new(
+        dir_path: PathBuf,
+        file_id: u32,
+        key_provider: Option<&Arc<dyn KeyProvider>>,
+        read_only: bool,
+    ) -> Result<BlobFile> {
+        let file_name = get_blob_file_name(dir_path, file_id);
+        if read_only && !file_name.is_file() {
+            return Err(Errors::DataFileNotFound);
+        }
+        let io_manager = new_io_manager(file_name.clone(), key_provider, read_only)?;
+        // 加密文件的逻辑大小不包含文件头
+        let file_size = std::fs::metadata(&file_name).map_or(0, |meta| meta.len());
+        let write_off = match key_provider {
+            Some(_) => file_size.saturating_sub(ENCRYPTED_FILE_HEADER_SIZE),
+            None => file_size,
+        };
+
+        Ok(BlobFile {
+            file_id,
+            write_off,
+            unsynced: false,
+            io_manager,
+        })
+    }
+
+    pub fn get_file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    pub fn get_write_off(&self) -> u64 {
+        self.write_off
+    }
+
+    // 追加写入 value，返回对应的 BlobRef
+    pub fn write(&mut self, value: &[u8]) -> Result<BlobRef> {
+        let offset = self.write_off;
+        let n = self.io_manager.write(value)?;
+        self.write_off += n as u64;
+        self.unsynced = true;
+        Ok(BlobRef {
+            file_id: self.file_id,
+            offset,
+            len: value.len() as u64,
+            crc: crc32fast::hash(value),
+        })
+    }
+
+    // 从 reader 中分块读取 len 个字节追加写入，reader 出错时已写入的部分成为垃圾
+    pub fn write_stream(&mut self, reader: &mut dyn Read, len: u64) -> Result<BlobRef> {
+        let offset = self.write_off;
+        let mut hasher = crc32fast::Hasher::new();
+        let mut buf = vec![0u8; VALUE_CHUNK_SIZE.min(len as usize)];
+        let mut remaining = len;
+        while remaining > 0 {
+            let chunk = &mut buf[..VALUE_CHUNK_SIZE.min(remaining as usize)];
+            read_value_chunk(reader, chunk)?;
+            let n = self.io_manager.write(chunk)?;
+            self.write_off += n as u64;
+            self.unsynced = true;
+            hasher.update(chunk);
+            remaining -= chunk.len() as u64;
+        }
+        Ok(BlobRef {
+            file_id: self.file_id,
+            offset,
+            len,
+            crc: hasher.finalize(),
+        })
+    }
+
+    // 读取 BlobRef 对应的 value 并校验
+    pub fn read(&self, blob_ref: &BlobRef) -> Result<Vec<u8>> {
+        let mut buf = vec![0u8; blob_ref.len as usize];
+        let n = self.io_manager.read(&mut buf, blob_ref.offset)?;
+        if n != buf.len() || crc32fast::hash(&buf) != blob_ref.crc {
+            return Err(Errors::InvalidBlobCrc);
+        }
+        Ok(buf)
+    }
+
+    // 从offset处读取原始数据，不做校验
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.io_manager.read(buf, offset)
+    }
+
+    pub fn sync(&mut self) -> Result<()> {
+        if self.unsynced {
+            self.io_manager.sync()?;
+            self.unsynced = false;
+        }
+        Ok(())
+    }
+}
+
+pub fn get_blob_file_name(path: PathBuf, file_id: u32) -> PathBuf {
+    let name = std::format!("{:09}", file_id) + BLOB_FILE_NAME_SUFFIX;
+    path.join(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blob_ref_encode_and_decode() {
+        let blob_ref = BlobRef {
+            file_id: 3,
+            offset: 1024,
+            len: 300 * 1024,
+            crc: 42,
+        };
+        let enc = blob_ref.encode();
+        assert_eq!(enc.len(), BLOB_REF_SIZE);
+        assert_eq!(BlobRef::decode(&enc), Ok(blob_ref));
+        assert_eq!(BlobRef::decode(&enc[1..]), Err(Errors::InvalidBlobRef));
+    }
+
+    #[test]
+    fn test_blob_file_write_and_read() {
+        let dir_path = std::env::temp_dir();
+        let _ = std::fs::remove_file(get_blob_file_name(dir_path.clone(), 100));
+        let mut blob_file = BlobFile::new(dir_path.clone(), 100, None, false).unwrap();
+        let ref1 = blob_file.write(&[1u8; 1000]).unwrap();
+        let ref2 = blob_file.write(&[2u8; 2000]).unwrap();
+        assert_eq!(ref2.offset, 1000);
+        blob_file.sync().unwrap();
+        assert_eq!(blob_file.read(&ref1).unwrap(), vec![1u8; 1000]);
+
+        // 重新打开后从文件末尾继续写入
+        let blob_file = BlobFile::new(dir_path.clone(), 100, None, true).unwrap();
+        assert_eq!(blob_file.get_write_off(), 3000);
+        assert_eq!(blob_file.read(&ref2).unwrap(), vec![2u8; 2000]);
+        let bad_ref = BlobRef { crc: 0, ..ref2 };
+        assert_eq!(blob_file.read(&bad_ref), Err(Errors::InvalidBlobCrc));
+
+        std::fs::remove_file(get_blob_file_name(dir_path, 100)).unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use crate::errors::{Errors, Result};
+use crate::options::Compression;
+use log::error;
+
+// zstd 使用的压缩级别
+const ZSTD_LEVEL: i32 = 3;
+
+impl Compression {
+    pub fn from_u8(v: u8) -> Result<Self> {
+        match v {
+            0 => Ok(Compression::None),
+            1 => Ok(Compression::Lz4),
+            2 => Ok(Compression::Zstd),
+            3 => Ok(Compression::Snappy),
+            _ => Err(Errors::UnknownCompressionType),
+        }
+    }
+}
+
+// 按照配置压缩value，value小于阈值或者压缩后没有变小时保持原样
+// 返回实际写入的数据以及对应的压缩类型
+pub fn maybe_compress(
+    compression: Compression,
+    threshold: usize,
+    value: &[u8],
+) -> Result<(Vec<u8>, Compression)> {
+    if compression == Compression::None || value.len() < threshold {
+        return Ok((value.to_vec(), Compression::None));
+    }
+    let compressed = compress(compression, value)?;
+    if compressed.len() >= value.len() {
+        return Ok((value.to_vec(), Compression::None));
+    }
+    Ok((compressed, compression))
+}
+
+pub fn compress(compression: Compression, value: &[u8]) -> Result<Vec<u8>> {
+    let res = match compression {
+        Compression::None => return Ok(value.to_vec()),
+        Compression::Lz4 => Ok(lz4_flex::compress_prepend_size(value)),
+        Compression::Zstd => zstd::bulk::compress(value, ZSTD_LEVEL).map_err(|e| e.to_string()),
+        Compression::Snappy => snap::raw::Encoder::new()
+            .compress_vec(value)
+            .map_err(|e| e.to_string()),
+    };
+    res.map_err(|e| {
+        error!("failed to compress value: {}", e);
+        Errors::FailedToCompressValue
+    })
+}
+
+pub fn decompress(compression: Compression, value: &[u8]) -> Result<Vec<u8>> {
+    let res = match compression {
+        Compression::None => return Ok(value.to_vec()),
+        Compression::Lz4 => lz4_flex::decompress_size_prepended(value).map_err(|e| e.to_string()),
+        Compression::Zstd => zstd::stream::decode_all(value).map_err(|e| e.to_string()),
+        Compression::Snappy => snap::raw::Decoder::new()
+            .decompress_vec(value)
+            .map_err(|e| e.to_string()),
+    };
+    res.map_err(|e| {
+        error!("failed to decompress value: {}", e);
+        Errors::FailedToDecompressValue
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compress_and_decompress() {
+        let value = "fdb-rs-value-".repeat(100).into_bytes();
+        for compression in [
+            Compression::None,
+            Compression::Lz4,
+            Compression::Zstd,
+            Compression::Snappy,
+        ] {
+            let enc = compress(compression, &value).unwrap();
+            let dec = decompress(compression, &enc).unwrap();
+            assert_eq!(dec, value);
+        }
+    }
+
+    #[test]
+    fn test_maybe_compress() {
+        let value = "fdb-rs-value-".repeat(100).into_bytes();
+
+        // 小于阈值不压缩
+        let (res1, c1) = maybe_compress(Compression::Lz4, 4096, &value).unwrap();
+        assert_eq!(c1, Compression::None);
+        assert_eq!(res1, value);
+
+        // 超过阈值压缩
+        let (res2, c2) = maybe_compress(Compression::Zstd, 64, &value).unwrap();
+        assert_eq!(c2, Compression::Zstd);
+        assert!(res2.len() < value.len());
+
+        // 压缩后没有变小则保持原样
+        let (res3, c3) = maybe_compress(Compression::Snappy, 0, "abc".as_bytes()).unwrap();
+        assert_eq!(c3, Compression::None);
+        assert_eq!(res3, "abc".as_bytes());
+    }
+
+    #[test]
+    fn test_decompress_corrupted() {
+        let res = decompress(Compression::Snappy, "not-snappy".as_bytes());
+        assert_eq!(res, Err(Errors::FailedToDecompressValue));
+    }
+}
@@ -1,12 +1,17 @@
+use crate::data::compression::decompress;
 use crate::data::log_record::{
-    max_log_record_header_size, LogRecord, LogRecordType, ReadLogRecord,
+    compression_from_type_byte, has_ext, has_seq, max_log_record_header_size, LogRecord, LogRecordHeader,
+    LogRecordType, ReadLogRecord,
 };
 use crate::errors::Errors;
-use crate::fio::new_io_manager;
+use crate::fio::file_pool::FilePool;
+use crate::fio::{new_io_manager, open_existing_io_manager};
+use crate::options::{Compression, KeyProvider};
 use crate::{errors::Result, fio};
 use bytes::{Buf, BytesMut};
 use parking_lot::RwLock;
-use prost::{decode_length_delimiter, length_delimiter_len};
+use prost::decode_length_delimiter;
+use prost::encoding::decode_varint;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -15,20 +20,99 @@ pub const DATA_FILE_NAME_SUFFIX: &str = ".data";
 pub struct DataFile {
     file_id: Arc<RwLock<u32>>,
     write_off: Arc<RwLock<u64>>,
-    io_manager: Box<dyn fio::IOManager>,
+    unsynced_bytes: Arc<RwLock<u64>>, // 上次sync之后写入的字节数
+    io_manager: Option<Arc<dyn fio::IOManager>>, // 常驻的文件句柄，由句柄池管理时为 None
+    file_pool: Option<Arc<FilePool>>,
+    file_name: PathBuf,
+    key_provider: Option<Arc<dyn KeyProvider>>,
+    read_only: bool, // 只读打开，不会创建或写入文件
 }
 
 impl DataFile {
-    // 创建或打开一个新的数据文件
-    pub fn new(dir_path: PathBuf, file_id: u32) -> Result<DataFile> {
+    // 创建或打开一个新的数据文件，key_provider 不为空时数据文件加密存储
+    pub fn new(
+        dir_path: PathBuf,
+        file_id: u32,
+        key_provider: Option<&Arc<dyn KeyProvider>>,
+    ) -> Result<DataFile> {
         // 根据path和ID构造出完整的文件名称
         let file_name = get_data_file_name(dir_path, file_id);
-        let io_manager = new_io_manager(file_name)?;
+        let io_manager = new_io_manager(file_name.clone(), key_provider, false)?;
 
         Ok(DataFile {
             file_id: Arc::new(RwLock::new(file_id)),
             write_off: Arc::new(RwLock::new(0)),
-            io_manager: Box::new(io_manager),
+            unsynced_bytes: Arc::new(RwLock::new(0)),
+            io_manager: Some(Arc::from(io_manager)),
+            file_pool: None,
+            file_name,
+            key_provider: key_provider.cloned(),
+            read_only: false,
+        })
+    }
+
+    // 以只读方式打开已有的数据文件，文件不存在时返回 DataFileNotFound
+    pub fn new_read_only(
+        dir_path: PathBuf,
+        file_id: u32,
+        key_provider: Option<&Arc<dyn KeyProvider>>,
+    ) -> Result<DataFile> {
+        let file_name = get_data_file_name(dir_path, file_id);
+        if !file_name.is_file() {
+            return Err(Errors::DataFileNotFound);
+        }
+        let io_manager = new_io_manager(file_name.clone(), key_provider, true)?;
+
+        Ok(DataFile {
+            file_id: Arc::new(RwLock::new(file_id)),
+            write_off: Arc::new(RwLock::new(0)),
+            unsynced_bytes: Arc::new(RwLock::new(0)),
+            io_manager: Some(Arc::from(io_manager)),
+            file_pool: None,
+            file_name,
+            key_provider: key_provider.cloned(),
+            read_only: true,
+        })
+    }
+
+    // 打开一个由句柄池管理的旧数据文件，文件句柄在读取时才按需打开
+    pub fn new_pooled(
+        dir_path: PathBuf,
+        file_id: u32,
+        key_provider: Option<&Arc<dyn KeyProvider>>,
+        file_pool: Arc<FilePool>,
+        read_only: bool,
+    ) -> Result<DataFile> {
+        let file_name = get_data_file_name(dir_path, file_id);
+        if !file_name.is_file() {
+            return Err(Errors::DataFileNotFound);
+        }
+
+        Ok(DataFile {
+            file_id: Arc::new(RwLock::new(file_id)),
+            write_off: Arc::new(RwLock::new(0)),
+            unsynced_bytes: Arc::new(RwLock::new(0)),
+            io_manager: None,
+            file_pool: Some(file_pool),
+            file_name,
+            key_provider: key_provider.cloned(),
+            read_only,
+        })
+    }
+
+    // 获取文件句柄，句柄池中的文件被淘汰后会在这里重新打开
+    // 旧文件在重新打开时已经不存在则返回 DataFileNotFound，不会创建空文件
+    fn io_manager(&self) -> Result<Arc<dyn fio::IOManager>> {
+        if let Some(io_manager) = self.io_manager.as_ref() {
+            return Ok(io_manager.clone());
+        }
+        let file_pool = self.file_pool.as_ref().unwrap();
+        file_pool.get_or_open(self.get_file_id(), || {
+            open_existing_io_manager(
+                self.file_name.clone(),
+                self.key_provider.as_ref(),
+                self.read_only,
+            )
         })
     }
 
@@ -41,39 +125,45 @@ impl DataFile {
         *write_guard = offset
     }
 
+    // 丢弃offset之后的数据，用于撤销写了一半的记录
+    pub fn truncate(&self, offset: u64) -> Result<()> {
+        self.io_manager()?.truncate(offset)?;
+        self.set_write_off(offset);
+        Ok(())
+    }
+
     pub fn get_file_id(&self) -> u32 {
         let read_guard = self.file_id.read();
         *read_guard
     }
 
+    pub fn get_unsynced_bytes(&self) -> u64 {
+        *self.unsynced_bytes.read()
+    }
+
     // 根据offset，从数据文件中读取 logRecord
     pub fn read_log_record(&self, offset: u64) -> Result<ReadLogRecord> {
+        let io_manager = self.io_manager()?;
         // 先读取出header部分的数据
         let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
-        // 取出type,在第一个字节
-        let rec_type = header_buf.get_u8();
-        // 取出key和value的长度
-        let key_size = decode_length_delimiter(&mut header_buf).unwrap();
-        let value_size = decode_length_delimiter(&mut header_buf).unwrap();
-        // 如果key_size、value_size均为空，则说明读取到了文件末尾，直接返回
-        if key_size == 0 && value_size == 0 {
-            return Err(Errors::ReadDataFileEOF);
-        }
-
-        // 获取实际的header大小
-        let actual_header_size =
-            length_delimiter_len(key_size) + length_delimiter_len(value_size) + 1;
+        io_manager.read(&mut header_buf, offset)?;
+        let header = decode_header(&header_buf)?;
+        let (key_size, value_size) = (header.key_size, header.value_size);
+        let actual_header_size = header.size;
 
         // 读取实际的key、value和最后的4字节（CRC校验值）
         let mut kv_buf = BytesMut::zeroed(key_size + value_size + 4);
-        self.io_manager
-            .read(&mut kv_buf, offset + actual_header_size as u64)?;
+        io_manager.read(&mut kv_buf, offset + actual_header_size as u64)?;
 
         // 构造logRecord
-        let log_record = LogRecord {
+        let mut log_record = LogRecord {
             key: kv_buf.get(..key_size).unwrap().to_vec(),
             value: kv_buf.get(key_size..kv_buf.len() - 4).unwrap().to_vec(),
-            rec_type: LogRecordType::from_u8(rec_type),
+            rec_type: LogRecordType::from_u8(header.rec_type),
+            compression: Compression::from_u8(compression_from_type_byte(header.rec_type))?,
+            seq: header.seq,
+            cf: header.cf,
+            expire: header.expire,
         };
 
         // 向前移动到最后的4个字节，就是CRC的值
@@ -82,6 +172,12 @@ impl DataFile {
             return Err(Errors::InvalidLogRecordCrc);
         }
 
+        // 校验通过后解压value
+        if log_record.compression != Compression::None {
+            log_record.value = decompress(log_record.compression, &log_record.value)?;
+            log_record.compression = Compression::None;
+        }
+
         // 构造结果并返回
         Ok(ReadLogRecord {
             record: log_record,
@@ -89,43 +185,144 @@ impl DataFile {
         })
     }
 
+    // 判断offset处的记录是否一直延伸到文件末尾，用于区分写了一半的记录和文件中间损坏的记录
+    pub fn is_tail_record(&self, offset: u64) -> Result<bool> {
+        let io_manager = self.io_manager()?;
+        let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
+        let n_bytes = io_manager.read(&mut header_buf, offset)?;
+        if n_bytes == 0 {
+            return Ok(true);
+        }
+        let size = match decode_header(&header_buf[..n_bytes]) {
+            Ok(header) => (header.size as u64)
+                .saturating_add(header.key_size as u64)
+                .saturating_add(header.value_size as u64)
+                .saturating_add(4),
+            // header 无法解码时，只有 header 本身没有写完才视为末尾的记录
+            Err(_) => return Ok(n_bytes < header_buf.len()),
+        };
+        Ok(io_manager.read(&mut [0u8; 1], offset.saturating_add(size))? == 0)
+    }
+
+    // 根据offset读取 logRecord 的 header 和 key，value 由调用方按需读取
+    pub fn read_log_record_header(&self, offset: u64) -> Result<LogRecordHeader> {
+        let io_manager = self.io_manager()?;
+        let mut header_buf = BytesMut::zeroed(max_log_record_header_size());
+        io_manager.read(&mut header_buf, offset)?;
+        let header = decode_header(&header_buf)?;
+        let (key_size, value_size) = (header.key_size, header.value_size);
+        let actual_header_size = header.size;
+
+        // 读取key，以及value之后的CRC校验值
+        let mut key = vec![0u8; key_size];
+        io_manager.read(&mut key, offset + actual_header_size as u64)?;
+        let value_offset = offset + (actual_header_size + key_size) as u64;
+        let mut crc_buf = [0u8; 4];
+        io_manager.read(&mut crc_buf, value_offset + value_size as u64)?;
+
+        Ok(LogRecordHeader {
+            header: header_buf[..actual_header_size].to_vec(),
+            key,
+            value_offset,
+            value_size,
+            crc: u32::from_be_bytes(crc_buf),
+            expire: header.expire,
+        })
+    }
+
+    // 从offset处读取原始数据
+    pub fn read_at(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        self.io_manager()?.read(buf, offset)
+    }
+
     pub fn write(&self, buf: &[u8]) -> Result<usize> {
-        let n_bytes = self.io_manager.write(buf)?;
+        let n_bytes = self.io_manager()?.write(buf)?;
         // 更新write_off字段
         let mut write_off = self.write_off.write();
         *write_off += n_bytes as u64;
+        // 累加未持久化的字节数
+        *self.unsynced_bytes.write() += n_bytes as u64;
 
         Ok(n_bytes)
     }
 
     pub fn sync(&self) -> Result<()> {
-        self.io_manager.sync()
+        self.io_manager()?.sync()?;
+        *self.unsynced_bytes.write() = 0;
+        Ok(())
     }
 }
 
-fn get_data_file_name(path: PathBuf, file_id: u32) -> PathBuf {
+pub(crate) fn get_data_file_name(path: PathBuf, file_id: u32) -> PathBuf {
     let name = std::format!("{:09}", file_id) + DATA_FILE_NAME_SUFFIX;
     path.to_path_buf().join(name)
 }
 
+// 解码后的 header 字段
+struct DecodedHeader {
+    rec_type: u8,
+    key_size: usize,
+    value_size: usize,
+    seq: u64,
+    cf: u32,
+    expire: u64,
+    size: usize, // header 实际占用的字节数
+}
+
+// 解码 header，旧格式的记录没有序列号和扩展字段
+fn decode_header(header_buf: &[u8]) -> Result<DecodedHeader> {
+    let mut buf = header_buf;
+    // 取出type,在第一个字节
+    let rec_type = buf.get_u8();
+    // 取出key和value的长度，写了一半或者损坏的长度视为校验失败
+    let key_size = decode_length_delimiter(&mut buf).map_err(|_| Errors::InvalidLogRecordCrc)?;
+    let value_size = decode_length_delimiter(&mut buf).map_err(|_| Errors::InvalidLogRecordCrc)?;
+    // 如果key_size、value_size均为空，则说明读取到了文件末尾，直接返回
+    if key_size == 0 && value_size == 0 {
+        return Err(Errors::ReadDataFileEOF);
+    }
+    if LogRecordType::try_from_u8(rec_type).is_none() {
+        return Err(Errors::InvalidLogRecordCrc);
+    }
+    let mut varint = |present: bool| match present {
+        true => decode_varint(&mut buf).map_err(|_| Errors::InvalidLogRecordCrc),
+        false => Ok(0),
+    };
+    let seq = varint(has_seq(rec_type))?;
+    let cf = varint(has_ext(rec_type))? as u32;
+    let expire = varint(has_ext(rec_type))?;
+
+    Ok(DecodedHeader {
+        rec_type,
+        key_size,
+        value_size,
+        seq,
+        cf,
+        expire,
+        size: header_buf.len() - buf.len(),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::data::compression::compress;
+    use crate::options::StaticKeyProvider;
 
     #[test]
     fn test_new_data_file() {
         let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 0);
+        let data_file_res1 = DataFile::new(dir_path.clone(), 0, None);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 0);
 
-        let data_file_res2 = DataFile::new(dir_path.clone(), 0);
+        let data_file_res2 = DataFile::new(dir_path.clone(), 0, None);
         assert!(data_file_res2.is_ok());
         let data_file2 = data_file_res2.unwrap();
         assert_eq!(data_file2.get_file_id(), 0);
 
-        let data_file_res3 = DataFile::new(dir_path.clone(), 660);
+        let data_file_res3 = DataFile::new(dir_path.clone(), 660, None);
         assert!(data_file_res3.is_ok());
         let data_file3 = data_file_res3.unwrap();
         assert_eq!(data_file3.get_file_id(), 660);
@@ -134,7 +331,7 @@ mod tests {
     #[test]
     fn test_data_file_write() {
         let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 100);
+        let data_file_res1 = DataFile::new(dir_path.clone(), 100, None);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 100);
@@ -155,7 +352,7 @@ mod tests {
     #[test]
     fn test_data_file_sync() {
         let dir_path = std::env::temp_dir();
-        let data_file_res1 = DataFile::new(dir_path.clone(), 200);
+        let data_file_res1 = DataFile::new(dir_path.clone(), 200, None);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 200);
@@ -164,11 +361,26 @@ mod tests {
         assert!(sync_res.is_ok());
     }
 
+    #[test]
+    fn test_data_file_unsynced_bytes() {
+        let dir_path = std::env::temp_dir();
+        let data_file1 = DataFile::new(dir_path.clone(), 300, None).unwrap();
+        assert_eq!(data_file1.get_unsynced_bytes(), 0);
+
+        data_file1.write("aaa".as_bytes()).unwrap();
+        data_file1.write("bbbb".as_bytes()).unwrap();
+        assert_eq!(data_file1.get_unsynced_bytes(), 7);
+
+        // sync之后清零
+        assert!(data_file1.sync().is_ok());
+        assert_eq!(data_file1.get_unsynced_bytes(), 0);
+    }
+
     #[test]
     fn test_data_file_read_log_record() {
         let dir_path = std::env::temp_dir();
         println!("{:?}-----",dir_path);
-        let data_file_res1 = DataFile::new(dir_path.clone(), 700);
+        let data_file_res1 = DataFile::new(dir_path.clone(), 700, None);
         assert!(data_file_res1.is_ok());
         let data_file1 = data_file_res1.unwrap();
         assert_eq!(data_file1.get_file_id(), 700);
@@ -177,6 +389,10 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs-kv".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let write_res1 = data_file1.write(&enc1.encode());
         println!("write_res1:---:{:?}",write_res1);
@@ -196,6 +412,10 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "new-value".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let write_res2 = data_file1.write(&enc2.encode());
         assert!(write_res2.is_ok());
@@ -212,6 +432,10 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: Default::default(),
             rec_type: LogRecordType::DELETE,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let write_res3 = data_file1.write(&enc3.encode());
         assert!(write_res3.is_ok());
@@ -223,4 +447,192 @@ mod tests {
         assert_eq!(enc3.value, read_enc3.value);
         assert_eq!(enc3.rec_type, read_enc3.rec_type);
     }
+
+    #[test]
+    fn test_data_file_read_compressed_log_record() {
+        let dir_path = std::env::temp_dir();
+        let _ = std::fs::remove_file(get_data_file_name(dir_path.clone(), 800));
+        let data_file1 = DataFile::new(dir_path.clone(), 800, None).unwrap();
+
+        let value = "bitcask-rs-kv".repeat(50).into_bytes();
+        let enc1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: compress(Compression::Lz4, &value).unwrap(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::Lz4,
+            seq: 300,
+            cf: 0,
+            expire: 0,
+        };
+        let write_res1 = data_file1.write(&enc1.encode());
+        assert!(write_res1.is_ok());
+
+        // 同一个文件中混合未压缩的记录
+        let enc2 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: value.clone(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
+        };
+        assert!(data_file1.write(&enc2.encode()).is_ok());
+
+        let read_res1 = data_file1.read_log_record(0).unwrap();
+        assert_eq!(read_res1.record.value, value);
+        assert_eq!(read_res1.record.compression, Compression::None);
+        assert_eq!(read_res1.record.seq, 300);
+        assert_eq!(read_res1.size, write_res1.unwrap());
+
+        let read_res2 = data_file1.read_log_record(read_res1.size as u64).unwrap();
+        assert_eq!(read_res2.record.value, value);
+        assert_eq!(read_res2.record.seq, 0);
+    }
+
+    #[test]
+    fn test_data_file_encrypted() {
+        let dir_path = std::env::temp_dir();
+        let _ = std::fs::remove_file(get_data_file_name(dir_path.clone(), 900));
+        let key: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new([9u8; 32]));
+        let data_file1 = DataFile::new(dir_path.clone(), 900, Some(&key)).unwrap();
+
+        let enc1 = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
+        };
+        assert!(data_file1.write(&enc1.encode()).is_ok());
+        let read_enc1 = data_file1.read_log_record(0).unwrap().record;
+        assert_eq!(enc1.value, read_enc1.value);
+        // 读到文件末尾
+        assert_eq!(
+            data_file1.read_log_record(24).err(),
+            Some(Errors::ReadDataFileEOF)
+        );
+        drop(data_file1);
+
+        // 没有密钥或者密钥错误都无法打开
+        let res1 = DataFile::new(dir_path.clone(), 900, None);
+        assert_eq!(res1.err(), Some(Errors::DataFileIsEncrypted));
+        let wrong_key: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new([1u8; 32]));
+        let res2 = DataFile::new(dir_path.clone(), 900, Some(&wrong_key));
+        assert_eq!(res2.err(), Some(Errors::WrongEncryptionKey));
+    }
+
+    #[test]
+    fn test_data_file_encrypted_tampered() {
+        let dir_path = std::env::temp_dir();
+        let file_name = get_data_file_name(dir_path.clone(), 901);
+        let _ = std::fs::remove_file(&file_name);
+        let key: Arc<dyn KeyProvider> = Arc::new(StaticKeyProvider::new([9u8; 32]));
+        let data_file = DataFile::new(dir_path.clone(), 901, Some(&key)).unwrap();
+        let rec = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs-kv".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
+        };
+        data_file.write(&rec.encode()).unwrap();
+        drop(data_file);
+
+        // 修改密文的最后一个字节，认证失败而不是 CRC 校验失败
+        let mut raw = std::fs::read(&file_name).unwrap();
+        let last = raw.len() - 20;
+        raw[last] ^= 0x01;
+        std::fs::write(&file_name, &raw).unwrap();
+        let data_file = DataFile::new(dir_path.clone(), 901, Some(&key)).unwrap();
+        assert_eq!(data_file.read_log_record(0).err(), Some(Errors::FailedToAuthenticateData));
+
+        std::fs::remove_file(&file_name).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_read_corrupted_header() {
+        let dir_path = std::env::temp_dir();
+        let file_id = 2000;
+        let _ = std::fs::remove_file(get_data_file_name(dir_path.clone(), file_id));
+        let data_file = DataFile::new(dir_path.clone(), file_id, None).unwrap();
+
+        // 未知的记录类型
+        let mut enc = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
+        }
+        .encode();
+        enc[0] = 7;
+        data_file.write(&enc).unwrap();
+        assert_eq!(data_file.read_log_record(0).err(), Some(Errors::InvalidLogRecordCrc));
+        assert_eq!(data_file.read_log_record_header(0).err(), Some(Errors::InvalidLogRecordCrc));
+
+        // 长度的变长编码没有结束
+        let offset = data_file.get_write_off();
+        let mut buf = vec![LogRecordType::NORMAL as u8];
+        buf.extend_from_slice(&[0xff; 20]);
+        data_file.write(&buf).unwrap();
+        assert_eq!(data_file.read_log_record(offset).err(), Some(Errors::InvalidLogRecordCrc));
+
+        std::fs::remove_file(get_data_file_name(dir_path, file_id)).unwrap();
+    }
+
+    #[test]
+    fn test_data_file_pooled() {
+        let dir_path = std::env::temp_dir();
+        let pool = Arc::new(FilePool::new(std::num::NonZeroUsize::new(1).unwrap()));
+        let mut data_files = Vec::new();
+        for file_id in 1000..1003 {
+            let _ = std::fs::remove_file(get_data_file_name(dir_path.clone(), file_id));
+            let data_file = DataFile::new(dir_path.clone(), file_id, None).unwrap();
+            let rec = LogRecord {
+                key: format!("key-{}", file_id).into_bytes(),
+                value: "value".as_bytes().to_vec(),
+                rec_type: LogRecordType::NORMAL,
+                compression: Compression::None,
+                seq: 0,
+                cf: 0,
+                expire: 0,
+            };
+            data_file.write(&rec.encode()).unwrap();
+            drop(data_file);
+            let pooled = DataFile::new_pooled(dir_path.clone(), file_id, None, pool.clone(), false);
+            data_files.push(pooled.unwrap());
+        }
+        // 创建时不打开文件
+        assert!(pool.is_empty());
+
+        // 交替读取，句柄被淘汰之后重新打开
+        for _ in 0..2 {
+            for data_file in data_files.iter() {
+                let rec = data_file.read_log_record(0).unwrap().record;
+                assert_eq!(rec.key, format!("key-{}", data_file.get_file_id()).into_bytes());
+                assert_eq!(pool.len(), 1);
+            }
+        }
+
+        let res = DataFile::new_pooled(dir_path.clone(), 1999, None, pool.clone(), false);
+        assert_eq!(res.err(), Some(Errors::DataFileNotFound));
+
+        // 被淘汰的文件在重新打开之前被删除，读取时不会创建空文件
+        let evicted = &data_files[0];
+        let evicted_name = get_data_file_name(dir_path.clone(), evicted.get_file_id());
+        std::fs::remove_file(&evicted_name).unwrap();
+        assert_eq!(evicted.read_log_record(0).err(), Some(Errors::DataFileNotFound));
+        assert!(!evicted_name.exists());
+
+        for file_id in 1001..1003 {
+            std::fs::remove_file(get_data_file_name(dir_path.clone(), file_id)).unwrap();
+        }
+    }
 }
@@ -1,13 +1,35 @@
+use crate::options::Compression;
 use prost::{encode_length_delimiter, length_delimiter_len};
+use prost::encoding::{encode_varint, encoded_len_varint};
 use bytes::{BufMut, BytesMut};
+use std::hash::{Hash, Hasher};
+use std::io::Read;
+use crate::errors::{Errors, Result};
+
+// type 字节中低3位存放记录类型，第3位表示 header 中是否有扩展字段，
+// 4~6位存放压缩类型，最高位表示 header 中是否有序列号
+const LOG_RECORD_TYPE_MASK: u8 = 0x07;
+const EXT_FLAG: u8 = 0x08;
+const COMPRESSION_SHIFT: u8 = 4;
+const COMPRESSION_MASK: u8 = 0x07;
+const SEQ_FLAG: u8 = 0x80;
+// 流式写入 value 时每次读取的字节数
+pub const VALUE_CHUNK_SIZE: usize = 64 * 1024;
 
 // 数据日志类型
+#[allow(clippy::upper_case_acronyms)]
 #[derive(PartialEq, Copy, Clone,Debug)]
 pub enum LogRecordType {
     // 正常put的数据
     NORMAL = 1,
     // 被删除数据标识，墓碑值
     DELETE = 2,
+    // value 存放在 blob 文件中，记录的 value 为编码后的 BlobRef
+    BLOB = 3,
+    // 合并操作数，读取时合并到 key 之前的值上
+    MERGE = 4,
+    // 批量写入的标记，value 为之后属于同一批量的记录数量
+    BATCH = 5,
 }
 
 // 数据日志结构体，表示实际写到数据文件中的数据
@@ -16,13 +38,23 @@ pub struct LogRecord {
     pub(crate) key: Vec<u8>,
     pub(crate) value: Vec<u8>,
     pub(crate) rec_type: LogRecordType,
+    // value 的压缩类型，从数据文件读取出的记录已经解压，均为 None
+    pub(crate) compression: Compression,
+    // 写入时分配的序列号，旧格式的记录没有序列号，为 0
+    pub(crate) seq: u64,
+    // 所属的列族id，默认列族为 0
+    pub(crate) cf: u32,
+    // 过期时间，毫秒时间戳，0 表示不过期
+    pub(crate) expire: u64,
 }
 
 // 数据文件索引信息，描述数据存储到了哪个位置
-#[derive(Clone, Copy, Debug)]
+// 索引中的位置同时保存记录的序列号，即 key 的版本号；比较位置时只比较 file_id 和 offset
+#[derive(Clone, Copy, Debug, Default)]
 pub struct LogRecordPos {
     pub(crate) file_id: u32,
     pub(crate) offset: u64,
+    pub(crate) seq: u64,
 }
 
 // 从数据文件中读取的log record 信息，包含size
@@ -32,13 +64,38 @@ pub struct ReadLogRecord {
     pub(crate) size: usize,
 }
 
+// 从数据文件中读取的log record header 和 key，不包含value，用于流式读取value
+#[derive(Debug)]
+pub struct LogRecordHeader {
+    pub(crate) header: Vec<u8>, // 编码后的 type、key size、value size、序列号
+    pub(crate) key: Vec<u8>,
+    pub(crate) value_offset: u64, // value 在数据文件中的offset
+    pub(crate) value_size: usize,
+    pub(crate) crc: u32,
+    pub(crate) expire: u64, // 过期时间的毫秒时间戳，0 表示不过期
+}
+
+impl LogRecordHeader {
+    pub fn rec_type(&self) -> LogRecordType {
+        LogRecordType::from_u8(self.header[0])
+    }
+
+    pub fn compression_type(&self) -> u8 {
+        compression_from_type_byte(self.header[0])
+    }
+}
+
 impl LogRecord {
     // EncodeLogRecord 对 LogRecord 进行编码，返回字节数组及长度
 //
-//	+-------------+-------------+--------------+-------------+-------------+--------------+
-//	|  type 类型   |    key size |   value size |      key    |      value  |  crc 校验值  |
-//	+-------------+-------------+--------------+-------------+-------------+--------------+
-//	    1字节          变长（最大5）    变长（最大5）      变长           变长          4字节
+//	+-------------+-------------+--------------+-------------+-------------+-------------+-------------+-------------+--------------+
+//	|  type 类型   |    key size |   value size |     seq     |    cf id    |    expire   |      key    |      value  |  crc 校验值  |
+//	+-------------+-------------+--------------+-------------+-------------+-------------+-------------+-------------+--------------+
+//	    1字节          变长（最大5）    变长（最大5）  变长（最大10）  变长（最大5）  变长（最大10）     变长           变长          4字节
+//
+//  type 字节的第3位表示是否有 cf id 和 expire 扩展字段，默认列族中不过期的记录不写入
+//  type 字节的4~6位为 value 的压缩类型，旧数据该部分为0，即不压缩
+//  type 字节的最高位表示是否有 seq，旧数据没有 seq，序列号为0时同样不写入
     pub fn encode(& self) -> Vec<u8> {
         let (enc_buf, _) = self.encode_and_get_crc();
 
@@ -55,13 +112,9 @@ impl LogRecord {
         let mut buf = BytesMut::new();
         buf.reserve(self.encode_length());
 
-        // 第一个字节存放type类型
-        buf.put_u8(self.rec_type as u8);
-        // 再存储key和value的长度
-        encode_length_delimiter(self.key.len(), &mut buf).unwrap();
-        encode_length_delimiter(self.value.len(), &mut buf).unwrap();
-        // 存储key和value
-        buf.extend_from_slice(&self.key);
+        // 存储header和key
+        buf.extend_from_slice(&self.encode_header(self.value.len()));
+        // 存储value
         buf.extend_from_slice(&self.value);
         // 计算并存储CRC校验值
         let mut hasher = crc32fast::Hasher::new();
@@ -72,34 +125,132 @@ impl LogRecord {
         (buf.to_vec(), crc)
     }
 
+    // 编码 header 和 key，不包含 value 和 CRC，也用于流式写入 value
+    pub fn encode_header(&self, value_len: usize) -> Vec<u8> {
+        let mut buf = BytesMut::with_capacity(max_log_record_header_size() + self.key.len());
+        // 第一个字节存放type类型、压缩类型，以及是否有序列号和扩展字段
+        let has_ext = self.cf != 0 || self.expire != 0;
+        let mut type_byte = encode_type_byte(self.rec_type, self.compression);
+        if self.seq > 0 {
+            type_byte |= SEQ_FLAG;
+        }
+        if has_ext {
+            type_byte |= EXT_FLAG;
+        }
+        buf.put_u8(type_byte);
+        // 再存储key和value的长度，以及序列号和扩展字段
+        encode_length_delimiter(self.key.len(), &mut buf).unwrap();
+        encode_length_delimiter(value_len, &mut buf).unwrap();
+        if self.seq > 0 {
+            encode_varint(self.seq, &mut buf);
+        }
+        if has_ext {
+            encode_varint(self.cf as u64, &mut buf);
+            encode_varint(self.expire, &mut buf);
+        }
+        buf.extend_from_slice(&self.key);
+        buf.to_vec()
+    }
+
     // LogRecord 编码后的长度
     fn encode_length(&self) -> usize {
         std::mem::size_of::<u8>() + length_delimiter_len(self.key.len()) +
             std::mem::size_of::<u8>() + length_delimiter_len(self.value.len()) +
+            encoded_len_varint(self.seq) +
+            encoded_len_varint(self.cf as u64) + encoded_len_varint(self.expire) +
             self.key.len() +
             self.value.len() +
             4
     }
 }
 
+impl LogRecordPos {
+    /// 根据文件id和offset构造位置，用于从保存的检查点恢复
+    pub fn new(file_id: u32, offset: u64) -> Self {
+        LogRecordPos { file_id, offset, seq: 0 }
+    }
+
+    // 带有记录序列号的位置，用于内存索引
+    pub(crate) fn with_seq(file_id: u32, offset: u64, seq: u64) -> Self {
+        LogRecordPos { file_id, offset, seq }
+    }
+
+    pub fn file_id(&self) -> u32 {
+        self.file_id
+    }
+
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+}
+
+impl PartialEq for LogRecordPos {
+    fn eq(&self, other: &Self) -> bool {
+        self.file_id == other.file_id && self.offset == other.offset
+    }
+}
+
+impl Eq for LogRecordPos {}
+
+impl Hash for LogRecordPos {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.file_id.hash(state);
+        self.offset.hash(state);
+    }
+}
+
 impl LogRecordType {
     pub fn from_u8(v: u8) -> Self {
-        match v {
-            1 => LogRecordType::NORMAL,
-            2 => LogRecordType::DELETE,
-            _ => panic!("unknown log record type"),
+        Self::try_from_u8(v).expect("unknown log record type")
+    }
+
+    // 未知的类型返回 None，用于解码可能写了一半或者损坏的数据
+    pub fn try_from_u8(v: u8) -> Option<Self> {
+        match v & LOG_RECORD_TYPE_MASK {
+            1 => Some(LogRecordType::NORMAL),
+            2 => Some(LogRecordType::DELETE),
+            3 => Some(LogRecordType::BLOB),
+            4 => Some(LogRecordType::MERGE),
+            5 => Some(LogRecordType::BATCH),
+            _ => None,
         }
     }
 }
 
+// 将记录类型和压缩类型编码到同一个字节中
+pub fn encode_type_byte(rec_type: LogRecordType, compression: Compression) -> u8 {
+    (rec_type as u8) | ((compression as u8) << COMPRESSION_SHIFT)
+}
+
+// 从 type 字节中解析出压缩类型
+pub fn compression_from_type_byte(v: u8) -> u8 {
+    (v >> COMPRESSION_SHIFT) & COMPRESSION_MASK
+}
+
+// type 字节中是否标记了 header 中有序列号
+pub fn has_seq(v: u8) -> bool {
+    v & SEQ_FLAG != 0
+}
+
+// type 字节中是否标记了 header 中有 cf id 和 expire 扩展字段
+pub fn has_ext(v: u8) -> bool {
+    v & EXT_FLAG != 0
+}
+
+// 从 reader 中读取恰好填满 buf 的数据，reader 出错或者提前结束时返回错误
+pub fn read_value_chunk(reader: &mut dyn Read, buf: &mut [u8]) -> Result<()> {
+    reader.read_exact(buf).map_err(|_| Errors::FailedToReadValueStream)
+}
+
 // Rust 代码把CRC部分放在数据最后部分，为了处理方便不放header里面,获取最大长度，非实际长度
 pub fn max_log_record_header_size() -> usize {
-    // 类型size + key size + value size
-    std::mem::size_of::<u8>() + length_delimiter_len(u32::MAX as usize) * 2
+    // 类型size + key size + value size + seq + cf id + expire
+    std::mem::size_of::<u8>() + length_delimiter_len(u32::MAX as usize) * 3 + encoded_len_varint(u64::MAX) * 2
 }
 
 
 #[cfg(test)]
+#[allow(unused_mut)]
 mod tests {
     use super::*;
 
@@ -110,6 +261,10 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs".as_bytes().to_vec(),
             rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let enc1 = rec1.encode();
         assert!(enc1.len() > 5);
@@ -120,6 +275,10 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: Default::default(),
             rec_type: LogRecordType::NORMAL,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let enc2 = rec2.encode();
         assert!(enc2.len() > 5);
@@ -130,9 +289,57 @@ mod tests {
             key: "name".as_bytes().to_vec(),
             value: "bitcask-rs".as_bytes().to_vec(),
             rec_type: LogRecordType::DELETE,
+            compression: Compression::None,
+            seq: 0,
+            cf: 0,
+            expire: 0,
         };
         let enc3 = rec3.encode();
         assert!(enc3.len() > 5);
         assert_eq!(1867197446, rec3.get_crc());
     }
+
+    #[test]
+    fn test_log_record_type_byte() {
+        let b = encode_type_byte(LogRecordType::DELETE, Compression::Zstd);
+        assert_eq!(LogRecordType::from_u8(b), LogRecordType::DELETE);
+        assert_eq!(compression_from_type_byte(b), Compression::Zstd as u8);
+
+        // 旧格式的 type 字节没有压缩类型
+        assert_eq!(compression_from_type_byte(LogRecordType::NORMAL as u8), 0);
+
+        assert_eq!(LogRecordType::try_from_u8(b), Some(LogRecordType::DELETE));
+        assert_eq!(LogRecordType::try_from_u8(0), None);
+        assert_eq!(LogRecordType::try_from_u8(7), None);
+    }
+
+    #[test]
+    fn test_log_record_seq() {
+        let rec = LogRecord {
+            key: "name".as_bytes().to_vec(),
+            value: "bitcask-rs".as_bytes().to_vec(),
+            rec_type: LogRecordType::NORMAL,
+            compression: Compression::Lz4,
+            seq: 300,
+            cf: 0,
+            expire: 0,
+        };
+        let enc = rec.encode();
+        assert!(has_seq(enc[0]));
+        assert_eq!(LogRecordType::from_u8(enc[0]), LogRecordType::NORMAL);
+        assert_eq!(compression_from_type_byte(enc[0]), Compression::Lz4 as u8);
+
+        assert!(!has_ext(enc[0]));
+
+        // 序列号为0时与旧格式相同
+        let old = LogRecord { seq: 0, ..rec };
+        assert!(!has_seq(old.encode()[0]));
+
+        // 非默认列族或者有过期时间的记录才有扩展字段
+        let rec = LogRecord { cf: 3, ..old };
+        let enc = rec.encode();
+        assert!(has_ext(enc[0]));
+        assert_eq!(LogRecordType::from_u8(enc[0]), LogRecordType::NORMAL);
+        assert_eq!(compression_from_type_byte(enc[0]), Compression::Lz4 as u8);
+    }
 }
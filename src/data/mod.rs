@@ -1,2 +1,4 @@
+pub mod blob_file;
+pub mod compression;
 pub mod data_file;
 pub mod log_record;
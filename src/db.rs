@@ -1,21 +1,39 @@
+use crate::blob::{BlobFileStat, BlobStore};
+use crate::bulk_load;
+use crate::cache::{CacheStats, ValueCache};
+use crate::column_family::{Families, DEFAULT_CF_ID, SYSTEM_CF_ID};
+use crate::data::blob_file::BlobRef;
+use crate::data::compression::maybe_compress;
 use crate::data::data_file::{DataFile, DATA_FILE_NAME_SUFFIX};
-use crate::data::log_record::LogRecordType::{DELETE, NORMAL};
-use crate::data::log_record::{LogRecord, LogRecordPos, LogRecordType};
+use crate::data::log_record::LogRecordType::{BATCH, BLOB, DELETE, MERGE, NORMAL};
+use crate::data::log_record::{
+    read_value_chunk, LogRecord, LogRecordPos, ReadLogRecord, VALUE_CHUNK_SIZE,
+};
 use crate::errors::Errors::{
-    DataDirectoryCorrupted, DataFileNotFound, DataFileSizeTooSmall, DirPathIsEmpty,
-    FailedToCreateDatabaseDir, FailedToReadDatabaseDir, IndexUpdateFailed, KeyIsEmpty, KeyNotFound,
-    ReadDataFileEOF,
+    DataDirectoryCorrupted, DataFileNotFound, DataFileSizeTooSmall, DatabaseIsReadOnly,
+    DirPathIsEmpty, FailedToCreateDatabaseDir, FailedToReadDatabaseDir, IndexUpdateFailed,
+    InvalidLogRecordCrc, InvalidSyncPolicy, KeyIsEmpty, KeyNotFound, ReadDataFileEOF,
 };
 use crate::errors::{Errors, Result};
-use crate::index;
-use crate::options::Options;
+use crate::fio::file_pool::FilePool;
+use crate::index::{self, Indexer};
+use crate::options::{Compression, KeyProvider, Options, SyncPolicy};
+use crate::structures::now_millis;
 use bytes::Bytes;
-use log::warn;
-use parking_lot::RwLock;
+use log::{error, warn};
+use parking_lot::{
+    Condvar, Mutex, MutexGuard, RwLock, RwLockUpgradableReadGuard, RwLockWriteGuard,
+};
 use std::collections::HashMap;
 use std::fs;
+use std::io::Read;
+use std::num::NonZeroUsize;
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
 use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
 
 pub const INITIAL_FILE_ID: u32 = 0;
 
@@ -24,8 +42,61 @@ pub struct Engine {
     options: Arc<Options>,
     active_file: Arc<RwLock<DataFile>>,
     older_files: Arc<RwLock<HashMap<u32, DataFile>>>,
-    index: Box<dyn index::Indexer>,
+    pub(crate) index: Box<dyn index::Indexer>,
     file_ids: Vec<u32>,
+    // 已持久化的位置，该位置之前的所有数据都已经 sync 到磁盘
+    durable: Arc<PosState>,
+    // 大 value 存放的 blob 文件
+    pub(crate) blobs: Arc<BlobStore>,
+    // 已写入的位置，每次追加写之后推进
+    written: PosState,
+    // 写锁，保证读取索引、追加记录和更新索引作为一个整体执行
+    write_lock: Mutex<()>,
+    // 最后分配的序列号，每次写入递增，打开时从数据文件中恢复
+    seq: AtomicU64,
+    flusher: Option<Flusher>,
+    file_pool: Option<Arc<FilePool>>,
+    value_cache: Option<ValueCache>,
+    // 列族，与默认列族共享数据文件和写入路径
+    pub(crate) families: RwLock<Families>,
+}
+
+/// 存储引擎的统计信息
+#[derive(Clone, Debug, PartialEq)]
+pub struct Stat {
+    pub key_num: usize,       // key 的数量
+    pub data_file_num: usize, // 数据文件的数量
+    pub disk_size: u64,       // 数据文件占用的磁盘空间，单位字节
+}
+
+/// 可等待的位置，位置推进时通知等待者
+struct PosState {
+    pos: Mutex<LogRecordPos>,
+    cond: Condvar,
+}
+
+impl PosState {
+    fn new(file_id: u32) -> Self {
+        Self {
+            pos: Mutex::new(LogRecordPos::new(file_id, 0)),
+            cond: Condvar::new(),
+        }
+    }
+
+    // 推进位置并通知等待者
+    fn advance(&self, pos: LogRecordPos) {
+        let mut curr = self.pos.lock();
+        if pos_covers(&pos, &curr) {
+            *curr = pos;
+        }
+        self.cond.notify_all();
+    }
+}
+
+/// SyncPolicy::Interval 对应的后台刷盘线程
+struct Flusher {
+    stop_sender: Option<Sender<()>>,
+    handle: Option<JoinHandle<()>>,
 }
 
 impl Engine {
@@ -37,78 +108,301 @@ impl Engine {
         // 判断数据目录是否存在，如果不存在的话，则创建这个目录
         let dir_path = options.dir_path.clone();
         if !dir_path.is_dir() {
+            // 只读模式下不创建目录
+            if opts.read_only {
+                return Err(FailedToReadDatabaseDir);
+            }
             if let Err(e) = fs::create_dir_all(dir_path.clone()) {
                 warn!("create database directory err:{}", e);
                 return Err(FailedToCreateDatabaseDir);
             }
         }
+        // 完成或者丢弃上次没有完成的批量导入
+        if !opts.read_only {
+            bulk_load::recover(&dir_path)?;
+        }
+        // 限制旧数据文件同时打开的句柄数
+        let file_pool = NonZeroUsize::new(opts.max_open_files)
+            .map(|capacity| Arc::new(FilePool::new(capacity)));
         // 加载数据文件
-        let mut data_files = load_data_files(dir_path.clone())?;
+        let mut data_files = load_data_files(
+            dir_path.clone(),
+            opts.key_provider.as_ref(),
+            file_pool.as_ref(),
+            opts.read_only,
+        )?;
         // 设置file ID信息
         let mut file_ids = Vec::new();
         for v in data_files.iter() {
             file_ids.push(v.get_file_id());
         }
 
-        // 将旧数据文件保存到older_files中
-        let mut older_files = HashMap::new();
-        if data_files.len() > 1 {
-            for _ in 0..=data_files.len() - 2 {
-                let file = data_files.pop().unwrap();
-                older_files.insert(file.get_file_id(), file);
-            }
-        }
-
         // 拿到当前活跃文件，即列表中最后一个文件
         let active_file = match data_files.pop() {
             Some(v) => v,
-            None => DataFile::new(dir_path.clone(), INITIAL_FILE_ID)?,
+            // 只读模式下不能创建新的数据文件
+            None if opts.read_only => return Err(DataFileNotFound),
+            None => DataFile::new(
+                dir_path.clone(),
+                INITIAL_FILE_ID,
+                opts.key_provider.as_ref(),
+            )?,
         };
 
+        // 将旧数据文件保存到older_files中
+        let mut older_files = HashMap::new();
+        for file in data_files {
+            older_files.insert(file.get_file_id(), file);
+        }
+
+        let active_fid = active_file.get_file_id();
+
         // 构造存储引擎实例
-        let engine = Self {
+        let mut engine = Self {
             options: Arc::new(opts.clone()),
             active_file: Arc::new(RwLock::new(active_file)),
             older_files: Arc::new(RwLock::new(older_files)),
             index: Box::new(index::new_indexer(opts.index_type.clone())),
             file_ids,
+            durable: Arc::new(PosState::new(active_fid)),
+            written: PosState::new(active_fid),
+            seq: AtomicU64::new(0),
+            write_lock: Mutex::new(()),
+            flusher: None,
+            file_pool,
+            blobs: Arc::new(BlobStore::open(
+                dir_path.clone(),
+                opts.key_provider.as_ref(),
+                opts.blob_file_size,
+                opts.read_only,
+            )?),
+            value_cache: match opts.value_cache_size {
+                0 => None,
+                size => Some(ValueCache::new(size)),
+            },
+            families: RwLock::new(Families::default()),
         };
 
         // 加载内存索引
         engine.load_index_from_data_files()?;
 
+        // 启动时磁盘上已有的数据都视为已持久化
+        let write_pos = {
+            let active_file = engine.active_file.read();
+            LogRecordPos::new(active_file.get_file_id(), active_file.get_write_off())
+        };
+        *engine.durable.pos.lock() = write_pos;
+        *engine.written.pos.lock() = write_pos;
+
+        // 按时间间隔持久化时，启动后台刷盘线程，只读模式下不需要
+        if let (SyncPolicy::Interval(interval), false) = (opts.sync_policy, opts.read_only) {
+            engine.flusher = Some(Flusher::start(
+                interval,
+                engine.active_file.clone(),
+                engine.blobs.clone(),
+                engine.durable.clone(),
+            ));
+        }
+
         Ok(engine)
     }
 
     pub fn put(&self, key: Bytes, value: Bytes) -> Result<()> {
+        self.put_with_expire(key, value, 0)
+    }
+
+    // 写入 key 并使用指定的过期时间，复制时按照 leader 记录中的过期时间写入
+    pub(crate) fn put_with_expire(&self, key: Bytes, value: Bytes, expire: u64) -> Result<()> {
+        if self.options.read_only {
+            return Err(DatabaseIsReadOnly);
+        }
         // 判断key的有效性
         if key.is_empty() {
             return Err(KeyIsEmpty);
         }
-        // 构造logRecord
-        let mut record = LogRecord {
-            key: key.to_vec(),
-            value: value.to_vec(),
-            rec_type: NORMAL,
-        };
+        let _guard = self.lock_writes();
+        self.put_entry(DEFAULT_CF_ID, &*self.index, &key, &value, expire)
+    }
+
+    // 写入 key 的新值并更新索引，调用方需要持有写锁
+    pub(crate) fn put_value(&self, key: &[u8], value: &[u8]) -> Result<()> {
+        self.put_entry(DEFAULT_CF_ID, &*self.index, key, value, 0)
+    }
+
+    // 写入列族 cf 中 key 的新值并更新列族的索引，expire 为过期时间的毫秒时间戳，0 表示不过期
+    pub(crate) fn put_entry(
+        &self,
+        cf: u32,
+        index: &dyn Indexer,
+        key: &[u8],
+        value: &[u8],
+        expire: u64,
+    ) -> Result<()> {
+        let (mut record, blob_ref) = self.new_put_record(cf, key, value, expire)?;
         // 追加写到活跃数据文件中
         let log_record_pos = self.append_log_record(&mut record)?;
         // 更新内存索引
-        let ok = self.index.put(key.to_vec(), log_record_pos);
+        let ok = index.put(key.to_vec(), log_record_pos);
+        if !ok {
+            return Err(IndexUpdateFailed);
+        }
+        self.blobs.track(cf, key, blob_ref);
+
+        Ok(())
+    }
+
+    // 构造写入 key 的记录，大 value 先写入 blob 文件，日志记录中只保存 BlobRef，blob 文件中的 value 不压缩
+    pub(crate) fn new_put_record(
+        &self,
+        cf: u32,
+        key: &[u8],
+        value: &[u8],
+        expire: u64,
+    ) -> Result<(LogRecord, Option<BlobRef>)> {
+        let blob_threshold = self.options.blob_threshold;
+        let (record, blob_ref) = if blob_threshold > 0 && value.len() >= blob_threshold {
+            let blob_ref = self.blobs.write(value)?;
+            let record = LogRecord {
+                key: key.to_vec(),
+                value: blob_ref.encode(),
+                rec_type: BLOB,
+                compression: Compression::None,
+                seq: 0,
+                cf,
+                expire,
+            };
+            (record, Some(blob_ref))
+        } else {
+            // 根据配置压缩value
+            let (value, compression) = maybe_compress(
+                self.options.compression,
+                self.options.compression_threshold,
+                value,
+            )?;
+            let record = LogRecord {
+                key: key.to_vec(),
+                value,
+                rec_type: NORMAL,
+                compression,
+                seq: 0,
+                cf,
+                expire,
+            };
+            (record, None)
+        };
+        Ok((record, blob_ref))
+    }
+
+    /// 从 reader 中流式写入 len 个字节的 value，不需要把整个 value 读入内存
+    ///
+    /// value 分块写入活跃文件并增量计算 CRC，完整的记录 sync 到磁盘之后才更新索引；
+    /// 开启了 blob 分离并且 len 不小于阈值时写入 blob 文件。流式写入的 value 不压缩。
+    /// 写入过程中其他写操作需要等待，读操作不受影响。
+    /// reader 出错或者提前结束时返回 FailedToReadValueStream，key 保持原来的值。
+    pub fn put_stream<R: Read>(&self, key: Bytes, mut reader: R, len: u64) -> Result<()> {
+        if self.options.read_only {
+            return Err(DatabaseIsReadOnly);
+        }
+        // 判断key的有效性
+        if key.is_empty() {
+            return Err(KeyIsEmpty);
+        }
+        let _guard = self.lock_writes();
+        let blob_threshold = self.options.blob_threshold;
+        let (pos, blob_ref) = if blob_threshold > 0 && len >= blob_threshold as u64 {
+            let blob_ref = self.blobs.write_stream(&mut reader, len)?;
+            let mut record = LogRecord {
+                key: key.to_vec(),
+                value: blob_ref.encode(),
+                rec_type: BLOB,
+                compression: Compression::None,
+                seq: 0,
+                cf: DEFAULT_CF_ID,
+                expire: 0,
+            };
+            let pos = self.append_log_record(&mut record)?;
+            // sync 时先持久化 blob 文件，再持久化数据文件
+            self.sync()?;
+            (pos, Some(blob_ref))
+        } else {
+            (self.write_value_stream(&key, &mut reader, len)?, None)
+        };
+        // 记录已经持久化，更新内存索引
+        let ok = self.index.put(key.to_vec(), pos);
         if !ok {
             return Err(IndexUpdateFailed);
         }
+        self.blobs.track(DEFAULT_CF_ID, &key, blob_ref);
 
         Ok(())
     }
 
+    // 流式写入一条完整的 NORMAL 记录并持久化，返回记录的位置
+    fn write_value_stream(
+        &self,
+        key: &[u8],
+        reader: &mut dyn Read,
+        len: u64,
+    ) -> Result<LogRecordPos> {
+        let seq = self.next_seq();
+        let header = LogRecord {
+            key: key.to_vec(),
+            value: Vec::new(),
+            rec_type: NORMAL,
+            compression: Compression::None,
+            seq,
+            cf: DEFAULT_CF_ID,
+            expire: 0,
+        }
+        .encode_header(len as usize);
+        let record_len = header.len() as u64 + len + 4;
+
+        // 持有可升级的读锁，阻止其他写入，但不影响读取活跃文件
+        let mut active_file = self.active_file.upgradable_read();
+        if active_file.get_write_off() + record_len > self.options.data_file_size {
+            let mut write_guard = RwLockUpgradableReadGuard::upgrade(active_file);
+            self.rotate_active_file(&mut write_guard)?;
+            active_file = RwLockWriteGuard::downgrade_to_upgradable(write_guard);
+        }
+        let pos =
+            LogRecordPos::with_seq(active_file.get_file_id(), active_file.get_write_off(), seq);
+
+        if let Err(e) = stream_log_record(&active_file, &header, reader, len) {
+            // 撤销写了一半的记录，并切换到新的活跃文件
+            let mut write_guard = RwLockUpgradableReadGuard::upgrade(active_file);
+            write_guard.truncate(pos.offset)?;
+            self.rotate_active_file(&mut write_guard)?;
+            return Err(e);
+        }
+        sync_data_file(&active_file, &self.blobs, &self.durable)?;
+        self.written.advance(LogRecordPos::new(
+            active_file.get_file_id(),
+            active_file.get_write_off(),
+        ));
+        Ok(pos)
+    }
+
     pub fn delete(&self, key: Bytes) -> Result<()> {
+        if self.options.read_only {
+            return Err(DatabaseIsReadOnly);
+        }
         // 判断key的有效性
         if key.is_empty() {
             return Err(Errors::KeyIsEmpty);
         }
+        let _guard = self.lock_writes();
+        self.delete_value(&key)
+    }
+
+    // 删除 key 并更新索引，调用方需要持有写锁
+    pub(crate) fn delete_value(&self, key: &[u8]) -> Result<()> {
+        self.delete_entry(DEFAULT_CF_ID, &*self.index, key)
+    }
+
+    // 删除列族 cf 中的 key 并更新列族的索引
+    pub(crate) fn delete_entry(&self, cf: u32, index: &dyn Indexer, key: &[u8]) -> Result<()> {
         // 从内存索引当中取出对应的数据，不存在的话就直接返回
-        let pos = self.index.get(key.to_vec());
+        let pos = index.get(key.to_vec());
         if pos.is_none() {
             return Ok(());
         }
@@ -118,16 +412,21 @@ impl Engine {
             key: key.to_vec(),
             value: Default::default(),
             rec_type: DELETE,
+            compression: Compression::None,
+            seq: 0,
+            cf,
+            expire: 0,
         };
 
         // 写入到数据文件当中
         self.append_log_record(&mut record)?;
 
         // 删除内存索引对应的key
-        let ok = self.index.delete(key.to_vec());
+        let ok = index.delete(key.to_vec());
         if !ok {
             return Err(Errors::IndexUpdateFailed);
         }
+        self.blobs.track(cf, key, None);
 
         Ok(())
     }
@@ -144,67 +443,348 @@ impl Engine {
             return Err(KeyNotFound);
         }
 
-        // 从对应数据文件中拿到log record
-        let log_record_pos = pos.unwrap();
+        self.get_value_by_position(&pos.unwrap())
+    }
+
+    /// 判断 key 是否存在，只检查内存索引，不读取 value
+    pub fn exists(&self, key: Bytes) -> Result<bool> {
+        if key.is_empty() {
+            return Err(KeyIsEmpty);
+        }
+        // 默认列族中的记录没有过期时间，删除的 key 已经从索引中移除
+        Ok(self.index.get(key.to_vec()).is_some())
+    }
+
+    /// value 缓存的统计信息，未开启缓存时返回 None
+    pub fn cache_stats(&self) -> Option<CacheStats> {
+        self.value_cache.as_ref().map(|cache| cache.stats())
+    }
+
+    /// 每个 blob 文件的大小以及其中垃圾数据的大小
+    pub fn blob_stats(&self) -> Vec<BlobFileStat> {
+        self.blobs.stats()
+    }
+
+    // 根据 BLOB 记录中编码的 BlobRef 读取 value
+    pub(crate) fn read_blob(&self, blob_ref: &[u8]) -> Result<Bytes> {
+        self.blobs.read(&BlobRef::decode(blob_ref)?)
+    }
+
+    /// 获取存储引擎的统计信息
+    pub fn stat(&self) -> Result<Stat> {
+        let data_file_num = self.older_files.read().len() + 1;
+        let dir = match fs::read_dir(&self.options.dir_path) {
+            Ok(dir) => dir,
+            Err(_) => return Err(FailedToReadDatabaseDir),
+        };
+        let mut disk_size = 0;
+        for entry in dir.flatten() {
+            if entry
+                .file_name()
+                .to_string_lossy()
+                .ends_with(DATA_FILE_NAME_SUFFIX)
+            {
+                disk_size += entry.metadata().map_or(0, |meta| meta.len());
+            }
+        }
+        Ok(Stat {
+            key_num: self.index.key_count(),
+            data_file_num,
+            disk_size,
+        })
+    }
+
+    // 根据位置信息获取value，优先从缓存中读取
+    pub(crate) fn get_value_by_position(&self, log_record_pos: &LogRecordPos) -> Result<Bytes> {
+        if let Some(cache) = self.value_cache.as_ref() {
+            if let Some(value) = cache.get(log_record_pos) {
+                return Ok(value);
+            }
+        }
+
+        let log_record = self.read_log_record_at(log_record_pos)?.record;
+        // 已过期的记录视为不存在
+        if log_record.expire != 0 && log_record.expire <= now_millis() {
+            return Err(KeyNotFound);
+        }
+
+        // 判断类型，value 存放在 blob 文件中时读取对应的 blob
+        let value = match log_record.rec_type {
+            DELETE | BATCH => return Err(KeyNotFound),
+            NORMAL => Bytes::from(log_record.value),
+            BLOB => self.read_blob(&log_record.value)?,
+            MERGE => self.fold_merge(&log_record)?,
+        };
+
+        // 放入缓存并返回对应的value，带过期时间的记录不缓存
+        if let (Some(cache), 0) = (self.value_cache.as_ref(), log_record.expire) {
+            cache.put(*log_record_pos, value.clone());
+        }
+        Ok(value)
+    }
+
+    // 读取指定位置的 log record，位置超出文件末尾时返回 ReadDataFileEOF
+    pub(crate) fn read_log_record_at(&self, pos: &LogRecordPos) -> Result<ReadLogRecord> {
+        self.with_data_file(pos.file_id, |data_file| {
+            data_file.read_log_record(pos.offset)
+        })
+    }
+
+    // 对 file_id 对应的数据文件执行 f
+    pub(crate) fn with_data_file<T, F>(&self, file_id: u32, f: F) -> Result<T>
+    where
+        F: FnOnce(&DataFile) -> Result<T>,
+    {
         let active_file = self.active_file.read();
+        if active_file.get_file_id() == file_id {
+            return f(&active_file);
+        }
         let older_files = self.older_files.read();
-        let log_record = match active_file.get_file_id() == log_record_pos.file_id {
-            true => active_file.read_log_record(log_record_pos.offset)?.record,
-            false => {
-                let data_file = older_files.get(&log_record_pos.file_id);
-                if data_file.is_none() {
-                    // 找不到数据文件
-                    return Err(DataFileNotFound);
-                }
-                data_file
-                    .unwrap()
-                    .read_log_record(log_record_pos.offset)?
-                    .record
-            }
+        match older_files.get(&file_id) {
+            Some(data_file) => f(data_file),
+            // 找不到数据文件
+            None => Err(DataFileNotFound),
+        }
+    }
+
+    pub(crate) fn blobs(&self) -> &BlobStore {
+        &self.blobs
+    }
+
+    // file_id 之后的下一个数据文件，不存在时返回 None
+    pub(crate) fn next_file_id(&self, file_id: u32) -> Option<u32> {
+        let active_fid = self.active_file.read().get_file_id();
+        let older_files = self.older_files.read();
+        older_files
+            .keys()
+            .copied()
+            .chain(std::iter::once(active_fid))
+            .filter(|id| *id > file_id)
+            .min()
+    }
+
+    // 阻塞直到写入位置不再等于 pos 或者超时，返回当前的写入位置
+    pub(crate) fn wait_for_write(&self, pos: LogRecordPos, timeout: Duration) -> LogRecordPos {
+        let mut written = self.written.pos.lock();
+        if *written == pos {
+            self.written.cond.wait_for(&mut written, timeout);
+        }
+        *written
+    }
+
+    // 串行化所有写操作，持有写锁期间读取的索引与追加写入之间不会有其他写入
+    pub(crate) fn lock_writes(&self) -> MutexGuard<'_, ()> {
+        self.write_lock.lock()
+    }
+
+    pub(crate) fn options(&self) -> &Options {
+        &self.options
+    }
+
+    /// 持久化当前活跃文件，并更新已持久化的位置，只读模式下没有需要持久化的数据
+    pub fn sync(&self) -> Result<()> {
+        if self.options.read_only {
+            return Ok(());
+        }
+        let active_file = self.active_file.read();
+        sync_data_file(&active_file, &self.blobs, &self.durable)
+    }
+
+    /// 下一条数据将要写入的位置，该位置之前的记录均已完整写入（但不一定已持久化）
+    ///
+    /// 流式写入过程中活跃文件末尾不完整的记录不包含在内。
+    pub fn write_pos(&self) -> LogRecordPos {
+        *self.written.pos.lock()
+    }
+
+    /// 最后一次持久化的位置，该位置之前的数据均已 sync 到磁盘
+    pub fn durable_pos(&self) -> LogRecordPos {
+        *self.durable.pos.lock()
+    }
+
+    /// 阻塞直到 pos 之前的数据都已持久化
+    /// 按时间间隔持久化时等待后台线程刷盘，其他策略下直接 sync 活跃文件
+    pub fn wait_for_durable(&self, pos: LogRecordPos) -> Result<()> {
+        // 不能等待尚未写入的位置
+        let write_pos = self.write_pos();
+        let target = match pos_covers(&write_pos, &pos) {
+            true => pos,
+            false => write_pos,
         };
 
-        // 判断类型
-        if log_record.rec_type == DELETE {
-            return Err(KeyNotFound);
+        if let SyncPolicy::Interval(_) = self.options.sync_policy {
+            let mut durable_pos = self.durable.pos.lock();
+            while !pos_covers(&durable_pos, &target) {
+                self.durable.cond.wait(&mut durable_pos);
+            }
+            return Ok(());
         }
 
-        // 返回对应的value
-        Ok(log_record.value.into())
+        if pos_covers(&self.durable_pos(), &target) {
+            return Ok(());
+        }
+        self.sync()
     }
 
-    fn append_log_record(&self, record: &mut LogRecord) -> Result<LogRecordPos> {
-        let dir_path = self.options.dir_path.clone();
+    // 分配序列号并追加写入记录，返回的位置中包含序列号，调用方需要持有写锁
+    pub(crate) fn append_log_record(&self, record: &mut LogRecord) -> Result<LogRecordPos> {
+        if self.options.read_only {
+            return Err(DatabaseIsReadOnly);
+        }
 
         // 输入数据进行编码
+        record.seq = self.next_seq();
         let enc_record = record.encode();
-        let record_len = enc_record.len() as u64;
+        let pos = self.append_encoded(&enc_record)?;
+
+        // 构造数据索引信息
+        Ok(LogRecordPos {
+            seq: record.seq,
+            ..pos
+        })
+    }
+
+    // 把一批记录连续写入同一个数据文件，之前写入一条 BATCH 标记，返回每条记录的位置
+    //
+    // 整批记录只调用一次写入，其他读取方不会看到写了一半的批量；
+    // 崩溃时写了一半的批量在重新打开时被截断。
+    pub(crate) fn append_batch(&self, records: &mut [LogRecord]) -> Result<Vec<LogRecordPos>> {
+        if self.options.read_only {
+            return Err(DatabaseIsReadOnly);
+        }
+        if records.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let marker = LogRecord {
+            key: Vec::new(),
+            value: (records.len() as u32).to_be_bytes().to_vec(),
+            rec_type: BATCH,
+            compression: Compression::None,
+            seq: self.next_seq(),
+            cf: DEFAULT_CF_ID,
+            expire: 0,
+        };
+        let mut buf = marker.encode();
+        let mut offsets = Vec::with_capacity(records.len());
+        for record in records.iter_mut() {
+            record.seq = self.next_seq();
+            offsets.push((buf.len() as u64, record.seq));
+            buf.extend_from_slice(&record.encode());
+        }
+        let pos = self.append_encoded(&buf)?;
 
+        Ok(offsets
+            .into_iter()
+            .map(|(offset, seq)| LogRecordPos::with_seq(pos.file_id, pos.offset + offset, seq))
+            .collect())
+    }
+
+    // 把编码后的数据追加写到活跃文件，并根据持久化策略 sync，返回写入的起始位置
+    fn append_encoded(&self, buf: &[u8]) -> Result<LogRecordPos> {
         // 获取并写入到当前活跃文件
         let mut active_file = self.active_file.write();
+        let pos = self.write_record_locked(&mut active_file, buf)?;
+        // 根据持久化策略决定是否sync活跃文件
+        let need_sync = match self.options.sync_policy {
+            SyncPolicy::Always => true,
+            SyncPolicy::EveryNBytes(n) => active_file.get_unsynced_bytes() >= n,
+            SyncPolicy::Interval(_) | SyncPolicy::Never => false,
+        };
+        if need_sync {
+            sync_data_file(&active_file, &self.blobs, &self.durable)?;
+        }
+        self.written.advance(LogRecordPos::new(
+            active_file.get_file_id(),
+            active_file.get_write_off(),
+        ));
+        Ok(pos)
+    }
+
+    // 分配下一个序列号
+    pub(crate) fn next_seq(&self) -> u64 {
+        self.seq.fetch_add(1, Ordering::SeqCst) + 1
+    }
+
+    // 最后分配的序列号
+    pub(crate) fn current_seq(&self) -> u64 {
+        self.seq.load(Ordering::SeqCst)
+    }
+
+    // 跳过 seq 及之前的序列号，之后分配的序列号都大于 seq
+    pub(crate) fn skip_seq(&self, seq: u64) {
+        self.seq.fetch_max(seq, Ordering::SeqCst);
+    }
+
+    // 追加写编码后的记录到活跃文件，调用方需要持有活跃文件的写锁
+    fn write_record_locked(
+        &self,
+        active_file: &mut DataFile,
+        enc_record: &[u8],
+    ) -> Result<LogRecordPos> {
         // 判断活跃文件大小
-        if active_file.get_write_off() + record_len > self.options.data_file_size {
-            active_file.sync()?;
-            // 将活跃文件转换为旧的数据文件，存储到map中
-            let current_fid = active_file.get_file_id();
-            let mut older_files = self.older_files.write();
-            let old_file = DataFile::new(dir_path.clone(), current_fid)?;
-            older_files.insert(current_fid, old_file);
-            // 打开新的活跃数据文件
-            let new_file = DataFile::new(dir_path.clone(), current_fid + 1)?;
-            *active_file = new_file;
+        if active_file.get_write_off() + enc_record.len() as u64 > self.options.data_file_size {
+            self.rotate_active_file(active_file)?;
         }
         // 追加写数据到当前活跃文件中
-        let write_off = active_file.write(&enc_record)?;
-        // 根据配置项决定是否sync活跃文件
-        if self.options.sync_writes {
-            active_file.sync()?;
+        let write_off = active_file.get_write_off();
+        active_file.write(enc_record)?;
+        Ok(LogRecordPos::new(active_file.get_file_id(), write_off))
+    }
+
+    // 持久化并关闭当前活跃文件，打开新的活跃文件
+    // 把 count 个完整的数据文件接到活跃文件之后，install 负责把文件放到 first_id 开始的位置，
+    // 之后打开新的活跃文件，返回 first_id。调用方需要持有写锁
+    pub(crate) fn attach_data_files<F>(&self, count: u32, install: F) -> Result<u32>
+    where
+        F: FnOnce(u32) -> Result<()>,
+    {
+        let dir_path = self.options.dir_path.clone();
+        let key_provider = self.options.key_provider.as_ref();
+        let mut active_file = self.active_file.write();
+        sync_data_file(&active_file, &self.blobs, &self.durable)?;
+        let current_fid = active_file.get_file_id();
+        let first_id = current_fid + 1;
+        install(first_id)?;
+
+        // 当前活跃文件和接入的文件都作为旧的数据文件
+        let mut older_files = self.older_files.write();
+        for file_id in current_fid..first_id + count {
+            let data_file = open_older_file(
+                dir_path.clone(),
+                file_id,
+                key_provider,
+                self.file_pool.as_ref(),
+                false,
+            )?;
+            older_files.insert(file_id, data_file);
         }
+        *active_file = DataFile::new(dir_path, first_id + count, key_provider)?;
+        sync_data_file(&active_file, &self.blobs, &self.durable)?;
+        self.written
+            .advance(LogRecordPos::new(active_file.get_file_id(), 0));
+        Ok(first_id)
+    }
 
-        // 构造数据索引信息
-        Ok(LogRecordPos {
-            file_id: active_file.get_file_id(),
-            offset: write_off as u64,
-        })
+    fn rotate_active_file(&self, active_file: &mut DataFile) -> Result<()> {
+        let dir_path = self.options.dir_path.clone();
+        sync_data_file(active_file, &self.blobs, &self.durable)?;
+        // 将活跃文件转换为旧的数据文件，存储到map中
+        let current_fid = active_file.get_file_id();
+        let mut older_files = self.older_files.write();
+        let key_provider = self.options.key_provider.as_ref();
+        let old_file = open_older_file(
+            dir_path.clone(),
+            current_fid,
+            key_provider,
+            self.file_pool.as_ref(),
+            false,
+        )?;
+        older_files.insert(current_fid, old_file);
+        // 打开新的活跃数据文件
+        *active_file = DataFile::new(dir_path, current_fid + 1, key_provider)?;
+        // 旧文件已全部持久化，新文件从0开始
+        sync_data_file(active_file, &self.blobs, &self.durable)
     }
 
     // 从数据文件中加载索引
@@ -213,55 +793,213 @@ impl Engine {
         if self.file_ids.is_empty() {
             return Ok(());
         }
-        let active_file = self.active_file.read();
+        let mut active_file = self.active_file.write();
         let older_files = self.older_files.read();
+        let mut incomplete_tail = false;
         // 遍历每个文件id,取出对应的数据文件，并加载其中的数据
         for (i, file_id) in self.file_ids.iter().enumerate() {
-            let mut offset = 0;
-            loop {
-                let log_record_read = match *file_id == active_file.get_file_id() {
-                    true => active_file.read_log_record(offset),
-                    false => {
-                        let data_file = older_files.get(file_id).unwrap();
-                        data_file.read_log_record(offset)
-                    }
-                };
-                let (log_record, size) = match log_record_read {
-                    Ok(result) => (result.record, result.size),
-                    Err(e) => {
-                        if e == ReadDataFileEOF {
-                            break;
-                        }
-                        return Err(e);
-                    }
-                };
-                // 构建内存索引
-                let log_record_pos = LogRecordPos {
-                    file_id: *file_id,
-                    offset,
-                };
-                let ok = match log_record.rec_type {
-                    NORMAL => self.index.put(log_record.key.to_vec(), log_record_pos),
-                    DELETE => self.index.delete(log_record.key.to_vec()),
-                };
-                if !ok {
-                    return Err(IndexUpdateFailed);
+            // 活跃文件可能正在被写入，或者写入时崩溃，末尾不完整的记录视为文件结束
+            let (offset, incomplete) = match *file_id == active_file.get_file_id() {
+                true => self.load_index_from_data_file(&active_file, 0, true)?,
+                false => {
+                    let data_file = older_files.get(file_id).unwrap();
+                    self.load_index_from_data_file(data_file, 0, false)?
                 }
-                // 递增offset
-                offset += size as u64
-            }
+            };
 
             // 设置活跃文件的offset
             if i == self.file_ids.len() - 1 {
-                active_file.set_write_off(offset)
+                active_file.set_write_off(offset);
+                incomplete_tail = incomplete;
             }
         }
+        drop(older_files);
+
+        // 截断崩溃时写了一半的记录或批量写入，并切换到新的活跃文件
+        if incomplete_tail && !self.options.read_only {
+            let offset = active_file.get_write_off();
+            active_file.truncate(offset)?;
+            self.rotate_active_file(&mut active_file)?;
+        }
 
         Ok(())
     }
+
+    /// 只读模式下加载写入进程新追加的记录以及新创建的数据文件
+    pub fn refresh(&self) -> Result<()> {
+        if !self.options.read_only {
+            return Ok(());
+        }
+        let dir_path = self.options.dir_path.clone();
+        let key_provider = self.options.key_provider.as_ref();
+        let mut active_file = self.active_file.write();
+        loop {
+            // 写入进程正在写入的批量，下次从批量的起始位置重新读取
+            let (offset, _) =
+                self.load_index_from_data_file(&active_file, active_file.get_write_off(), true)?;
+            active_file.set_write_off(offset);
+
+            // 写入进程切换活跃文件时，旧文件已经全部写完
+            let next_fid = active_file.get_file_id() + 1;
+            let new_file = match DataFile::new_read_only(dir_path.clone(), next_fid, key_provider) {
+                Ok(data_file) => data_file,
+                Err(DataFileNotFound) => break,
+                Err(e) => return Err(e),
+            };
+            // 读取检查新文件之前追加的剩余记录
+            let (offset, _) = self.load_index_from_data_file(&active_file, offset, false)?;
+            active_file.set_write_off(offset);
+
+            let current_fid = active_file.get_file_id();
+            let old_file = open_older_file(
+                dir_path.clone(),
+                current_fid,
+                key_provider,
+                self.file_pool.as_ref(),
+                true,
+            )?;
+            self.older_files.write().insert(current_fid, old_file);
+            *active_file = new_file;
+        }
+        // 通知订阅者有新的数据
+        self.written.advance(LogRecordPos::new(
+            active_file.get_file_id(),
+            active_file.get_write_off(),
+        ));
+        Ok(())
+    }
+
+    // 从 offset 开始加载一个数据文件中的记录，返回最后一条记录之后的位置，以及末尾是否有不完整的数据
+    // allow_partial 为 true 时，末尾校验失败的记录视为尚未写完
+    fn load_index_from_data_file(
+        &self,
+        data_file: &DataFile,
+        mut offset: u64,
+        allow_partial: bool,
+    ) -> Result<(u64, bool)> {
+        // 正在读取的批量写入的起始位置、记录数量，以及已经读到的记录
+        let mut batch: Option<(u64, usize)> = None;
+        let mut batch_records = Vec::new();
+        let partial = loop {
+            let (log_record, size) = match data_file.read_log_record(offset) {
+                Ok(result) => (result.record, result.size),
+                // header 只写了一部分时读到的长度为0，但文件中仍有剩余的数据
+                Err(ReadDataFileEOF) => {
+                    break allow_partial && data_file.read_at(&mut [0u8; 1], offset)? > 0
+                }
+                // 只有活跃文件末尾的记录才可能没有写完，批量写入中的会随整个批量一起丢弃，
+                // 旧的数据文件以及文件中间校验失败的记录说明数据已经损坏
+                Err(InvalidLogRecordCrc)
+                    if allow_partial && data_file.is_tail_record(offset)? =>
+                {
+                    break true
+                }
+                Err(e) => return Err(e),
+            };
+            // 构建内存索引
+            let log_record_pos =
+                LogRecordPos::with_seq(data_file.get_file_id(), offset, log_record.seq);
+            self.seq.fetch_max(log_record.seq, Ordering::SeqCst);
+            if log_record.rec_type == BATCH {
+                let count = decode_batch_count(&log_record.value)?;
+                batch = Some((offset, count));
+                batch_records.clear();
+            } else if let Some((_, count)) = batch {
+                batch_records.push((log_record, log_record_pos));
+                // 批量中的记录全部读到之后才更新索引
+                if batch_records.len() == count {
+                    for (log_record, log_record_pos) in batch_records.drain(..) {
+                        self.apply_log_record(&log_record, log_record_pos)?;
+                    }
+                    batch = None;
+                }
+            } else {
+                self.apply_log_record(&log_record, log_record_pos)?;
+            }
+            // 递增offset
+            offset += size as u64
+        };
+        // 没有读完的批量写入视为没有写入
+        match batch {
+            Some((batch_offset, _)) => Ok((batch_offset, true)),
+            None => Ok((offset, partial)),
+        }
+    }
+
+    // 根据记录所属的列族更新对应的内存索引
+    fn apply_log_record(&self, log_record: &LogRecord, log_record_pos: LogRecordPos) -> Result<()> {
+        match log_record.cf {
+            DEFAULT_CF_ID => self.load_log_record(&*self.index, log_record, log_record_pos),
+            SYSTEM_CF_ID => self.apply_cf_record(log_record),
+            cf => {
+                // 已经删除的列族中的记录直接跳过
+                let family = self.families.read().get(cf);
+                match family {
+                    Some(family) => {
+                        self.load_log_record(&*family.index, log_record, log_record_pos)
+                    }
+                    None => Ok(()),
+                }
+            }
+        }
+    }
+
+    // 根据加载的记录更新列族的内存索引
+    fn load_log_record(
+        &self,
+        index: &dyn Indexer,
+        log_record: &LogRecord,
+        log_record_pos: LogRecordPos,
+    ) -> Result<()> {
+        let (cf, key) = (log_record.cf, &log_record.key);
+        let ok = match log_record.rec_type {
+            // 已过期的记录视为删除
+            _ if log_record.expire != 0 && log_record.expire <= now_millis() => {
+                self.blobs.track(cf, key, None);
+                index.delete(key.to_vec());
+                true
+            }
+            NORMAL => {
+                self.blobs.track(cf, key, None);
+                index.put(key.to_vec(), log_record_pos)
+            }
+            BLOB => {
+                let blob_ref = BlobRef::decode(&log_record.value)?;
+                self.blobs.track(cf, key, Some(blob_ref));
+                index.put(key.to_vec(), log_record_pos)
+            }
+            // 删除的 key 可能因为过期已经不在索引中
+            DELETE => {
+                self.blobs.track(cf, key, None);
+                index.delete(key.to_vec());
+                true
+            }
+            // 合并链上的值仍然被引用，不更新 blob 统计
+            MERGE => index.put(key.to_vec(), log_record_pos),
+            // 批量写入的标记在读取数据文件时处理
+            BATCH => true,
+        };
+        if !ok {
+            return Err(IndexUpdateFailed);
+        }
+        Ok(())
+    }
 }
 
-fn load_data_files(dir_path: PathBuf) -> Result<Vec<DataFile>> {
+// 解码 BATCH 标记中的记录数量
+fn decode_batch_count(value: &[u8]) -> Result<usize> {
+    match <[u8; 4]>::try_from(value) {
+        Ok(buf) => Ok(u32::from_be_bytes(buf) as usize),
+        Err(_) => Err(DataDirectoryCorrupted),
+    }
+}
+
+fn load_data_files(
+    dir_path: PathBuf,
+    key_provider: Option<&Arc<dyn KeyProvider>>,
+    file_pool: Option<&Arc<FilePool>>,
+    read_only: bool,
+) -> Result<Vec<DataFile>> {
     // 读取数据目录
     let dir = fs::read_dir(dir_path.clone());
     if dir.is_err() {
@@ -270,22 +1008,20 @@ fn load_data_files(dir_path: PathBuf) -> Result<Vec<DataFile>> {
 
     let mut file_ids: Vec<u32> = Vec::new();
     let mut data_files: Vec<DataFile> = Vec::new();
-    for file in dir.unwrap() {
-        if let Ok(entry) = file {
-            // 拿到文件名
-            let file_os_str = entry.file_name();
-            let file_name = file_os_str.to_str().unwrap();
-            // 判断文件名是否以指定后缀结尾
-            if file_name.ends_with(DATA_FILE_NAME_SUFFIX) {
-                let split_names: Vec<&str> = file_name.split(".").collect();
-                let file_id = match split_names[0].parse::<u32>() {
-                    Ok(fid) => fid,
-                    Err(_) => {
-                        return Err(DataDirectoryCorrupted);
-                    }
-                };
-                file_ids.push(file_id);
-            }
+    for entry in dir.unwrap().flatten() {
+        // 拿到文件名
+        let file_os_str = entry.file_name();
+        let file_name = file_os_str.to_str().unwrap();
+        // 判断文件名是否以指定后缀结尾
+        if file_name.ends_with(DATA_FILE_NAME_SUFFIX) {
+            let split_names: Vec<&str> = file_name.split('.').collect();
+            let file_id = match split_names[0].parse::<u32>() {
+                Ok(fid) => fid,
+                Err(_) => {
+                    return Err(DataDirectoryCorrupted);
+                }
+            };
+            file_ids.push(file_id);
         }
     }
     // 如果没有数据文件，则直接返回
@@ -294,22 +1030,736 @@ fn load_data_files(dir_path: PathBuf) -> Result<Vec<DataFile>> {
     }
     // 对文件ID进行排序，从小到大依次加载
     file_ids.sort();
-    // 遍历所有的文件ID，依次打开对应的数据文件
+    // 遍历所有的文件ID，依次打开对应的数据文件，最后一个为活跃文件，文件句柄常驻
+    let active_fid = *file_ids.last().unwrap();
     for file_id in file_ids {
-        let data_file = DataFile::new(dir_path.clone(), file_id)?;
+        let data_file = match (file_id == active_fid, read_only) {
+            (true, false) => DataFile::new(dir_path.clone(), file_id, key_provider)?,
+            (true, true) => DataFile::new_read_only(dir_path.clone(), file_id, key_provider)?,
+            (false, _) => open_older_file(
+                dir_path.clone(),
+                file_id,
+                key_provider,
+                file_pool,
+                read_only,
+            )?,
+        };
         data_files.push(data_file);
     }
 
     Ok(data_files)
 }
 
+// 打开旧数据文件，配置了句柄池时由句柄池管理文件句柄
+fn open_older_file(
+    dir_path: PathBuf,
+    file_id: u32,
+    key_provider: Option<&Arc<dyn KeyProvider>>,
+    file_pool: Option<&Arc<FilePool>>,
+    read_only: bool,
+) -> Result<DataFile> {
+    match (file_pool, read_only) {
+        (Some(pool), _) => {
+            DataFile::new_pooled(dir_path, file_id, key_provider, pool.clone(), read_only)
+        }
+        (None, false) => DataFile::new(dir_path, file_id, key_provider),
+        (None, true) => DataFile::new_read_only(dir_path, file_id, key_provider),
+    }
+}
+
+impl Drop for Engine {
+    fn drop(&mut self) {
+        // 先停止后台刷盘线程，再做最后一次持久化
+        if let Some(flusher) = self.flusher.take() {
+            flusher.stop();
+        }
+        if let Err(e) = self.sync() {
+            error!("failed to sync data file on close: {}", e);
+        }
+    }
+}
+
+impl Flusher {
+    fn start(
+        interval: std::time::Duration,
+        active_file: Arc<RwLock<DataFile>>,
+        blobs: Arc<BlobStore>,
+        durable: Arc<PosState>,
+    ) -> Self {
+        let (stop_sender, stop_receiver) = mpsc::channel::<()>();
+        let handle = std::thread::spawn(move || {
+            // 收到停止信号或者引擎已关闭时退出
+            while let Err(RecvTimeoutError::Timeout) = stop_receiver.recv_timeout(interval) {
+                let active_file = active_file.read();
+                if active_file.get_unsynced_bytes() == 0 {
+                    continue;
+                }
+                if let Err(e) = sync_data_file(&active_file, &blobs, &durable) {
+                    error!("background sync data file err: {}", e);
+                }
+            }
+        });
+
+        Self {
+            stop_sender: Some(stop_sender),
+            handle: Some(handle),
+        }
+    }
+
+    fn stop(mut self) {
+        // 关闭发送端，后台线程会随之退出
+        self.stop_sender.take();
+        if let Some(handle) = self.handle.take() {
+            let _ = handle.join();
+        }
+    }
+}
+
+// 持久化数据文件，并将已持久化位置推进到文件当前的写入位置
+// 先持久化 blob 文件，保证已持久化的记录引用的 blob 都已经落盘
+fn sync_data_file(data_file: &DataFile, blobs: &BlobStore, durable: &PosState) -> Result<()> {
+    blobs.sync()?;
+    let pos = LogRecordPos::new(data_file.get_file_id(), data_file.get_write_off());
+    data_file.sync()?;
+    durable.advance(pos);
+    Ok(())
+}
+
+// 写入 header 和 key，再分块写入 value，最后写入增量计算的 CRC
+fn stream_log_record(
+    data_file: &DataFile,
+    header: &[u8],
+    reader: &mut dyn Read,
+    len: u64,
+) -> Result<()> {
+    let mut hasher = crc32fast::Hasher::new();
+    hasher.update(header);
+    data_file.write(header)?;
+    let mut buf = vec![0u8; VALUE_CHUNK_SIZE.min(len as usize)];
+    let mut remaining = len;
+    while remaining > 0 {
+        let chunk = &mut buf[..VALUE_CHUNK_SIZE.min(remaining as usize)];
+        read_value_chunk(reader, chunk)?;
+        hasher.update(chunk);
+        data_file.write(chunk)?;
+        remaining -= chunk.len() as u64;
+    }
+    data_file.write(&hasher.finalize().to_be_bytes())?;
+    Ok(())
+}
+
+// 判断 a 是否不早于 b，位置先按文件id比较，再按offset比较
+pub(crate) fn pos_covers(a: &LogRecordPos, b: &LogRecordPos) -> bool {
+    a.file_id > b.file_id || (a.file_id == b.file_id && a.offset >= b.offset)
+}
+
 fn check_options(opts: Options) -> Option<Errors> {
     let dir_path = opts.dir_path.to_str();
-    if dir_path.is_none() || dir_path.unwrap().len() == 0 {
+    if dir_path.is_none() || dir_path.unwrap().is_empty() {
         return Some(DirPathIsEmpty);
     }
-    if opts.data_file_size <= 0 {
+    if opts.data_file_size == 0 {
         return Some(DataFileSizeTooSmall);
     }
+    match opts.sync_policy {
+        SyncPolicy::EveryNBytes(0) => return Some(InvalidSyncPolicy),
+        SyncPolicy::Interval(d) if d.is_zero() => return Some(InvalidSyncPolicy),
+        _ => {}
+    }
+    if let Err(e) = index::check_index_type(&opts.index_type) {
+        return Some(e);
+    }
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::StaticKeyProvider;
+    use std::time::Duration;
+
+    fn open_engine(name: &str, sync_policy: SyncPolicy) -> (Engine, PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            sync_policy,
+            ..Default::default()
+        };
+        (Engine::open(opts).unwrap(), dir_path)
+    }
+
+    #[test]
+    fn test_engine_put_get_delete() {
+        let (engine, dir_path) = open_engine("fdb-put-get-delete", SyncPolicy::Never);
+
+        assert!(engine.put(Bytes::from("name"), Bytes::from("fdb")).is_ok());
+        assert_eq!(engine.get(Bytes::from("name")).unwrap(), Bytes::from("fdb"));
+
+        assert!(engine
+            .put(Bytes::from("name"), Bytes::from("fdb-rs"))
+            .is_ok());
+        assert_eq!(
+            engine.get(Bytes::from("name")).unwrap(),
+            Bytes::from("fdb-rs")
+        );
+
+        assert!(engine.delete(Bytes::from("name")).is_ok());
+        assert_eq!(engine.get(Bytes::from("name")), Err(KeyNotFound));
+        assert_eq!(engine.put(Bytes::new(), Bytes::from("v")), Err(KeyIsEmpty));
+
+        // 重启之后数据依然存在
+        assert!(engine.put(Bytes::from("a"), Bytes::from("1")).is_ok());
+        drop(engine);
+        let engine2 = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(engine2.get(Bytes::from("a")).unwrap(), Bytes::from("1"));
+        assert_eq!(engine2.get(Bytes::from("name")), Err(KeyNotFound));
+
+        drop(engine2);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_rotate_data_files() {
+        let dir_path = std::env::temp_dir().join("fdb-rotate");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 64,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        for i in 0..20 {
+            let key = format!("key-{:02}", i);
+            assert!(engine.put(Bytes::from(key), Bytes::from("value")).is_ok());
+        }
+        assert!(engine.write_pos().file_id > 0);
+        let stat = engine.stat().unwrap();
+        assert_eq!(stat.key_num, 20);
+        assert_eq!(stat.data_file_num, engine.write_pos().file_id as usize + 1);
+        assert!(stat.disk_size > 0);
+        drop(engine);
+
+        let engine2 = Engine::open(opts).unwrap();
+        for i in 0..20 {
+            let key = format!("key-{:02}", i);
+            assert_eq!(engine2.get(Bytes::from(key)).unwrap(), Bytes::from("value"));
+        }
+
+        drop(engine2);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_sync_policy_always() {
+        let (engine, dir_path) = open_engine("fdb-sync-always", SyncPolicy::Always);
+        engine.put(Bytes::from("k"), Bytes::from("v")).unwrap();
+        assert_eq!(engine.durable_pos(), engine.write_pos());
+        assert_eq!(engine.active_file.read().get_unsynced_bytes(), 0);
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_sync_policy_every_n_bytes() {
+        let (engine, dir_path) = open_engine("fdb-sync-n-bytes", SyncPolicy::EveryNBytes(32));
+        // 每条记录 4 + 4 + 10 + 4 = 22 字节，header 中包含 1 字节的序列号
+        engine
+            .put(Bytes::from("key1"), Bytes::from("0123456789"))
+            .unwrap();
+        assert_eq!(engine.active_file.read().get_unsynced_bytes(), 22);
+        assert_eq!(engine.durable_pos().offset, 0);
+
+        engine
+            .put(Bytes::from("key2"), Bytes::from("0123456789"))
+            .unwrap();
+        assert_eq!(engine.active_file.read().get_unsynced_bytes(), 0);
+        assert_eq!(engine.durable_pos(), engine.write_pos());
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_sync_policy_never() {
+        let (engine, dir_path) = open_engine("fdb-sync-never", SyncPolicy::Never);
+        engine.put(Bytes::from("k"), Bytes::from("v")).unwrap();
+        let write_pos = engine.write_pos();
+        assert!(!pos_covers(&engine.durable_pos(), &write_pos));
+
+        assert!(engine.wait_for_durable(write_pos).is_ok());
+        assert_eq!(engine.durable_pos(), write_pos);
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_sync_policy_interval() {
+        let (engine, dir_path) = open_engine(
+            "fdb-sync-interval",
+            SyncPolicy::Interval(Duration::from_millis(10)),
+        );
+        engine.put(Bytes::from("k"), Bytes::from("v")).unwrap();
+        let write_pos = engine.write_pos();
+        // 由后台线程完成持久化
+        assert!(engine.wait_for_durable(write_pos).is_ok());
+        assert!(pos_covers(&engine.durable_pos(), &write_pos));
+        assert_eq!(engine.active_file.read().get_unsynced_bytes(), 0);
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_compression() {
+        let dir_path = std::env::temp_dir().join("fdb-compression");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let value = Bytes::from("{\"name\":\"fdb\",\"tags\":[1,2,3]}".repeat(100));
+
+        // 依次使用不同的压缩配置写入同一个数据库
+        let settings = [
+            Compression::None,
+            Compression::Lz4,
+            Compression::Zstd,
+            Compression::Snappy,
+        ];
+        for (i, compression) in settings.iter().enumerate() {
+            let engine = Engine::open(Options {
+                dir_path: dir_path.clone(),
+                compression: *compression,
+                compression_threshold: 128,
+                ..Default::default()
+            })
+            .unwrap();
+            let write_pos = engine.write_pos();
+            engine
+                .put(Bytes::from(format!("key-{}", i)), value.clone())
+                .unwrap();
+            engine
+                .put(Bytes::from(format!("small-{}", i)), Bytes::from("v"))
+                .unwrap();
+            // 开启压缩后写入的数据明显变小
+            if *compression != Compression::None {
+                assert!(engine.write_pos().offset - write_pos.offset < value.len() as u64);
+            }
+        }
+
+        // 混合配置写入的数据均可正常读取
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        for i in 0..settings.len() {
+            assert_eq!(
+                engine.get(Bytes::from(format!("key-{}", i))).unwrap(),
+                value
+            );
+            assert_eq!(
+                engine.get(Bytes::from(format!("small-{}", i))).unwrap(),
+                Bytes::from("v")
+            );
+        }
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_encryption() {
+        let dir_path = std::env::temp_dir().join("fdb-encryption");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 128,
+            key_provider: Some(Arc::new(StaticKeyProvider::new([3u8; 32]))),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        for i in 0..10 {
+            let key = format!("secret-key-{}", i);
+            engine
+                .put(Bytes::from(key), Bytes::from("secret-value"))
+                .unwrap();
+        }
+        drop(engine);
+
+        // 数据文件中不包含明文
+        for entry in fs::read_dir(dir_path.clone()).unwrap() {
+            let content = fs::read(entry.unwrap().path()).unwrap();
+            assert!(!content.windows(12).any(|w| w == b"secret-value"));
+        }
+
+        let engine2 = Engine::open(opts.clone()).unwrap();
+        for i in 0..10 {
+            let key = format!("secret-key-{}", i);
+            assert_eq!(engine2.get(Bytes::from(key)).unwrap(), "secret-value");
+        }
+        drop(engine2);
+
+        // 使用错误的密钥或者不提供密钥打开
+        let wrong_opts = Options {
+            key_provider: Some(Arc::new(StaticKeyProvider::new([4u8; 32]))),
+            ..opts.clone()
+        };
+        assert!(matches!(
+            Engine::open(wrong_opts),
+            Err(Errors::WrongEncryptionKey)
+        ));
+        let plain_opts = Options {
+            key_provider: None,
+            ..opts
+        };
+        assert!(matches!(
+            Engine::open(plain_opts),
+            Err(Errors::DataFileIsEncrypted)
+        ));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_value_cache() {
+        let dir_path = std::env::temp_dir().join("fdb-value-cache");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            value_cache_size: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+
+        engine
+            .put(Bytes::from("a"), Bytes::from("value-a"))
+            .unwrap();
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "value-a");
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "value-a");
+        let stats = engine.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 1, 1));
+
+        // 更新之后位置改变，读取到的是新的value
+        engine
+            .put(Bytes::from("a"), Bytes::from("value-a2"))
+            .unwrap();
+        assert_eq!(engine.get(Bytes::from("a")).unwrap(), "value-a2");
+        engine.delete(Bytes::from("a")).unwrap();
+        assert_eq!(engine.get(Bytes::from("a")), Err(KeyNotFound));
+        let stats = engine.cache_stats().unwrap();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (1, 2, 2));
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_max_open_files() {
+        let dir_path = std::env::temp_dir().join("fdb-max-open-files");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 64,
+            max_open_files: 2,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        for i in 0..30 {
+            let key = format!("key-{:02}", i);
+            engine.put(Bytes::from(key), Bytes::from("value")).unwrap();
+        }
+        assert!(engine.file_pool.as_ref().unwrap().len() <= 2);
+        drop(engine);
+
+        // 重新打开，所有旧文件都能被按需打开读取
+        let engine2 = Engine::open(opts).unwrap();
+        assert!(engine2.older_files.read().len() > 2);
+        for i in (0..30).rev() {
+            let key = format!("key-{:02}", i);
+            assert_eq!(engine2.get(Bytes::from(key)).unwrap(), "value");
+            assert!(engine2.file_pool.as_ref().unwrap().len() <= 2);
+        }
+
+        drop(engine2);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_invalid_sync_policy() {
+        let opts = Options {
+            dir_path: std::env::temp_dir().join("fdb-invalid-sync"),
+            sync_policy: SyncPolicy::EveryNBytes(0),
+            ..Default::default()
+        };
+        assert!(matches!(Engine::open(opts), Err(InvalidSyncPolicy)));
+    }
+
+    #[test]
+    fn test_engine_read_only() {
+        let dir_path = std::env::temp_dir().join("fdb-read-only");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let read_only_opts = Options {
+            dir_path: dir_path.clone(),
+            read_only: true,
+            ..Default::default()
+        };
+        // 只读模式下不会创建目录和数据文件
+        assert!(matches!(
+            Engine::open(read_only_opts.clone()),
+            Err(FailedToReadDatabaseDir)
+        ));
+        assert!(!dir_path.exists());
+
+        let writer = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 64,
+            ..Default::default()
+        })
+        .unwrap();
+        for i in 0..5 {
+            let key = format!("key-{:02}", i);
+            writer.put(Bytes::from(key), Bytes::from("value")).unwrap();
+        }
+
+        let reader = Engine::open(read_only_opts).unwrap();
+        assert_eq!(reader.list_keys().unwrap().len(), 5);
+        assert!(matches!(
+            reader.put(Bytes::from("k"), Bytes::from("v")),
+            Err(DatabaseIsReadOnly)
+        ));
+        assert!(matches!(
+            reader.delete(Bytes::from("key-00")),
+            Err(DatabaseIsReadOnly)
+        ));
+
+        // 刷新之后可以读到新追加的记录和新创建的数据文件
+        let file_num = writer.stat().unwrap().data_file_num;
+        for i in 5..20 {
+            let key = format!("key-{:02}", i);
+            writer.put(Bytes::from(key), Bytes::from("value")).unwrap();
+        }
+        writer.delete(Bytes::from("key-00")).unwrap();
+        assert!(writer.stat().unwrap().data_file_num > file_num);
+        assert_eq!(reader.list_keys().unwrap().len(), 5);
+        reader.refresh().unwrap();
+        assert_eq!(reader.list_keys().unwrap().len(), 19);
+        assert_eq!(reader.write_pos(), writer.write_pos());
+        assert!(reader.get(Bytes::from("key-00")).is_err());
+        assert_eq!(reader.get(Bytes::from("key-19")).unwrap(), "value");
+
+        // 活跃文件末尾不完整的记录在刷新时被忽略
+        let write_pos = writer.write_pos();
+        let data_file = DataFile::new(dir_path.clone(), write_pos.file_id, None).unwrap();
+        data_file.write(&[1, 10, 10, b'k']).unwrap();
+        reader.refresh().unwrap();
+        assert_eq!(reader.write_pos(), write_pos);
+
+        drop(reader);
+        drop(writer);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_blob_values() {
+        let dir_path = std::env::temp_dir().join("fdb-blob-values");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            blob_threshold: 1024,
+            blob_file_size: 8 * 1024,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        // 小 value 不使用 blob 文件
+        engine.put(Bytes::from("small"), Bytes::from("v")).unwrap();
+        assert!(engine.blob_stats().is_empty());
+
+        let large = |i: u8| Bytes::from(vec![i; 3000]);
+        for i in 0..5 {
+            let key = format!("key-{}", i);
+            engine.put(Bytes::from(key), large(i)).unwrap();
+        }
+        // 数据文件中只保存 BlobRef
+        assert!(engine.stat().unwrap().disk_size < 1024);
+        let stats = engine.blob_stats();
+        assert_eq!(stats.len(), 3);
+        assert!(stats.iter().all(|stat| stat.garbage_size == 0));
+        assert_eq!(engine.get(Bytes::from("key-3")).unwrap(), large(3));
+
+        // 覆盖和删除之后旧的 blob 成为垃圾
+        engine.put(Bytes::from("key-0"), Bytes::from("v")).unwrap();
+        engine.delete(Bytes::from("key-1")).unwrap();
+        engine.put(Bytes::from("key-2"), large(9)).unwrap();
+        let garbage =
+            |stats: Vec<BlobFileStat>| -> u64 { stats.iter().map(|stat| stat.garbage_size).sum() };
+        assert_eq!(garbage(engine.blob_stats()), 9000);
+        assert_eq!(engine.get(Bytes::from("key-2")).unwrap(), large(9));
+        drop(engine);
+
+        // 重新打开之后垃圾统计不变
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(garbage(engine.blob_stats()), 9000);
+        assert_eq!(engine.get(Bytes::from("key-0")).unwrap(), "v");
+        assert!(engine.get(Bytes::from("key-1")).is_err());
+        assert_eq!(engine.get(Bytes::from("key-2")).unwrap(), large(9));
+        assert_eq!(engine.get(Bytes::from("key-4")).unwrap(), large(4));
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_put_stream() {
+        let dir_path = std::env::temp_dir().join("fdb-put-stream");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            data_file_size: 4 * 1024 * 1024,
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+        let value: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+        engine.put(Bytes::from("small"), Bytes::from("v")).unwrap();
+        engine
+            .put_stream(Bytes::from("large"), &value[..], value.len() as u64)
+            .unwrap();
+        // 记录已持久化
+        assert_eq!(engine.durable_pos(), engine.write_pos());
+        assert_eq!(engine.get(Bytes::from("large")).unwrap(), value);
+
+        // 超出活跃文件大小时切换到新的文件
+        engine
+            .put_stream(Bytes::from("large2"), &value[..], value.len() as u64)
+            .unwrap();
+        assert_eq!(engine.write_pos().file_id(), 1);
+
+        // reader 提前结束时保持原来的值，写了一半的记录被丢弃
+        let res = engine.put_stream(Bytes::from("small"), &value[..100], 1000);
+        assert_eq!(res, Err(Errors::FailedToReadValueStream));
+        assert_eq!(engine.get(Bytes::from("small")).unwrap(), "v");
+        let res = engine.put_stream(Bytes::from("new"), &value[..100], 1000);
+        assert_eq!(res, Err(Errors::FailedToReadValueStream));
+        assert_eq!(engine.get(Bytes::from("new")), Err(KeyNotFound));
+        engine.put(Bytes::from("after"), Bytes::from("1")).unwrap();
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.get(Bytes::from("large2")).unwrap(), value);
+        assert_eq!(engine.get(Bytes::from("small")).unwrap(), "v");
+        assert_eq!(engine.get(Bytes::from("new")), Err(KeyNotFound));
+        assert_eq!(engine.get(Bytes::from("after")).unwrap(), "1");
+        drop(engine);
+
+        // 开启 blob 分离时大 value 流式写入 blob 文件
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            blob_threshold: 1024,
+            ..Default::default()
+        })
+        .unwrap();
+        engine
+            .put_stream(Bytes::from("blob"), &value[..], value.len() as u64)
+            .unwrap();
+        assert_eq!(engine.blob_stats()[0].total_size, value.len() as u64);
+        assert_eq!(engine.get(Bytes::from("blob")).unwrap(), value);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_torn_record() {
+        let dir_path = std::env::temp_dir().join("fdb-torn-record");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let file_size = |file_id: u32| {
+            let file_name = dir_path.join(format!("{:09}.data", file_id));
+            fs::metadata(file_name).unwrap().len()
+        };
+        let set_file_size = |file_id: u32, size: u64| {
+            let file_name = dir_path.join(format!("{:09}.data", file_id));
+            let file = fs::OpenOptions::new().write(true).open(file_name).unwrap();
+            file.set_len(size).unwrap();
+        };
+
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(Bytes::from("before"), Bytes::from("v")).unwrap();
+        let value: Vec<u8> = (0..100 * 1024).map(|i| (i % 251) as u8).collect();
+        engine
+            .put_stream(Bytes::from("large"), &value[..], value.len() as u64)
+            .unwrap();
+        drop(engine);
+
+        // 模拟流式写入时崩溃，最后一条记录只写入了一部分
+        set_file_size(0, file_size(0) - 1000);
+        let engine = Engine::open(opts.clone()).unwrap();
+        assert_eq!(engine.get(Bytes::from("before")).unwrap(), "v");
+        assert_eq!(engine.get(Bytes::from("large")), Err(KeyNotFound));
+        assert_eq!(engine.write_pos().file_id(), 1);
+        engine.put(Bytes::from("after"), Bytes::from("v")).unwrap();
+        drop(engine);
+
+        // 最后一条记录的 header 只写入了一部分
+        let size = file_size(1);
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(Bytes::from("torn"), Bytes::from("v")).unwrap();
+        drop(engine);
+        set_file_size(1, size + 1);
+        let engine = Engine::open(opts.clone()).unwrap();
+        assert_eq!(engine.get(Bytes::from("torn")), Err(KeyNotFound));
+        engine.put(Bytes::from("last"), Bytes::from("v")).unwrap();
+        drop(engine);
+
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.get(Bytes::from("before")).unwrap(), "v");
+        assert_eq!(engine.get(Bytes::from("after")).unwrap(), "v");
+        assert_eq!(engine.get(Bytes::from("last")).unwrap(), "v");
+        assert_eq!(engine.list_keys().unwrap().len(), 3);
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_corrupted_record() {
+        let dir_path = std::env::temp_dir().join("fdb-corrupted-record");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let file_name = dir_path.join(format!("{:09}.data", 0));
+
+        let engine = Engine::open(opts.clone()).unwrap();
+        engine.put(Bytes::from("a"), Bytes::from("va")).unwrap();
+        let pos = engine.write_pos();
+        engine.put(Bytes::from("b"), Bytes::from("vb")).unwrap();
+        engine.put(Bytes::from("c"), Bytes::from("vc")).unwrap();
+        drop(engine);
+
+        // 破坏活跃文件中间的一条记录，后面的记录仍然完整
+        let mut data = fs::read(&file_name).unwrap();
+        let size = data.len();
+        let i = pos.offset() as usize + 4;
+        data[i] ^= 0xff;
+        fs::write(&file_name, &data).unwrap();
+        assert_eq!(Engine::open(opts.clone()).err(), Some(InvalidLogRecordCrc));
+        assert_eq!(fs::metadata(&file_name).unwrap().len(), size as u64);
+
+        // 恢复之后所有记录都还在
+        data[i] ^= 0xff;
+        fs::write(&file_name, &data).unwrap();
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.get(Bytes::from("b")).unwrap(), "vb");
+        assert_eq!(engine.get(Bytes::from("c")).unwrap(), "vc");
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -1,4 +1,3 @@
-use log::error;
 use std::result;
 use thiserror::Error;
 
@@ -48,6 +47,117 @@ pub enum Errors {
 
     #[error("invalid crc value, log record maybe corrupted")]
     InvalidLogRecordCrc,
+
+    #[error("sync policy threshold must be greater than 0")]
+    InvalidSyncPolicy,
+
+    #[error("unknown compression type in log record")]
+    UnknownCompressionType,
+
+    #[error("failed to compress value")]
+    FailedToCompressValue,
+
+    #[error("failed to decompress value, log record maybe corrupted")]
+    FailedToDecompressValue,
+
+    #[error("wrong encryption key for data file")]
+    WrongEncryptionKey,
+
+    #[error("data file is encrypted but no encryption key is provided")]
+    DataFileIsEncrypted,
+
+    #[error("data file is not encrypted but an encryption key is provided")]
+    DataFileIsNotEncrypted,
+
+    #[error("failed to authenticate encrypted data, the data file may be corrupted")]
+    FailedToAuthenticateData,
+
+    #[error("WRONGTYPE Operation against a key holding the wrong kind of value")]
+    WrongTypeOperation,
+
+    #[error("invalid data structure metadata")]
+    InvalidDataStructureMetadata,
+
+    #[error("log record position is beyond the current write position")]
+    InvalidLogRecordPos,
+
+    #[error("the database is opened in read-only mode")]
+    DatabaseIsReadOnly,
+
+    #[error("async engine background task failed")]
+    AsyncTaskFailed,
+
+    #[error("invalid blob reference in log record")]
+    InvalidBlobRef,
+
+    #[error("invalid crc value, blob maybe corrupted")]
+    InvalidBlobCrc,
+
+    #[error("failed to read value from the stream, or the stream ended early")]
+    FailedToReadValueStream,
+
+    #[error("merge operator is not configured")]
+    MergeOperatorNotConfigured,
+
+    #[error("merge operator failed to merge operands")]
+    MergeFailed,
+
+    #[error("invalid merge operand record")]
+    InvalidMergeRecord,
+
+    #[error("the version of the key has changed")]
+    VersionConflict,
+
+    #[error("column family already exists")]
+    ColumnFamilyExists,
+
+    #[error("column family not found")]
+    ColumnFamilyNotFound,
+
+    #[error("invalid column family name")]
+    InvalidColumnFamilyName,
+
+    #[error("invalid column family record")]
+    InvalidColumnFamilyRecord,
+
+    #[error("another bulk load is in progress")]
+    BulkLoadInProgress,
+
+    #[error("bulk load keys must be in ascending order")]
+    BulkLoadKeyNotSorted,
+
+    #[error("too many writes during the bulk load")]
+    BulkLoadSeqExhausted,
+
+    #[error("failed to attach bulk loaded data files")]
+    FailedToAttachDataFiles,
+
+    #[error("unsupported import or export format")]
+    UnsupportedFormat,
+
+    #[error("failed to write export data")]
+    FailedToWriteExport,
+
+    #[error("failed to read import data")]
+    FailedToReadImport,
+
+    #[error("invalid import record")]
+    InvalidImportRecord,
+
+    #[error("the index type is not supported yet")]
+    UnsupportedIndexType,
+
+    #[error("invalid rdb file")]
+    InvalidRdbFile,
+
+    #[error("unsupported rdb version")]
+    UnsupportedRdbVersion,
+
+    #[error("unsupported value type in rdb file")]
+    UnsupportedRdbType,
+
+    #[error("rdb file checksum mismatch")]
+    RdbChecksumMismatch,
 }
 
 pub type Result<T> = result::Result<T, Errors>;
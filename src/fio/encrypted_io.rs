@@ -0,0 +1,498 @@
+use crate::errors::{Errors, Result};
+use crate::fio::file_io::FileIO;
+use crate::fio::IOManager;
+use bytes::BufMut;
+use chacha20poly1305::aead::{Aead, AeadCore, KeyInit, OsRng, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Nonce, XChaCha20Poly1305, XNonce};
+use log::error;
+use parking_lot::{Mutex, RwLock};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+// 加密数据文件的文件头，位于文件起始位置，数据从文件头之后开始存放
+//
+//	+-------------+--------------+---------------+----------------------+
+//	|    magic    |  file nonce  |  check nonce  |  key check 密文 + tag |
+//	+-------------+--------------+---------------+----------------------+
+//	    8字节          12字节          12字节              32字节
+//
+// 文件头之后是若干个帧，每次写入的数据按 MAX_FRAME_SIZE 切分，每一段加密为一个帧：
+//
+//	+-------------+-------------+----------------------+
+//	|  数据长度    |    nonce    |     密文 + tag        |
+//	+-------------+-------------+----------------------+
+//	    4字节          24字节          变长 + 16字节
+//
+// 帧使用 XChaCha20-Poly1305 加密，nonce 随机生成，附加数据为 file nonce、帧的逻辑offset和数据长度，
+// 修改密文，或者在文件之间、文件内部移动帧都会导致认证失败。
+// key check 使用 ChaCha20-Poly1305 加密固定明文，密钥错误时认证失败
+pub const ENCRYPTED_FILE_MAGIC: &[u8; 8] = b"FDBENC02";
+pub const ENCRYPTED_FILE_HEADER_SIZE: u64 = 64;
+const NONCE_SIZE: usize = 12;
+const KEY_CHECK_PLAINTEXT: &[u8; 16] = b"fdb-key-check-v1";
+const FRAME_NONCE_SIZE: usize = 24;
+// 每个帧除数据之外占用的字节数：数据长度 + nonce + tag
+const FRAME_OVERHEAD: u64 = 4 + FRAME_NONCE_SIZE as u64 + 16;
+// 每个帧中数据的最大长度，随机读取时需要解密整个帧
+const MAX_FRAME_SIZE: usize = 64 * 1024;
+// 打开文件时扫描帧的位置，每次读取的字节数
+const SCAN_BUF_SIZE: usize = 1024 * 1024;
+
+/// 加密的文件IO，对上层屏蔽文件头和帧，上层看到的offset均为逻辑offset
+///
+/// 打开文件时扫描所有帧的位置并保存在内存中，每个帧占用 8 字节。
+pub struct EncryptedIO {
+    inner: FileIO,
+    file_name: PathBuf,
+    cipher: XChaCha20Poly1305,
+    file_nonce: [u8; NONCE_SIZE],
+    read_only: bool,
+    frames: RwLock<Frames>,
+    // 最近一次解密的帧，读取 header 之后通常紧接着读取同一条记录的 key 和 value
+    last_frame: Mutex<Option<(usize, Arc<Vec<u8>>)>>,
+}
+
+// 文件中所有帧的逻辑起始offset，以及逻辑大小
+#[derive(Default)]
+struct Frames {
+    starts: Vec<u64>,
+    size: u64,
+}
+
+impl Frames {
+    // 第 index 个帧在文件中的实际offset
+    fn physical_offset(&self, index: usize) -> u64 {
+        let start = self.starts.get(index).copied().unwrap_or(self.size);
+        ENCRYPTED_FILE_HEADER_SIZE + start + index as u64 * FRAME_OVERHEAD
+    }
+
+    fn frame_len(&self, index: usize) -> usize {
+        let end = self.starts.get(index + 1).copied().unwrap_or(self.size);
+        (end - self.starts[index]) as usize
+    }
+}
+
+impl EncryptedIO {
+    pub fn new(file_name: PathBuf, key: [u8; 32]) -> Result<Self> {
+        let inner = FileIO::new(file_name.clone())?;
+        Self::open(inner, &file_name, key, false)
+    }
+
+    // 以读写方式打开已有的加密文件，文件不存在时不会创建
+    pub fn new_existing(file_name: PathBuf, key: [u8; 32]) -> Result<Self> {
+        let inner = FileIO::new_existing(file_name.clone())?;
+        Self::open(inner, &file_name, key, false)
+    }
+
+    // 以只读方式打开已有的加密文件，不会写入文件头
+    pub fn new_read_only(file_name: PathBuf, key: [u8; 32]) -> Result<Self> {
+        let inner = FileIO::new_read_only(file_name.clone())?;
+        Self::open(inner, &file_name, key, true)
+    }
+
+    fn open(inner: FileIO, file_name: &PathBuf, key: [u8; 32], read_only: bool) -> Result<Self> {
+        let file_size = file_size(file_name)?;
+
+        // 新文件写入文件头，已有文件则校验文件头和密钥
+        let file_nonce = match file_size {
+            // 只读时文件头尚未写完的新文件视为还不存在
+            n if read_only && n < ENCRYPTED_FILE_HEADER_SIZE => {
+                return Err(Errors::DataFileNotFound)
+            }
+            0 => write_header(&inner, &key)?,
+            _ => read_header(&inner, &key)?,
+        };
+
+        let mut frames = Frames::default();
+        let end = scan_frames(&inner, &mut frames, file_size)?;
+        // 写入时崩溃留下的不完整的帧，截断之后才能继续追加
+        if !read_only && end < file_size {
+            inner.truncate(end)?;
+        }
+
+        Ok(EncryptedIO {
+            inner,
+            file_name: file_name.clone(),
+            cipher: XChaCha20Poly1305::new(&key.into()),
+            file_nonce,
+            read_only,
+            frames: RwLock::new(frames),
+            last_frame: Mutex::new(None),
+        })
+    }
+
+    // 帧的附加数据，将帧与所在的文件和位置绑定
+    fn frame_aad(&self, start: u64, len: usize) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(NONCE_SIZE + 12);
+        aad.extend_from_slice(&self.file_nonce);
+        aad.put_u64(start);
+        aad.put_u32(len as u32);
+        aad
+    }
+
+    // 读取并解密第 index 个帧
+    fn read_frame(&self, frames: &Frames, index: usize) -> Result<Arc<Vec<u8>>> {
+        if let Some((cached, plain)) = self.last_frame.lock().as_ref() {
+            if *cached == index {
+                return Ok(plain.clone());
+            }
+        }
+
+        let len = frames.frame_len(index);
+        let mut buf = vec![0u8; len + FRAME_OVERHEAD as usize];
+        let n = self.inner.read(&mut buf, frames.physical_offset(index))?;
+        if n < buf.len() || buf[..4] != (len as u32).to_be_bytes() {
+            return Err(Errors::FailedToAuthenticateData);
+        }
+        let nonce = &buf[4..4 + FRAME_NONCE_SIZE];
+        let payload = Payload {
+            msg: &buf[4 + FRAME_NONCE_SIZE..],
+            aad: &self.frame_aad(frames.starts[index], len),
+        };
+        let plain = self
+            .cipher
+            .decrypt(XNonce::from_slice(nonce), payload)
+            .map_err(|_| Errors::FailedToAuthenticateData)?;
+
+        let plain = Arc::new(plain);
+        *self.last_frame.lock() = Some((index, plain.clone()));
+        Ok(plain)
+    }
+
+    // 只读打开时写入进程可能追加了新的帧
+    fn refresh_frames(&self) -> Result<()> {
+        let mut frames = self.frames.write();
+        let file_size = file_size(&self.file_name)?;
+        scan_frames(&self.inner, &mut frames, file_size)?;
+        Ok(())
+    }
+}
+
+impl IOManager for EncryptedIO {
+    fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
+        let want = offset + buf.len() as u64;
+        if self.read_only && want > self.frames.read().size {
+            self.refresh_frames()?;
+        }
+        let frames = self.frames.read();
+        let end = want.min(frames.size);
+        if offset >= end {
+            return Ok(0);
+        }
+
+        // 依次解密 offset 所在的帧以及之后的帧
+        let mut index = frames.starts.partition_point(|start| *start <= offset) - 1;
+        let mut pos = offset;
+        while pos < end {
+            let plain = self.read_frame(&frames, index)?;
+            let start = frames.starts[index];
+            let from = (pos - start) as usize;
+            let to = ((end - start) as usize).min(plain.len());
+            let dst = (pos - offset) as usize;
+            buf[dst..dst + to - from].copy_from_slice(&plain[from..to]);
+            pos += (to - from) as u64;
+            index += 1;
+        }
+        Ok((end - offset) as usize)
+    }
+
+    fn write(&self, buf: &[u8]) -> Result<usize> {
+        // 持有锁，保证帧的逻辑offset与实际写入位置一致
+        let mut frames = self.frames.write();
+        let frame_num = buf.len().div_ceil(MAX_FRAME_SIZE);
+        let mut enc_buf = Vec::with_capacity(buf.len() + frame_num * FRAME_OVERHEAD as usize);
+        let mut starts = Vec::with_capacity(frame_num);
+        let mut start = frames.size;
+        for chunk in buf.chunks(MAX_FRAME_SIZE) {
+            let nonce = XChaCha20Poly1305::generate_nonce(&mut OsRng);
+            let payload = Payload {
+                msg: chunk,
+                aad: &self.frame_aad(start, chunk.len()),
+            };
+            let enc = self
+                .cipher
+                .encrypt(&nonce, payload)
+                .map_err(|_| Errors::FailedToWriteToDataFile)?;
+            enc_buf.put_u32(chunk.len() as u32);
+            enc_buf.extend_from_slice(&nonce);
+            enc_buf.extend_from_slice(&enc);
+            starts.push(start);
+            start += chunk.len() as u64;
+        }
+        if self.inner.write(&enc_buf)? != enc_buf.len() {
+            return Err(Errors::FailedToWriteToDataFile);
+        }
+        frames.starts.extend(starts);
+        frames.size = start;
+        Ok(buf.len())
+    }
+
+    fn sync(&self) -> Result<()> {
+        self.inner.sync()
+    }
+
+    // size 需要是某次写入的起始位置
+    fn truncate(&self, size: u64) -> Result<()> {
+        let mut frames = self.frames.write();
+        if size == frames.size {
+            return Ok(());
+        }
+        let Ok(index) = frames.starts.binary_search(&size) else {
+            return Err(Errors::FailedToWriteToDataFile);
+        };
+        self.inner.truncate(frames.physical_offset(index))?;
+        frames.starts.truncate(index);
+        frames.size = size;
+        *self.last_frame.lock() = None;
+        Ok(())
+    }
+}
+
+// 判断文件是否为加密的数据文件
+pub fn is_encrypted_file(io: &dyn IOManager) -> Result<bool> {
+    let mut magic = [0u8; 8];
+    let n = io.read(&mut magic, 0)?;
+    Ok(n == magic.len() && &magic == ENCRYPTED_FILE_MAGIC)
+}
+
+fn write_header(io: &FileIO, key: &[u8; 32]) -> Result<[u8; NONCE_SIZE]> {
+    let data_nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let check_nonce = ChaCha20Poly1305::generate_nonce(&mut OsRng);
+    let check = ChaCha20Poly1305::new(key.into())
+        .encrypt(&check_nonce, KEY_CHECK_PLAINTEXT.as_ref())
+        .map_err(|_| Errors::FailedToWriteToDataFile)?;
+
+    let mut header = Vec::with_capacity(ENCRYPTED_FILE_HEADER_SIZE as usize);
+    header.extend_from_slice(ENCRYPTED_FILE_MAGIC);
+    header.extend_from_slice(&data_nonce);
+    header.extend_from_slice(&check_nonce);
+    header.extend_from_slice(&check);
+    io.write(&header)?;
+    io.sync()?;
+
+    Ok(data_nonce.into())
+}
+
+fn read_header(io: &FileIO, key: &[u8; 32]) -> Result<[u8; NONCE_SIZE]> {
+    let mut header = [0u8; ENCRYPTED_FILE_HEADER_SIZE as usize];
+    let n = io.read(&mut header, 0)?;
+    if n < ENCRYPTED_FILE_MAGIC.len() || &header[..8] != ENCRYPTED_FILE_MAGIC {
+        return Err(Errors::DataFileIsNotEncrypted);
+    }
+    if n < header.len() {
+        return Err(Errors::DataDirectoryCorrupted);
+    }
+
+    let data_nonce = &header[8..8 + NONCE_SIZE];
+    let check_nonce = Nonce::from_slice(&header[8 + NONCE_SIZE..8 + NONCE_SIZE * 2]);
+    let check = &header[8 + NONCE_SIZE * 2..];
+    match ChaCha20Poly1305::new(key.into()).decrypt(check_nonce, check) {
+        Ok(plain) if plain == KEY_CHECK_PLAINTEXT => {}
+        _ => return Err(Errors::WrongEncryptionKey),
+    }
+
+    Ok(data_nonce.try_into().unwrap())
+}
+
+// 从文件头之后开始扫描 frames 之后的帧，返回最后一个完整的帧之后的实际offset
+//
+// 末尾不完整的帧是写入时崩溃留下的，不计入文件的逻辑大小；帧的长度不合法时说明文件已经损坏
+fn scan_frames(io: &FileIO, frames: &mut Frames, file_size: u64) -> Result<u64> {
+    let mut pos = frames.physical_offset(frames.starts.len());
+    let mut buf = vec![0u8; SCAN_BUF_SIZE];
+    let (mut buf_start, mut buf_len) = (pos, 0);
+    while pos + 4 <= file_size {
+        if pos + 4 > buf_start + buf_len as u64 {
+            buf_start = pos;
+            buf_len = io.read(&mut buf, pos)?;
+            if buf_len < 4 {
+                break;
+            }
+        }
+        let i = (pos - buf_start) as usize;
+        let len = u32::from_be_bytes(buf[i..i + 4].try_into().unwrap()) as u64;
+        if len == 0 || len > MAX_FRAME_SIZE as u64 {
+            return Err(Errors::DataDirectoryCorrupted);
+        }
+        if pos + len + FRAME_OVERHEAD > file_size {
+            break;
+        }
+        frames.starts.push(frames.size);
+        frames.size += len;
+        pos += len + FRAME_OVERHEAD;
+    }
+    Ok(pos)
+}
+
+fn file_size(file_name: &PathBuf) -> Result<u64> {
+    match std::fs::metadata(file_name) {
+        Ok(meta) => Ok(meta.len()),
+        Err(e) => {
+            error!("failed to read data file metadata: {}", e);
+            Err(Errors::FailedReadFromDataFile)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn test_encrypted_io_write_and_read() {
+        let path = PathBuf::from("/tmp/enc-a.data");
+        let _ = fs::remove_file(path.clone());
+        let key = [7u8; 32];
+
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        assert_eq!(5, eio.write("key-a".as_bytes()).unwrap());
+        assert_eq!(5, eio.write("key-b".as_bytes()).unwrap());
+
+        let mut buf = [0u8; 5];
+        assert_eq!(5, eio.read(&mut buf, 5).unwrap());
+        assert_eq!(&buf, b"key-b");
+
+        // 磁盘上的内容是密文
+        let raw = fs::read(path.clone()).unwrap();
+        assert_eq!(
+            raw.len() as u64,
+            ENCRYPTED_FILE_HEADER_SIZE + 10 + 2 * FRAME_OVERHEAD
+        );
+        assert!(!raw.windows(5).any(|w| w == b"key-a"));
+
+        // 重新打开后可以继续追加并读取
+        drop(eio);
+        let eio2 = EncryptedIO::new(path.clone(), key).unwrap();
+        assert_eq!(5, eio2.write("key-c".as_bytes()).unwrap());
+        let mut buf2 = [0u8; 15];
+        assert_eq!(15, eio2.read(&mut buf2, 0).unwrap());
+        assert_eq!(&buf2, b"key-akey-bkey-c");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_io_wrong_key() {
+        let path = PathBuf::from("/tmp/enc-b.data");
+        let _ = fs::remove_file(path.clone());
+
+        let eio = EncryptedIO::new(path.clone(), [1u8; 32]).unwrap();
+        eio.write("key-a".as_bytes()).unwrap();
+        drop(eio);
+
+        let res = EncryptedIO::new(path.clone(), [2u8; 32]);
+        assert_eq!(res.err(), Some(Errors::WrongEncryptionKey));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_io_plain_file() {
+        let path = PathBuf::from("/tmp/enc-c.data");
+        let _ = fs::remove_file(path.clone());
+
+        let fio = FileIO::new(path.clone()).unwrap();
+        fio.write("plain-data".as_bytes()).unwrap();
+        assert!(!is_encrypted_file(&fio).unwrap());
+
+        let res = EncryptedIO::new(path.clone(), [1u8; 32]);
+        assert_eq!(res.err(), Some(Errors::DataFileIsNotEncrypted));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_io_tampered() {
+        let path = PathBuf::from("/tmp/enc-d.data");
+        let _ = fs::remove_file(path.clone());
+        let key = [7u8; 32];
+
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        eio.write("key-a".as_bytes()).unwrap();
+        eio.write("key-b".as_bytes()).unwrap();
+        drop(eio);
+        let raw = fs::read(path.clone()).unwrap();
+
+        // 修改第二个帧密文中的一个字节
+        let mut tampered = raw.clone();
+        let second = (ENCRYPTED_FILE_HEADER_SIZE + 5 + FRAME_OVERHEAD) as usize;
+        tampered[second + 4 + FRAME_NONCE_SIZE] ^= 0x01;
+        fs::write(path.clone(), &tampered).unwrap();
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        let mut buf = [0u8; 5];
+        assert_eq!(5, eio.read(&mut buf, 0).unwrap());
+        assert_eq!(eio.read(&mut buf, 5), Err(Errors::FailedToAuthenticateData));
+        drop(eio);
+
+        // 交换两个长度相同的帧
+        let mut spliced = raw[..ENCRYPTED_FILE_HEADER_SIZE as usize].to_vec();
+        spliced.extend_from_slice(&raw[second..]);
+        spliced.extend_from_slice(&raw[ENCRYPTED_FILE_HEADER_SIZE as usize..second]);
+        fs::write(path.clone(), &spliced).unwrap();
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        assert_eq!(eio.read(&mut buf, 0), Err(Errors::FailedToAuthenticateData));
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_io_torn_frame() {
+        let path = PathBuf::from("/tmp/enc-e.data");
+        let _ = fs::remove_file(path.clone());
+        let key = [7u8; 32];
+
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        eio.write("key-a".as_bytes()).unwrap();
+        eio.write("key-b".as_bytes()).unwrap();
+        drop(eio);
+
+        // 最后一个帧只写入了一部分
+        let file = fs::OpenOptions::new().write(true).open(&path).unwrap();
+        let size = file.metadata().unwrap().len();
+        file.set_len(size - 3).unwrap();
+        drop(file);
+
+        let eio = EncryptedIO::new_read_only(path.clone(), key).unwrap();
+        let mut buf = [0u8; 10];
+        assert_eq!(5, eio.read(&mut buf, 0).unwrap());
+        drop(eio);
+
+        // 读写打开时截断不完整的帧，之后可以继续追加
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        let size = fs::metadata(&path).unwrap().len();
+        assert_eq!(size, ENCRYPTED_FILE_HEADER_SIZE + 5 + FRAME_OVERHEAD);
+        eio.write("key-c".as_bytes()).unwrap();
+        assert_eq!(10, eio.read(&mut buf, 0).unwrap());
+        assert_eq!(&buf, b"key-akey-c");
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_encrypted_io_large_write() {
+        let path = PathBuf::from("/tmp/enc-f.data");
+        let _ = fs::remove_file(path.clone());
+        let key = [7u8; 32];
+
+        // 超过帧最大长度的写入切分为多个帧，读取可以跨越帧
+        let data: Vec<u8> = (0..MAX_FRAME_SIZE * 2 + 100)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        assert_eq!(data.len(), eio.write(&data).unwrap());
+        drop(eio);
+
+        let eio = EncryptedIO::new(path.clone(), key).unwrap();
+        let offset = MAX_FRAME_SIZE - 10;
+        let mut buf = vec![0u8; MAX_FRAME_SIZE + 20];
+        assert_eq!(buf.len(), eio.read(&mut buf, offset as u64).unwrap());
+        assert_eq!(&buf[..], &data[offset..offset + buf.len()]);
+        eio.truncate(MAX_FRAME_SIZE as u64 + 1).unwrap_err();
+        eio.truncate(0).unwrap();
+        assert_eq!(0, eio.read(&mut buf, 0).unwrap());
+
+        fs::remove_file(path).unwrap();
+    }
+}
@@ -3,7 +3,7 @@ use crate::fio::IOManager;
 use log::error;
 use parking_lot::RwLock;
 use std::fs::{File, OpenOptions};
-use std::io::Write;
+use std::io::{ErrorKind, Write};
 use std::os::unix::prelude::FileExt;
 use std::path::PathBuf;
 use std::sync::Arc;
@@ -14,10 +14,9 @@ pub struct FileIO {
 
 impl FileIO {
     pub fn new(file_name: PathBuf) -> Result<Self> {
-        return match OpenOptions::new()
+        match OpenOptions::new()
             .create(true)
             .read(true)
-            .write(true)
             .append(true)
             .open(file_name)
         {
@@ -28,7 +27,31 @@ impl FileIO {
                 error!("file to open data file:{}", e);
                 Err(Errors::FailedToOpenDataFile)
             }
-        };
+        }
+    }
+
+    // 以只读方式打开已有的文件，文件不存在时不会创建
+    pub fn new_read_only(file_name: PathBuf) -> Result<Self> {
+        Self::open_existing(file_name, OpenOptions::new().read(true))
+    }
+
+    // 以读写方式打开已有的文件，文件不存在时不会创建
+    pub fn new_existing(file_name: PathBuf) -> Result<Self> {
+        Self::open_existing(file_name, OpenOptions::new().read(true).append(true))
+    }
+
+    // 文件不存在时返回 DataFileNotFound
+    fn open_existing(file_name: PathBuf, options: &OpenOptions) -> Result<Self> {
+        match options.open(file_name) {
+            Ok(file) => Ok(FileIO {
+                fd: Arc::new(RwLock::new(file)),
+            }),
+            Err(e) if e.kind() == ErrorKind::NotFound => Err(Errors::DataFileNotFound),
+            Err(e) => {
+                error!("file to open data file:{}", e);
+                Err(Errors::FailedToOpenDataFile)
+            }
+        }
     }
 }
 
@@ -36,12 +59,12 @@ impl IOManager for FileIO {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize> {
         let read_guard = self.fd.read();
         match read_guard.read_at(buf, offset) {
-            Ok(n) => return Ok(n),
+            Ok(n) => Ok(n),
             Err(e) => {
                 error!("read from data file err: {}", e);
-                return Err(Errors::FailedReadFromDataFile);
+                Err(Errors::FailedReadFromDataFile)
             }
-        };
+        }
     }
 
     fn write(&self, buf: &[u8]) -> Result<usize> {
@@ -63,6 +86,15 @@ impl IOManager for FileIO {
         }
         Ok(())
     }
+
+    fn truncate(&self, size: u64) -> Result<()> {
+        let write_guard = self.fd.write();
+        if let Err(e) = write_guard.set_len(size) {
+            error!("failed to truncate data file :{}", e);
+            return Err(Errors::FailedToWriteToDataFile);
+        }
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -141,4 +173,22 @@ mod tests {
         let res3 = fs::remove_file(path.clone());
         assert!(res3.is_ok());
     }
+
+    #[test]
+    fn test_file_io_truncate() {
+        let path = PathBuf::from("/tmp/d.data");
+        let _ = fs::remove_file(path.clone());
+        let fio = FileIO::new(path.clone()).unwrap();
+        fio.write("key-a".as_bytes()).unwrap();
+        fio.write("key-b".as_bytes()).unwrap();
+
+        // 截断后从新的文件末尾继续追加
+        fio.truncate(5).unwrap();
+        fio.write("key-c".as_bytes()).unwrap();
+        let mut buf = [0u8; 10];
+        assert_eq!(fio.read(&mut buf, 0).unwrap(), 10);
+        assert_eq!(&buf, b"key-akey-c");
+
+        fs::remove_file(path).unwrap();
+    }
 }
@@ -0,0 +1,127 @@
+use crate::errors::Result;
+use crate::fio::IOManager;
+use lru::LruCache;
+use parking_lot::Mutex;
+use std::num::NonZeroUsize;
+use std::sync::Arc;
+
+/// 旧数据文件的文件句柄池，限制同时打开的文件数量
+/// 超出上限时关闭最久未使用的文件，下次读取时再重新打开
+pub struct FilePool {
+    files: Mutex<LruCache<u32, Arc<dyn IOManager>>>,
+}
+
+impl FilePool {
+    pub fn new(capacity: NonZeroUsize) -> Self {
+        Self {
+            files: Mutex::new(LruCache::new(capacity)),
+        }
+    }
+
+    // 获取文件句柄，不存在时调用 open 打开文件，并淘汰最久未使用的句柄
+    // 被淘汰的句柄如果仍在被读取，会在读取结束后才真正关闭
+    // 打开文件时不持有锁，避免阻塞其他文件的读取
+    pub fn get_or_open<F>(&self, file_id: u32, open: F) -> Result<Arc<dyn IOManager>>
+    where
+        F: FnOnce() -> Result<Box<dyn IOManager>>,
+    {
+        if let Some(io_manager) = self.files.lock().get(&file_id) {
+            return Ok(io_manager.clone());
+        }
+        let io_manager: Arc<dyn IOManager> = Arc::from(open()?);
+
+        // 打开期间其他线程可能已经放入了同一个文件，复用已有的句柄
+        let mut files = self.files.lock();
+        if let Some(existing) = files.get(&file_id) {
+            return Ok(existing.clone());
+        }
+        files.push(file_id, io_manager.clone());
+        Ok(io_manager)
+    }
+
+    // 关闭对应的文件句柄
+    pub fn remove(&self, file_id: u32) {
+        self.files.lock().pop(&file_id);
+    }
+
+    // 当前打开的文件句柄数量
+    pub fn len(&self) -> usize {
+        self.files.lock().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fio::file_io::FileIO;
+    use std::path::PathBuf;
+
+    fn open_file(file_id: u32) -> Result<Box<dyn IOManager>> {
+        let path = PathBuf::from(format!("/tmp/pool-{}.data", file_id));
+        Ok(Box::new(FileIO::new(path)?))
+    }
+
+    #[test]
+    fn test_file_pool_evict() {
+        let pool = FilePool::new(NonZeroUsize::new(2).unwrap());
+        assert!(pool.is_empty());
+
+        let io1 = pool.get_or_open(1, || open_file(1)).unwrap();
+        io1.write("key-a".as_bytes()).unwrap();
+        pool.get_or_open(2, || open_file(2)).unwrap();
+        assert_eq!(pool.len(), 2);
+
+        // 已打开的文件不会重复打开
+        let reopen = pool.get_or_open(1, || panic!("file 1 should be cached"));
+        assert!(reopen.is_ok());
+
+        // 超出上限时淘汰最久未使用的文件2
+        pool.get_or_open(3, || open_file(3)).unwrap();
+        assert_eq!(pool.len(), 2);
+        let mut opened = false;
+        pool.get_or_open(2, || {
+            opened = true;
+            open_file(2)
+        })
+        .unwrap();
+        assert!(opened);
+
+        pool.remove(2);
+        assert_eq!(pool.len(), 1);
+
+        for file_id in 1..=3 {
+            let _ = std::fs::remove_file(format!("/tmp/pool-{}.data", file_id));
+        }
+    }
+
+    #[test]
+    fn test_file_pool_open_without_lock() {
+        let pool = FilePool::new(NonZeroUsize::new(2).unwrap());
+        pool.get_or_open(11, || open_file(11)).unwrap();
+
+        // 打开文件时可以访问句柄池，并且打开期间放入的句柄会被复用
+        let io = pool
+            .get_or_open(12, || {
+                assert_eq!(pool.len(), 1);
+                let first = pool.get_or_open(12, || open_file(12)).unwrap();
+                first.write("key-a".as_bytes()).unwrap();
+                open_file(12)
+            })
+            .unwrap();
+        assert_eq!(pool.len(), 2);
+        let mut buf = [0u8; 5];
+        let cached = pool
+            .get_or_open(12, || panic!("file 12 should be cached"))
+            .unwrap();
+        assert!(Arc::ptr_eq(&io, &cached));
+        assert_eq!(cached.read(&mut buf, 0).unwrap(), 5);
+
+        for file_id in 11..=12 {
+            let _ = std::fs::remove_file(format!("/tmp/pool-{}.data", file_id));
+        }
+    }
+}
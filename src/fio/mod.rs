@@ -1,8 +1,13 @@
+pub mod encrypted_io;
 pub mod file_io;
+pub mod file_pool;
 
-use crate::errors::Result;
+use crate::errors::{Errors, Result};
+use crate::fio::encrypted_io::{is_encrypted_file, EncryptedIO};
 use crate::fio::file_io::FileIO;
+use crate::options::KeyProvider;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 pub trait IOManager: Sync + Send {
     fn read(&self, buf: &mut [u8], offset: u64) -> Result<usize>;
@@ -10,8 +15,58 @@ pub trait IOManager: Sync + Send {
     fn write(&self, buf: &[u8]) -> Result<usize>;
 
     fn sync(&self) -> Result<()>;
+
+    // 将文件截断为 size 字节，加密文件的 size 需要是某次写入的起始位置
+    fn truncate(&self, size: u64) -> Result<()>;
+}
+
+// 根据是否配置了密钥，打开普通文件IO或者加密文件IO，只读时不会创建或写入文件
+pub fn new_io_manager(
+    file_name: PathBuf,
+    key_provider: Option<&Arc<dyn KeyProvider>>,
+    read_only: bool,
+) -> Result<Box<dyn IOManager>> {
+    open_io_manager(file_name, key_provider, read_only, true)
+}
+
+// 打开已有的文件，文件不存在时返回 DataFileNotFound 而不是创建空文件
+pub fn open_existing_io_manager(
+    file_name: PathBuf,
+    key_provider: Option<&Arc<dyn KeyProvider>>,
+    read_only: bool,
+) -> Result<Box<dyn IOManager>> {
+    open_io_manager(file_name, key_provider, read_only, false)
 }
 
-pub fn new_io_manager(file_name: PathBuf) -> Result<impl IOManager> {
-    FileIO::new(file_name)
+fn open_io_manager(
+    file_name: PathBuf,
+    key_provider: Option<&Arc<dyn KeyProvider>>,
+    read_only: bool,
+    create: bool,
+) -> Result<Box<dyn IOManager>> {
+    match (key_provider, read_only) {
+        (Some(provider), false) => {
+            let io = match create {
+                true => EncryptedIO::new(file_name, provider.key())?,
+                false => EncryptedIO::new_existing(file_name, provider.key())?,
+            };
+            Ok(Box::new(io))
+        }
+        (Some(provider), true) => Ok(Box::new(EncryptedIO::new_read_only(
+            file_name,
+            provider.key(),
+        )?)),
+        (None, _) => {
+            let file_io = match (read_only, create) {
+                (true, _) => FileIO::new_read_only(file_name)?,
+                (false, true) => FileIO::new(file_name)?,
+                (false, false) => FileIO::new_existing(file_name)?,
+            };
+            // 没有密钥时不能打开加密的数据文件
+            if is_encrypted_file(&file_io)? {
+                return Err(Errors::DataFileIsEncrypted);
+            }
+            Ok(Box::new(file_io))
+        }
+    }
 }
@@ -0,0 +1,120 @@
+/// Redis 风格的 glob 匹配，支持 `*`、`?`、`[abc]`、`[^a-z]` 以及 `\` 转义
+pub fn glob_match(pattern: &[u8], s: &[u8]) -> bool {
+    let (mut p, mut i) = (0, 0);
+    // 最近一次 `*` 的位置，用于匹配失败时回溯
+    let mut star: Option<(usize, usize)> = None;
+
+    while i < s.len() {
+        if p < pattern.len() {
+            match pattern[p] {
+                b'*' => {
+                    star = Some((p, i));
+                    p += 1;
+                    continue;
+                }
+                b'?' => {
+                    p += 1;
+                    i += 1;
+                    continue;
+                }
+                b'[' => match match_class(pattern, p, s[i]) {
+                    Some((true, next)) => {
+                        p = next;
+                        i += 1;
+                        continue;
+                    }
+                    Some((false, _)) => {}
+                    // 没有闭合的 `[` 按普通字符处理
+                    None if s[i] == b'[' => {
+                        p += 1;
+                        i += 1;
+                        continue;
+                    }
+                    None => {}
+                },
+                b'\\' if p + 1 < pattern.len() => {
+                    if pattern[p + 1] == s[i] {
+                        p += 2;
+                        i += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == s[i] {
+                        p += 1;
+                        i += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+        // 当前字符不匹配，回溯到上一个 `*` 多匹配一个字符
+        match star {
+            Some((star_p, star_i)) => {
+                p = star_p + 1;
+                i = star_i + 1;
+                star = Some((star_p, star_i + 1));
+            }
+            None => return false,
+        }
+    }
+
+    // 剩余的模式只能是 `*`
+    pattern[p..].iter().all(|c| *c == b'*')
+}
+
+// 匹配 `[...]` 字符集合，返回是否匹配以及集合之后的位置，集合没有闭合时返回 None
+fn match_class(pattern: &[u8], start: usize, c: u8) -> Option<(bool, usize)> {
+    let mut p = start + 1;
+    let negate = p < pattern.len() && pattern[p] == b'^';
+    if negate {
+        p += 1;
+    }
+    let mut matched = false;
+    while p < pattern.len() && pattern[p] != b']' {
+        if pattern[p] == b'\\' && p + 1 < pattern.len() {
+            matched |= pattern[p + 1] == c;
+            p += 2;
+        } else if p + 2 < pattern.len() && pattern[p + 1] == b'-' && pattern[p + 2] != b']' {
+            let (lo, hi) = match pattern[p] <= pattern[p + 2] {
+                true => (pattern[p], pattern[p + 2]),
+                false => (pattern[p + 2], pattern[p]),
+            };
+            matched |= lo <= c && c <= hi;
+            p += 3;
+        } else {
+            matched |= pattern[p] == c;
+            p += 1;
+        }
+    }
+    if p >= pattern.len() {
+        return None;
+    }
+    Some((matched != negate, p + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_glob_match() {
+        assert!(glob_match(b"*", b""));
+        assert!(glob_match(b"*", b"anything"));
+        assert!(glob_match(b"user:*", b"user:1000"));
+        assert!(!glob_match(b"user:*", b"order:1"));
+        assert!(glob_match(b"h?llo", b"hello"));
+        assert!(!glob_match(b"h?llo", b"hllo"));
+        assert!(glob_match(b"h*llo", b"heeeello"));
+        assert!(glob_match(b"h[ae]llo", b"hallo"));
+        assert!(!glob_match(b"h[ae]llo", b"hillo"));
+        assert!(glob_match(b"h[^e]llo", b"hallo"));
+        assert!(!glob_match(b"h[^e]llo", b"hello"));
+        assert!(glob_match(b"h[a-c]llo", b"hbllo"));
+        assert!(glob_match(b"a\\*b", b"a*b"));
+        assert!(!glob_match(b"a\\*b", b"axb"));
+        assert!(glob_match(b"*:*:end", b"a:b:c:end"));
+        assert!(!glob_match(b"abc", b"abcd"));
+        assert!(glob_match(b"a[b", b"a[b"));
+    }
+}
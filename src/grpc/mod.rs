@@ -0,0 +1,6 @@
+pub mod server;
+
+/// 由 proto/fdb.proto 生成的消息和服务代码
+pub mod pb {
+    tonic::include_proto!("fdb");
+}
@@ -0,0 +1,266 @@
+use crate::db::Engine;
+use crate::errors::Errors;
+use crate::grpc::pb::kv_server::{Kv, KvServer};
+use crate::grpc::pb::{
+    batch_op, BatchRequest, BatchResponse, DeleteRequest, DeleteResponse, GetRequest, GetResponse,
+    KeyValue, PutRequest, PutResponse, ScanRequest,
+};
+use crate::options::IteratorOptions;
+use std::sync::Arc;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{Request, Response, Status};
+
+// 扫描结果的缓冲数量，客户端消费较慢时扫描会暂停
+const SCAN_CHANNEL_SIZE: usize = 64;
+
+/// gRPC 键值服务
+///
+/// 存储引擎的读写都是阻塞的，统一放到 tokio 的阻塞线程池中执行，避免阻塞异步运行时。
+pub struct KvService {
+    engine: Arc<Engine>,
+}
+
+impl KvService {
+    pub fn new(engine: Arc<Engine>) -> Self {
+        Self { engine }
+    }
+
+    /// 转换为可以注册到 tonic Server 中的服务
+    pub fn into_server(self) -> KvServer<Self> {
+        KvServer::new(self)
+    }
+
+    // 在阻塞线程池中执行引擎操作
+    async fn blocking<T, F>(&self, f: F) -> Result<T, Status>
+    where
+        T: Send + 'static,
+        F: FnOnce(&Engine) -> crate::errors::Result<T> + Send + 'static,
+    {
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || f(&engine))
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?
+            .map_err(engine_status)
+    }
+}
+
+#[tonic::async_trait]
+impl Kv for KvService {
+    async fn get(&self, request: Request<GetRequest>) -> Result<Response<GetResponse>, Status> {
+        let key = request.into_inner().key;
+        let value = self.blocking(move |engine| engine.get(key)).await?;
+        Ok(Response::new(GetResponse { value }))
+    }
+
+    async fn put(&self, request: Request<PutRequest>) -> Result<Response<PutResponse>, Status> {
+        let PutRequest { key, value } = request.into_inner();
+        self.blocking(move |engine| engine.put(key, value)).await?;
+        Ok(Response::new(PutResponse {}))
+    }
+
+    async fn delete(
+        &self,
+        request: Request<DeleteRequest>,
+    ) -> Result<Response<DeleteResponse>, Status> {
+        let key = request.into_inner().key;
+        self.blocking(move |engine| engine.delete(key)).await?;
+        Ok(Response::new(DeleteResponse {}))
+    }
+
+    type ScanStream = ReceiverStream<Result<KeyValue, Status>>;
+
+    async fn scan(
+        &self,
+        request: Request<ScanRequest>,
+    ) -> Result<Response<Self::ScanStream>, Status> {
+        let ScanRequest {
+            prefix,
+            reverse,
+            limit,
+        } = request.into_inner();
+        let limit = match limit {
+            0 => usize::MAX,
+            limit => limit as usize,
+        };
+
+        let (sender, receiver) = mpsc::channel(SCAN_CHANNEL_SIZE);
+        let engine = self.engine.clone();
+        tokio::task::spawn_blocking(move || {
+            let iter = engine.iter(IteratorOptions {
+                prefix: prefix.to_vec(),
+                reverse,
+            });
+            let mut count = 0;
+            while count < limit {
+                let item = match iter.try_next() {
+                    Ok(Some((key, value))) => Ok(KeyValue { key, value }),
+                    Ok(None) => break,
+                    Err(e) => Err(engine_status(e)),
+                };
+                let failed = item.is_err();
+                // 客户端已经断开，或者读取出错之后停止扫描
+                if sender.blocking_send(item).is_err() || failed {
+                    break;
+                }
+                count += 1;
+            }
+        });
+        Ok(Response::new(ReceiverStream::new(receiver)))
+    }
+
+    async fn batch(
+        &self,
+        request: Request<BatchRequest>,
+    ) -> Result<Response<BatchResponse>, Status> {
+        let ops = request.into_inner().ops;
+        // 所有操作作为一个批量原子写入，任何一个操作出错时都不会执行
+        if ops.iter().any(|op| op.op.is_none()) {
+            return Err(Status::invalid_argument("empty batch op"));
+        }
+        let ops: Vec<_> = ops.into_iter().filter_map(|op| op.op).collect();
+        let applied = ops.len() as u32;
+        self.blocking(move |engine| {
+            let mut batch = engine.write_batch();
+            for op in ops {
+                match op {
+                    batch_op::Op::Put(PutRequest { key, value }) => batch.put(key, value)?,
+                    batch_op::Op::Delete(DeleteRequest { key }) => batch.delete(key)?,
+                }
+            }
+            batch.commit()
+        })
+        .await?;
+        Ok(Response::new(BatchResponse { applied }))
+    }
+}
+
+fn engine_status(e: Errors) -> Status {
+    match e {
+        Errors::KeyNotFound => Status::not_found(e.to_string()),
+        // 客户端请求本身有问题
+        Errors::KeyIsEmpty => Status::invalid_argument(e.to_string()),
+        Errors::DatabaseIsReadOnly => Status::failed_precondition(e.to_string()),
+        e => Status::internal(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::grpc::pb::kv_client::KvClient;
+    use crate::grpc::pb::BatchOp;
+    use crate::options::Options;
+    use bytes::Bytes;
+    use std::fs;
+    use tokio_stream::wrappers::TcpListenerStream;
+    use tonic::Code;
+
+    #[tokio::test]
+    async fn test_grpc_server() {
+        let dir_path = std::env::temp_dir().join("fdb-grpc-server");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(
+            tonic::transport::Server::builder()
+                .add_service(KvService::new(Arc::new(engine)).into_server())
+                .serve_with_incoming(TcpListenerStream::new(listener)),
+        );
+        let mut client = KvClient::connect(format!("http://{}", addr)).await.unwrap();
+
+        let status = client
+            .get(GetRequest {
+                key: Bytes::from("name"),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        let status = client
+            .put(PutRequest {
+                key: Bytes::new(),
+                value: Bytes::from("v"),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        // 不完整的批量操作不会执行任何写入
+        let ops = vec![
+            BatchOp {
+                op: Some(batch_op::Op::Put(PutRequest {
+                    key: Bytes::from("k"),
+                    value: Bytes::from("v"),
+                })),
+            },
+            BatchOp { op: None },
+        ];
+        let status = client.batch(BatchRequest { ops }).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(client
+            .get(GetRequest {
+                key: Bytes::from("k"),
+            })
+            .await
+            .is_err());
+
+        // 批量中的某个操作出错时，前面的操作也不会写入
+        let ops = vec![
+            BatchOp {
+                op: Some(batch_op::Op::Put(PutRequest {
+                    key: Bytes::from("k"),
+                    value: Bytes::from("v"),
+                })),
+            },
+            BatchOp {
+                op: Some(batch_op::Op::Put(PutRequest {
+                    key: Bytes::new(),
+                    value: Bytes::from("v"),
+                })),
+            },
+        ];
+        let status = client.batch(BatchRequest { ops }).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert!(client
+            .get(GetRequest {
+                key: Bytes::from("k"),
+            })
+            .await
+            .is_err());
+        assert_eq!(
+            engine_status(Errors::DatabaseIsReadOnly).code(),
+            Code::FailedPrecondition
+        );
+
+        for i in 0..5 {
+            client
+                .put(PutRequest {
+                    key: Bytes::from(format!("key-{}", i)),
+                    value: Bytes::from(format!("value-{}", i)),
+                })
+                .await
+                .unwrap();
+        }
+        let mut stream = client
+            .scan(ScanRequest {
+                prefix: Bytes::from("key-"),
+                reverse: true,
+                limit: 2,
+            })
+            .await
+            .unwrap()
+            .into_inner();
+        let mut keys = Vec::new();
+        while let Some(item) = stream.message().await.unwrap() {
+            keys.push(item.key);
+        }
+        assert_eq!(keys, vec![Bytes::from("key-4"), Bytes::from("key-3")]);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,65 @@
+// key 在 URL 路径、查询参数和 JSON 中均使用百分号编码，
+// 字母、数字以及 - . _ ~ 保持原样，其他字节编码为 %XX
+
+/// 将 key 编码为 URL 安全的字符串
+pub fn encode_key(key: &[u8]) -> String {
+    const HEX: &[u8; 16] = b"0123456789ABCDEF";
+    let mut encoded = String::with_capacity(key.len());
+    for &b in key {
+        match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(b as char)
+            }
+            _ => {
+                encoded.push('%');
+                encoded.push(HEX[(b >> 4) as usize] as char);
+                encoded.push(HEX[(b & 0x0f) as usize] as char);
+            }
+        }
+    }
+    encoded
+}
+
+/// 解码百分号编码的字符串，格式不正确时返回 None
+pub fn decode_key(encoded: &str) -> Option<Vec<u8>> {
+    let bytes = encoded.as_bytes();
+    let mut key = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = bytes.get(i + 1..i + 3)?;
+                if !hex.iter().all(u8::is_ascii_hexdigit) {
+                    return None;
+                }
+                let hex = std::str::from_utf8(hex).ok()?;
+                key.push(u8::from_str_radix(hex, 16).ok()?);
+                i += 3;
+            }
+            b => {
+                key.push(b);
+                i += 1;
+            }
+        }
+    }
+    Some(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_and_decode_key() {
+        assert_eq!(encode_key(b"user-1.name_~"), "user-1.name_~");
+        assert_eq!(encode_key(b"a/b c"), "a%2Fb%20c");
+        assert_eq!(encode_key(&[0x00, 0xff]), "%00%FF");
+
+        let key = b"a/b c?d=%\x00\xff".to_vec();
+        assert_eq!(decode_key(&encode_key(&key)), Some(key));
+        assert_eq!(decode_key("a%2fb"), Some(b"a/b".to_vec()));
+        assert_eq!(decode_key("bad%2"), None);
+        assert_eq!(decode_key("bad%zz"), None);
+        assert_eq!(decode_key("bad%+1"), None);
+    }
+}
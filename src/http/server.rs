@@ -0,0 +1,444 @@
+use crate::db::Engine;
+use crate::errors::Errors;
+use crate::http::encoding::{decode_key, encode_key};
+use crate::options::IteratorOptions;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use bytes::Bytes;
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{self, Read};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// 扫描时默认和最多返回的key数量
+const DEFAULT_SCAN_LIMIT: usize = 100;
+const MAX_SCAN_LIMIT: usize = 1000;
+// 请求体的最大长度
+const MAX_BODY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// HTTP/JSON 服务端
+///
+/// ```text
+/// GET    /health                           健康检查
+/// GET    /stats                            统计信息
+/// GET    /kv/{key}                         读取value，响应体为原始数据
+/// PUT    /kv/{key}                         写入value，请求体为原始数据
+/// DELETE /kv/{key}                         删除key
+/// GET    /kv?prefix=&limit=&cursor=        按前缀扫描，返回 next_cursor 用于翻页
+/// POST   /batch                            批量写入和删除
+/// ```
+///
+/// key 使用百分号编码，JSON 中的 value 使用 base64 编码。
+pub struct HttpServer {
+    engine: Arc<Engine>,
+    server: Server,
+}
+
+// 一次请求的响应内容
+struct Reply {
+    status: u16,
+    content_type: &'static str,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(status: u16, value: Value) -> Self {
+        Reply {
+            status,
+            content_type: "application/json",
+            body: value.to_string().into_bytes(),
+        }
+    }
+
+    fn error(status: u16, message: impl ToString) -> Self {
+        Reply::json(status, json!({ "error": message.to_string() }))
+    }
+
+    fn no_content() -> Self {
+        Reply {
+            status: 204,
+            content_type: "text/plain",
+            body: Vec::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct BatchRequest {
+    ops: Vec<BatchOp>,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+enum BatchOp {
+    Put { key: String, value: String },
+    Delete { key: String },
+}
+
+impl HttpServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A, engine: Arc<Engine>) -> io::Result<Self> {
+        let server = Server::http(addr).map_err(io::Error::other)?;
+        Ok(Self { engine, server })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.server
+            .server_addr()
+            .to_ip()
+            .ok_or_else(|| io::Error::other("server is not listening on a tcp address"))
+    }
+
+    /// 使用多个工作线程处理请求，直到服务端关闭
+    pub fn serve(&self) -> io::Result<()> {
+        info!("http server listening on {}", self.local_addr()?);
+        let workers = std::thread::available_parallelism().map_or(4, |n| n.get());
+        std::thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| {
+                    for request in self.server.incoming_requests() {
+                        handle_request(&self.engine, request);
+                    }
+                });
+            }
+        });
+        Ok(())
+    }
+}
+
+fn handle_request(engine: &Engine, mut request: Request) {
+    let reply = match read_body(&mut request) {
+        Ok(body) => route(engine, request.method(), request.url(), body),
+        Err(reply) => reply,
+    };
+    let content_type = Header::from_bytes("Content-Type", reply.content_type).unwrap();
+    let response = Response::from_data(reply.body)
+        .with_status_code(reply.status)
+        .with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        warn!("failed to write http response: {}", e);
+    }
+}
+
+fn read_body(request: &mut Request) -> Result<Vec<u8>, Reply> {
+    let mut body = Vec::new();
+    request
+        .as_reader()
+        .take(MAX_BODY_SIZE + 1)
+        .read_to_end(&mut body)
+        .map_err(|e| Reply::error(400, e))?;
+    if body.len() as u64 > MAX_BODY_SIZE {
+        return Err(Reply::error(413, "request body too large"));
+    }
+    Ok(body)
+}
+
+fn route(engine: &Engine, method: &Method, url: &str, body: Vec<u8>) -> Reply {
+    let (path, query) = url.split_once('?').unwrap_or((url, ""));
+    let res = match (method, path) {
+        (Method::Get, "/health") => Ok(Reply::json(200, json!({ "status": "ok" }))),
+        (Method::Get, "/stats") => stats(engine),
+        (Method::Get, "/kv") => scan(engine, query),
+        (Method::Post, "/batch") => batch(engine, &body),
+        (_, "/health" | "/stats" | "/kv" | "/batch") => {
+            Err(Reply::error(405, "method not allowed"))
+        }
+        (method, path) => match path.strip_prefix("/kv/") {
+            Some(key) => match decode_key(key) {
+                Some(key) => match method {
+                    Method::Get => get(engine, key),
+                    Method::Put => put(engine, key, body),
+                    Method::Delete => delete(engine, key),
+                    _ => Err(Reply::error(405, "method not allowed")),
+                },
+                None => Err(Reply::error(400, "invalid key encoding")),
+            },
+            None => Err(Reply::error(404, "not found")),
+        },
+    };
+    res.unwrap_or_else(|reply| reply)
+}
+
+// 路由处理函数的返回值，错误时直接返回对应的错误响应
+type HandlerResult = Result<Reply, Reply>;
+
+fn get(engine: &Engine, key: Vec<u8>) -> HandlerResult {
+    let value = engine.get(Bytes::from(key)).map_err(engine_error)?;
+    Ok(Reply {
+        status: 200,
+        content_type: "application/octet-stream",
+        body: value.to_vec(),
+    })
+}
+
+fn put(engine: &Engine, key: Vec<u8>, body: Vec<u8>) -> HandlerResult {
+    engine
+        .put(Bytes::from(key), Bytes::from(body))
+        .map_err(engine_error)?;
+    Ok(Reply::no_content())
+}
+
+fn delete(engine: &Engine, key: Vec<u8>) -> HandlerResult {
+    engine.delete(Bytes::from(key)).map_err(engine_error)?;
+    Ok(Reply::no_content())
+}
+
+// GET /kv?prefix=&limit=&cursor=，cursor 为上一页最后一个key，本页从其之后开始
+fn scan(engine: &Engine, query: &str) -> HandlerResult {
+    let mut prefix = Vec::new();
+    let mut limit = DEFAULT_SCAN_LIMIT;
+    let mut cursor = None;
+    for param in query.split('&').filter(|param| !param.is_empty()) {
+        let (name, value) = param.split_once('=').unwrap_or((param, ""));
+        match name {
+            "prefix" => prefix = decode_param(name, value)?,
+            "cursor" => cursor = Some(decode_param(name, value)?),
+            "limit" => {
+                limit = match value.parse::<usize>() {
+                    Ok(limit) if limit > 0 => limit.min(MAX_SCAN_LIMIT),
+                    _ => return Err(Reply::error(400, "invalid limit")),
+                }
+            }
+            _ => return Err(Reply::error(400, format!("unknown parameter: {}", name))),
+        }
+    }
+
+    let iter = engine.iter(IteratorOptions {
+        prefix,
+        reverse: false,
+    });
+    if let Some(cursor) = cursor.as_ref() {
+        iter.seek(cursor.clone());
+    }
+    let mut items = Vec::new();
+    let mut next_cursor = None;
+    while let Some((key, value)) = iter.try_next().map_err(engine_error)? {
+        if cursor.as_deref() == Some(key.as_ref()) {
+            continue;
+        }
+        // 还有更多数据时才返回 next_cursor
+        if items.len() == limit {
+            next_cursor = items
+                .last()
+                .and_then(|item: &Value| item["key"].as_str().map(str::to_string));
+            break;
+        }
+        items.push(json!({
+            "key": encode_key(&key),
+            "value": BASE64.encode(&value),
+        }));
+    }
+    Ok(Reply::json(
+        200,
+        json!({ "items": items, "next_cursor": next_cursor }),
+    ))
+}
+
+// POST /batch，所有操作作为一个批量原子写入，任何一个操作出错时都不会执行
+fn batch(engine: &Engine, body: &[u8]) -> HandlerResult {
+    let request: BatchRequest = serde_json::from_slice(body).map_err(|e| Reply::error(400, e))?;
+    let mut batch = engine.write_batch();
+    for op in request.ops {
+        let res = match op {
+            BatchOp::Put { key, value } => {
+                let key = decode_param("key", &key)?;
+                let value = BASE64
+                    .decode(value)
+                    .map_err(|_| Reply::error(400, "invalid base64 value"))?;
+                batch.put(Bytes::from(key), Bytes::from(value))
+            }
+            BatchOp::Delete { key } => batch.delete(Bytes::from(decode_param("key", &key)?)),
+        };
+        res.map_err(engine_error)?;
+    }
+
+    let applied = batch.len();
+    batch.commit().map_err(engine_error)?;
+    Ok(Reply::json(200, json!({ "applied": applied })))
+}
+
+fn stats(engine: &Engine) -> HandlerResult {
+    let stat = engine.stat().map_err(engine_error)?;
+    let write_pos = engine.write_pos();
+    let durable_pos = engine.durable_pos();
+    let cache = engine.cache_stats().map(|stats| {
+        json!({
+            "hits": stats.hits,
+            "misses": stats.misses,
+            "entries": stats.entries,
+            "size": stats.size,
+        })
+    });
+    Ok(Reply::json(
+        200,
+        json!({
+            "key_num": stat.key_num,
+            "data_file_num": stat.data_file_num,
+            "disk_size": stat.disk_size,
+            "write_pos": { "file_id": write_pos.file_id, "offset": write_pos.offset },
+            "durable_pos": { "file_id": durable_pos.file_id, "offset": durable_pos.offset },
+            "cache": cache,
+        }),
+    ))
+}
+
+fn decode_param(name: &str, value: &str) -> Result<Vec<u8>, Reply> {
+    decode_key(value).ok_or_else(|| Reply::error(400, format!("invalid {} encoding", name)))
+}
+
+fn engine_error(e: Errors) -> Reply {
+    match e {
+        Errors::KeyNotFound => Reply::error(404, e),
+        Errors::KeyIsEmpty => Reply::error(400, e),
+        e => Reply::error(500, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+    use std::io::Write;
+    use std::net::TcpStream;
+
+    fn start_server(name: &str) -> (SocketAddr, std::path::PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let server = HttpServer::bind("127.0.0.1:0", Arc::new(engine)).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+        (addr, dir_path)
+    }
+
+    // 发送一个 HTTP 请求，返回状态码和响应体
+    fn request(addr: SocketAddr, method: &str, path: &str, body: &[u8]) -> (u16, Vec<u8>) {
+        let mut stream = TcpStream::connect(addr).unwrap();
+        let head = format!(
+            "{} {} HTTP/1.1\r\nHost: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            path,
+            addr,
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let split = response.windows(4).position(|w| w == b"\r\n\r\n").unwrap();
+        let status_line = String::from_utf8_lossy(&response[..split]).to_string();
+        let status = status_line.split(' ').nth(1).unwrap().parse().unwrap();
+        (status, response[split + 4..].to_vec())
+    }
+
+    fn request_json(addr: SocketAddr, method: &str, path: &str, body: &[u8]) -> (u16, Value) {
+        let (status, body) = request(addr, method, path, body);
+        (status, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[test]
+    fn test_http_server_kv() {
+        let (addr, dir_path) = start_server("fdb-http-kv");
+        let (status, body) = request_json(addr, "GET", "/health", b"");
+        assert_eq!(status, 200);
+        assert_eq!(body["status"], "ok");
+
+        assert_eq!(request(addr, "GET", "/kv/name", b"").0, 404);
+        assert_eq!(request(addr, "PUT", "/kv/name", b"fdb").0, 204);
+        assert_eq!(
+            request(addr, "GET", "/kv/name", b""),
+            (200, b"fdb".to_vec())
+        );
+
+        // key 中的特殊字符需要编码
+        assert_eq!(request(addr, "PUT", "/kv/a%2Fb%00", b"\xff\x00").0, 204);
+        assert_eq!(
+            request(addr, "GET", "/kv/a%2Fb%00", b""),
+            (200, b"\xff\x00".to_vec())
+        );
+        assert_eq!(request(addr, "GET", "/kv/bad%zz", b"").0, 400);
+
+        assert_eq!(request(addr, "DELETE", "/kv/name", b"").0, 204);
+        assert_eq!(request(addr, "GET", "/kv/name", b"").0, 404);
+        assert_eq!(request(addr, "POST", "/kv/name", b"").0, 405);
+        assert_eq!(request(addr, "GET", "/unknown", b"").0, 404);
+
+        let (status, body) = request_json(addr, "GET", "/stats", b"");
+        assert_eq!(status, 200);
+        assert_eq!(body["key_num"], 1);
+        assert_eq!(body["data_file_num"], 1);
+        assert!(body["cache"].is_null());
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_http_server_scan_and_batch() {
+        let (addr, dir_path) = start_server("fdb-http-scan");
+        let batch = json!({
+            "ops": [
+                { "op": "put", "key": "user%3A1", "value": BASE64.encode("a") },
+                { "op": "put", "key": "user%3A2", "value": BASE64.encode("b") },
+                { "op": "put", "key": "user%3A3", "value": BASE64.encode("c") },
+                { "op": "put", "key": "other", "value": BASE64.encode("d") },
+                { "op": "delete", "key": "other" },
+            ]
+        });
+        let (status, body) = request_json(addr, "POST", "/batch", batch.to_string().as_bytes());
+        assert_eq!(status, 200);
+        assert_eq!(body["applied"], 5);
+
+        // 格式不正确的批量请求不会执行任何操作
+        let bad = json!({ "ops": [
+            { "op": "put", "key": "x", "value": BASE64.encode("x") },
+            { "op": "put", "key": "y", "value": "not base64!" },
+        ]});
+        assert_eq!(
+            request(addr, "POST", "/batch", bad.to_string().as_bytes()).0,
+            400
+        );
+        assert_eq!(request(addr, "GET", "/kv/x", b"").0, 404);
+        let bad = json!({ "ops": [
+            { "op": "put", "key": "x", "value": BASE64.encode("x") },
+            { "op": "delete", "key": "user%3A1" },
+            { "op": "put", "key": "", "value": BASE64.encode("y") },
+        ]});
+        assert_eq!(
+            request(addr, "POST", "/batch", bad.to_string().as_bytes()).0,
+            400
+        );
+        assert_eq!(request(addr, "GET", "/kv/x", b"").0, 404);
+        assert_eq!(request(addr, "GET", "/kv/user%3A1", b"").0, 200);
+
+        let (status, page) = request_json(addr, "GET", "/kv?prefix=user%3A&limit=2", b"");
+        assert_eq!(status, 200);
+        assert_eq!(page["items"][0]["key"], "user%3A1");
+        assert_eq!(page["items"][0]["value"], BASE64.encode("a"));
+        assert_eq!(page["items"][1]["key"], "user%3A2");
+        assert_eq!(page["next_cursor"], "user%3A2");
+
+        let (_, page) = request_json(
+            addr,
+            "GET",
+            "/kv?prefix=user%3A&limit=2&cursor=user%3A2",
+            b"",
+        );
+        assert_eq!(page["items"].as_array().unwrap().len(), 1);
+        assert_eq!(page["items"][0]["key"], "user%3A3");
+        assert!(page["next_cursor"].is_null());
+
+        let (_, page) = request_json(addr, "GET", "/kv", b"");
+        assert_eq!(page["items"].as_array().unwrap().len(), 3);
+        assert_eq!(request(addr, "GET", "/kv?limit=0", b"").0, 400);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -1,7 +1,9 @@
 use crate::data::log_record::LogRecordPos;
-use crate::index::Indexer;
+use crate::index::{IndexIterator, Indexer};
+use crate::options::IteratorOptions;
 use parking_lot::RwLock;
 use std::collections::BTreeMap;
+use std::ops::Bound;
 use std::sync::Arc;
 
 // Btree索引，主要封装了标准库中的btreeMap结构
@@ -17,6 +19,12 @@ impl Btree {
     }
 }
 
+impl Default for Btree {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl Indexer for Btree {
     fn put(&self, key: Vec<u8>, pos: LogRecordPos) -> bool {
         let mut write_guard = self.tree.write();
@@ -34,9 +42,85 @@ impl Indexer for Btree {
         let remove_res = write_guard.remove(&key);
         remove_res.is_some()
     }
+
+    fn list_keys(&self) -> Vec<Vec<u8>> {
+        let read_guard = self.tree.read();
+        read_guard.keys().cloned().collect()
+    }
+
+    fn keys_after(&self, start: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>> {
+        let read_guard = self.tree.read();
+        let lower = match start {
+            Some(key) => Bound::Excluded(key),
+            None => Bound::Unbounded,
+        };
+        read_guard
+            .range::<[u8], _>((lower, Bound::Unbounded))
+            .take(limit)
+            .map(|(key, _)| key.clone())
+            .collect()
+    }
+
+    fn key_count(&self) -> usize {
+        self.tree.read().len()
+    }
+
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexIterator> {
+        let read_guard = self.tree.read();
+        // 拷贝一份前缀范围内的索引快照，迭代期间不持有锁
+        let mut items: Vec<(Vec<u8>, LogRecordPos)> = read_guard
+            .range(options.prefix.clone()..)
+            .take_while(|(key, _)| key.starts_with(&options.prefix))
+            .map(|(key, pos)| (key.clone(), *pos))
+            .collect();
+        if options.reverse {
+            items.reverse();
+        }
+        Box::new(BtreeIterator {
+            items,
+            curr_index: 0,
+            options,
+        })
+    }
+}
+
+/// Btree 索引迭代器
+pub struct BtreeIterator {
+    items: Vec<(Vec<u8>, LogRecordPos)>, // 存储key+索引
+    curr_index: usize,                   // 当前遍历的位置下标
+    options: IteratorOptions,            // 配置项
+}
+
+impl IndexIterator for BtreeIterator {
+    fn rewind(&mut self) {
+        self.curr_index = 0;
+    }
+
+    fn seek(&mut self, key: Vec<u8>) {
+        self.curr_index = match self.items.binary_search_by(|(x, _)| {
+            if self.options.reverse {
+                x.cmp(&key).reverse()
+            } else {
+                x.cmp(&key)
+            }
+        }) {
+            Ok(equal_val) => equal_val,
+            Err(insert_val) => insert_val,
+        };
+    }
+
+    fn next(&mut self) -> Option<(&Vec<u8>, &LogRecordPos)> {
+        if self.curr_index >= self.items.len() {
+            return None;
+        }
+        let item = self.items.get(self.curr_index).unwrap();
+        self.curr_index += 1;
+        Some((&item.0, &item.1))
+    }
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
     use super::*;
 
@@ -48,6 +132,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                seq: 0,
             },
         );
         assert_eq!(res1, true);
@@ -56,6 +141,7 @@ mod tests {
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                seq: 0,
             },
         );
         assert_eq!(res2, true);
@@ -69,6 +155,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                seq: 0,
             },
         );
         assert_eq!(res1, true);
@@ -77,6 +164,7 @@ mod tests {
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                seq: 0,
             },
         );
         assert_eq!(res2, true);
@@ -102,6 +190,7 @@ mod tests {
             LogRecordPos {
                 file_id: 1,
                 offset: 10,
+                seq: 0,
             },
         );
         assert_eq!(res1, true);
@@ -110,6 +199,7 @@ mod tests {
             LogRecordPos {
                 file_id: 11,
                 offset: 22,
+                seq: 0,
             },
         );
         assert_eq!(res2, true);
@@ -129,4 +219,58 @@ mod tests {
         assert_eq!(pos1.unwrap().file_id, 11);
         assert_eq!(pos1.unwrap().offset, 22);
     }
+
+    #[test]
+    fn test_btree_iterator() {
+        let bt = Btree::new();
+        for (i, key) in ["aa", "ab", "ba", "cc"].iter().enumerate() {
+            bt.put(key.as_bytes().to_vec(), LogRecordPos::new(1, i as u64));
+        }
+        assert_eq!(bt.list_keys().len(), 4);
+        assert_eq!(bt.key_count(), 4);
+        assert_eq!(bt.keys_after(None, 1), vec![b"aa".to_vec()]);
+        assert_eq!(
+            bt.keys_after(Some(b"ab"), 10),
+            vec![b"ba".to_vec(), b"cc".to_vec()]
+        );
+        assert!(bt.keys_after(Some(b"cc"), 10).is_empty());
+
+        // 正向遍历
+        let mut iter1 = bt.iterator(IteratorOptions::default());
+        let keys1: Vec<Vec<u8>> =
+            std::iter::from_fn(|| iter1.next().map(|(k, _)| k.clone())).collect();
+        assert_eq!(
+            keys1,
+            vec![
+                b"aa".to_vec(),
+                b"ab".to_vec(),
+                b"ba".to_vec(),
+                b"cc".to_vec()
+            ]
+        );
+
+        // seek 到第一个大于等于目标的key
+        iter1.seek(b"b".to_vec());
+        assert_eq!(iter1.next().unwrap().0, &b"ba".to_vec());
+        iter1.rewind();
+        assert_eq!(iter1.next().unwrap().0, &b"aa".to_vec());
+
+        // 反向遍历
+        let mut iter2 = bt.iterator(IteratorOptions {
+            reverse: true,
+            ..Default::default()
+        });
+        iter2.seek(b"bb".to_vec());
+        assert_eq!(iter2.next().unwrap().0, &b"ba".to_vec());
+        assert_eq!(iter2.next().unwrap().0, &b"ab".to_vec());
+
+        // 指定前缀
+        let mut iter3 = bt.iterator(IteratorOptions {
+            prefix: b"a".to_vec(),
+            ..Default::default()
+        });
+        assert_eq!(iter3.next().unwrap().1.offset, 0);
+        assert_eq!(iter3.next().unwrap().1.offset, 1);
+        assert!(iter3.next().is_none());
+    }
 }
@@ -1,7 +1,8 @@
 pub mod btree;
 
 use crate::data::log_record::LogRecordPos;
-use crate::options::IndexType;
+use crate::errors::{Errors, Result};
+use crate::options::{IndexType, IteratorOptions};
 
 /// Indexer 抽象索引接口，后续如果想要接入其他的数据结构，则直接实现这个接口即可
 pub trait Indexer: Sync + Send {
@@ -11,6 +12,32 @@ pub trait Indexer: Sync + Send {
     fn get(&self, key: Vec<u8>) -> Option<LogRecordPos>;
     /// 根据key,删除对应的索引位置信息，已存在就删除并返回旧的value，否则返回nil
     fn delete(&self, key: Vec<u8>) -> bool; // 根据key,删除对应的索引位置信息，已存在就删除并返回旧的value，否则返回nil
+    /// 获取索引中所有的key
+    fn list_keys(&self) -> Vec<Vec<u8>>;
+    /// 按顺序获取 start 之后（不包含 start）的最多 limit 个key，start 为空时从第一个key开始
+    fn keys_after(&self, start: Option<&[u8]>, limit: usize) -> Vec<Vec<u8>>;
+    /// 索引中key的数量
+    fn key_count(&self) -> usize;
+    /// 返回索引迭代器
+    fn iterator(&self, options: IteratorOptions) -> Box<dyn IndexIterator>;
+}
+
+/// IndexIterator 索引迭代器
+pub trait IndexIterator: Sync + Send {
+    /// 重新回到迭代器的起点，即第一个数据
+    fn rewind(&mut self);
+    /// 根据传入的key查找到第一个大于（或小于）等于的目标key，从这个key开始遍历
+    fn seek(&mut self, key: Vec<u8>);
+    /// 跳转到下一个key，返回None则说明迭代完毕
+    fn next(&mut self) -> Option<(&Vec<u8>, &LogRecordPos)>;
+}
+
+/// 检查索引类型是否已经实现，未实现的类型不能用于创建索引
+pub fn check_index_type(index_type: &IndexType) -> Result<()> {
+    match index_type {
+        IndexType::Btree => Ok(()),
+        IndexType::SkipList => Err(Errors::UnsupportedIndexType),
+    }
 }
 
 pub fn new_indexer(index_type: IndexType) -> impl Indexer {
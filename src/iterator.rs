@@ -0,0 +1,213 @@
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::index::IndexIterator;
+use crate::options::IteratorOptions;
+use bytes::Bytes;
+use log::error;
+use parking_lot::RwLock;
+use std::sync::Arc;
+
+/// 迭代器接口
+pub struct Iterator<'a> {
+    index_iter: Arc<RwLock<Box<dyn IndexIterator>>>, // 索引迭代器
+    engine: &'a Engine,
+}
+
+impl Engine {
+    /// 获取迭代器，迭代器遍历的是创建时的索引快照
+    pub fn iter(&self, options: IteratorOptions) -> Iterator<'_> {
+        Iterator {
+            index_iter: Arc::new(RwLock::new(self.index.iterator(options))),
+            engine: self,
+        }
+    }
+
+    /// 返回数据库中所有的key
+    pub fn list_keys(&self) -> Result<Vec<Bytes>> {
+        let keys = self.index.list_keys();
+        Ok(keys.into_iter().map(Bytes::from).collect())
+    }
+
+    /// 按顺序返回 start 之后（不包含 start）的最多 limit 个key，用于分批遍历所有的key
+    pub fn list_keys_after(&self, start: Option<&[u8]>, limit: usize) -> Result<Vec<Bytes>> {
+        let keys = self.index.keys_after(start, limit);
+        Ok(keys.into_iter().map(Bytes::from).collect())
+    }
+
+    /// 数据库中key的数量
+    pub fn key_count(&self) -> usize {
+        self.index.key_count()
+    }
+
+    /// 对数据库当中的所有数据执行函数操作，函数返回false时终止
+    pub fn fold<F>(&self, f: F) -> Result<()>
+    where
+        Self: Sized,
+        F: Fn(Bytes, Bytes) -> bool,
+    {
+        let iter = self.iter(IteratorOptions::default());
+        while let Some((key, value)) = iter.try_next()? {
+            if !f(key, value) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Iterator<'_> {
+    /// 重新回到迭代器的起点，即第一个数据
+    pub fn rewind(&self) {
+        let mut index_iter = self.index_iter.write();
+        index_iter.rewind();
+    }
+
+    /// 根据传入的key查找到第一个大于（或小于）等于的目标key，从这个key开始遍历
+    pub fn seek(&self, key: Vec<u8>) {
+        let mut index_iter = self.index_iter.write();
+        index_iter.seek(key);
+    }
+
+    /// 跳转到下一个key，返回None则说明迭代完毕，读取数据出错时记录日志并停止遍历
+    pub fn next(&self) -> Option<(Bytes, Bytes)> {
+        match self.try_next() {
+            Ok(item) => item,
+            Err(e) => {
+                error!("failed to read value in iterator: {}", e);
+                None
+            }
+        }
+    }
+
+    /// 跳转到下一个key，返回读取数据时的错误，已经删除或过期的key会被跳过
+    pub fn try_next(&self) -> Result<Option<(Bytes, Bytes)>> {
+        let mut index_iter = self.index_iter.write();
+        while let Some((key, pos)) = index_iter.next() {
+            match self.engine.get_value_by_position(pos) {
+                Ok(value) => return Ok(Some((Bytes::from(key.to_vec()), value))),
+                Err(Errors::KeyNotFound) => continue,
+                Err(e) => return Err(e),
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+
+    #[test]
+    fn test_iterator_seek_and_next() {
+        let dir_path = std::env::temp_dir().join("fdb-iterator");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // 没有数据
+        let iter1 = engine.iter(IteratorOptions::default());
+        assert!(iter1.next().is_none());
+
+        for key in ["ccde", "aade", "bbcc", "aacc"] {
+            engine
+                .put(Bytes::from(key), Bytes::from(key.repeat(2)))
+                .unwrap();
+        }
+
+        let iter2 = engine.iter(IteratorOptions::default());
+        let (key, value) = iter2.next().unwrap();
+        assert_eq!(key, "aacc");
+        assert_eq!(value, "aaccaacc");
+        iter2.seek(b"bb".to_vec());
+        assert_eq!(iter2.next().unwrap().0, "bbcc");
+        assert_eq!(iter2.next().unwrap().0, "ccde");
+        assert!(iter2.next().is_none());
+        iter2.rewind();
+        assert_eq!(iter2.next().unwrap().0, "aacc");
+
+        // 反向遍历指定前缀
+        let iter3 = engine.iter(IteratorOptions {
+            prefix: b"aa".to_vec(),
+            reverse: true,
+        });
+        assert_eq!(iter3.next().unwrap().0, "aade");
+        assert_eq!(iter3.next().unwrap().0, "aacc");
+        assert!(iter3.next().is_none());
+
+        // 迭代器创建之后的修改不影响遍历结果
+        let iter4 = engine.iter(IteratorOptions::default());
+        engine.delete(Bytes::from("aacc")).unwrap();
+        assert_eq!(iter4.next().unwrap().0, "aacc");
+        let iter5 = engine.iter(IteratorOptions::default());
+        assert_eq!(iter5.next().unwrap().0, "aade");
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_list_keys_and_fold() {
+        let dir_path = std::env::temp_dir().join("fdb-list-keys");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert!(engine.list_keys().unwrap().is_empty());
+
+        for key in ["b", "a", "c"] {
+            engine.put(Bytes::from(key), Bytes::from("v")).unwrap();
+        }
+        assert_eq!(engine.list_keys().unwrap(), vec!["a", "b", "c"]);
+
+        let count = std::cell::Cell::new(0);
+        engine
+            .fold(|_, value| {
+                assert_eq!(value, "v");
+                count.set(count.get() + 1);
+                count.get() < 2
+            })
+            .unwrap();
+        assert_eq!(count.get(), 2);
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_iterator_read_error() {
+        let dir_path = std::env::temp_dir().join("fdb-iterator-read-error");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        engine.put(Bytes::from("a"), Bytes::from("va")).unwrap();
+        let pos = engine.write_pos();
+        engine.put(Bytes::from("b"), Bytes::from("vb")).unwrap();
+        engine.put(Bytes::from("c"), Bytes::from("vc")).unwrap();
+        engine.delete(Bytes::from("a")).unwrap();
+
+        // 数据文件中的记录损坏时返回错误，而不是跳过这个key
+        let file_name = dir_path.join(format!("{:09}.data", 0));
+        let mut data = fs::read(&file_name).unwrap();
+        data[pos.offset() as usize + 4] ^= 0xff;
+        fs::write(&file_name, &data).unwrap();
+
+        let iter = engine.iter(IteratorOptions::default());
+        assert_eq!(iter.try_next(), Err(Errors::InvalidLogRecordCrc));
+        assert_eq!(iter.try_next().unwrap().unwrap().0, "c");
+        assert_eq!(iter.try_next(), Ok(None));
+        assert_eq!(engine.fold(|_, _| true), Err(Errors::InvalidLogRecordCrc));
+
+        drop(engine);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
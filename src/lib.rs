@@ -1,6 +1,26 @@
-mod data;
-mod db;
-mod errors;
-mod fio;
-mod index;
-mod options;
+pub mod async_engine;
+pub mod batch;
+pub mod blob;
+pub mod bulk_load;
+pub mod cache;
+pub mod cdc;
+pub mod column_family;
+pub mod conditional;
+pub mod data;
+pub mod db;
+pub mod errors;
+pub mod fio;
+pub mod glob;
+pub mod grpc;
+pub mod http;
+pub mod index;
+pub mod iterator;
+pub mod memcached;
+pub mod merge;
+pub mod options;
+pub mod redis;
+pub mod replication;
+pub mod structures;
+pub mod transfer;
+pub mod value_reader;
+pub mod watch;
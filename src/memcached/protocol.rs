@@ -0,0 +1,311 @@
+use bytes::Bytes;
+use std::io::{self, BufRead, Read};
+
+// key 的最大长度
+const MAX_KEY_LEN: usize = 250;
+// 单个 value 的最大长度
+const MAX_DATA_LEN: usize = 64 * 1024 * 1024;
+// 命令行的最大长度，超过时视为协议错误
+const MAX_LINE_LEN: usize = 64 * 1024;
+
+/// memcached 文本协议的命令
+#[derive(Clone, Debug, PartialEq)]
+pub enum Command {
+    Get {
+        keys: Vec<Bytes>,
+        with_cas: bool, // gets 命令需要返回 cas 值
+    },
+    Store {
+        mode: StoreMode,
+        key: Bytes,
+        flags: u32,
+        exptime: i64,
+        data: Bytes,
+        noreply: bool,
+    },
+    Delete {
+        key: Bytes,
+        noreply: bool,
+    },
+    Incr {
+        key: Bytes,
+        delta: u64,
+        decr: bool,
+        noreply: bool,
+    },
+    Version,
+    Quit,
+}
+
+/// 写入命令的类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StoreMode {
+    Set,
+    Add,
+    Replace,
+    Cas(u64),
+}
+
+/// 命令解析错误，连接可以继续使用
+#[derive(Clone, Debug, PartialEq)]
+pub enum CommandError {
+    // 未知命令，回复 ERROR
+    Unknown,
+    // 命令格式错误，回复 CLIENT_ERROR
+    Client(&'static str),
+}
+
+/// 读取一条命令，连接关闭时返回 None
+pub fn read_command<R: BufRead>(
+    reader: &mut R,
+) -> io::Result<Option<Result<Command, CommandError>>> {
+    let line = loop {
+        match read_line(reader)? {
+            Some(line) if line.iter().all(u8::is_ascii_whitespace) => continue,
+            Some(line) => break line,
+            None => return Ok(None),
+        }
+    };
+    let args: Vec<&[u8]> = line
+        .split(|c| *c == b' ')
+        .filter(|s| !s.is_empty())
+        .collect();
+
+    let res = match args[0] {
+        b"get" | b"gets" => parse_get(&args),
+        b"set" | b"add" | b"replace" | b"cas" => return read_store(reader, &args).map(Some),
+        b"delete" => parse_delete(&args),
+        b"incr" | b"decr" => parse_incr(&args),
+        b"version" => Ok(Command::Version),
+        b"quit" => Ok(Command::Quit),
+        _ => Err(CommandError::Unknown),
+    };
+    Ok(Some(res))
+}
+
+fn parse_get(args: &[&[u8]]) -> Result<Command, CommandError> {
+    if args.len() < 2 {
+        return Err(CommandError::Unknown);
+    }
+    let keys = args[1..]
+        .iter()
+        .map(|key| parse_key(key))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Command::Get {
+        keys,
+        with_cas: args[0] == b"gets",
+    })
+}
+
+// <command> <key> <flags> <exptime> <bytes> [<cas unique>] [noreply]\r\n<data>\r\n
+fn read_store<R: BufRead>(
+    reader: &mut R,
+    args: &[&[u8]],
+) -> io::Result<Result<Command, CommandError>> {
+    let is_cas = args[0] == b"cas";
+    let required = if is_cas { 6 } else { 5 };
+    if args.len() < required || args.len() > required + 1 {
+        return Ok(Err(CommandError::Unknown));
+    }
+    // 数据长度不合法时无法确定数据块的边界，只能断开连接
+    let len = match parse_number::<usize>(args[4]) {
+        Ok(len) if len <= MAX_DATA_LEN => len,
+        _ => return Err(protocol_error("bad data chunk length")),
+    };
+    let mut data = vec![0u8; len + 2];
+    reader.read_exact(&mut data)?;
+    if &data[len..] != b"\r\n" {
+        // 丢弃数据块之后剩余的内容，直到行尾
+        if data.last() != Some(&b'\n') {
+            read_line(reader)?;
+        }
+        return Ok(Err(CommandError::Client("bad data chunk")));
+    }
+    data.truncate(len);
+
+    let parse = || -> Result<Command, CommandError> {
+        let key = parse_key(args[1])?;
+        let flags = parse_number::<u32>(args[2])?;
+        let exptime = parse_number::<i64>(args[3])?;
+        let mode = match args[0] {
+            b"set" => StoreMode::Set,
+            b"add" => StoreMode::Add,
+            b"replace" => StoreMode::Replace,
+            _ => StoreMode::Cas(parse_number::<u64>(args[5])?),
+        };
+        Ok(Command::Store {
+            mode,
+            key,
+            flags,
+            exptime,
+            data: Bytes::from(data),
+            noreply: parse_noreply(args, required)?,
+        })
+    };
+    Ok(parse())
+}
+
+// delete <key> [noreply]
+fn parse_delete(args: &[&[u8]]) -> Result<Command, CommandError> {
+    if args.len() < 2 || args.len() > 3 {
+        return Err(CommandError::Unknown);
+    }
+    Ok(Command::Delete {
+        key: parse_key(args[1])?,
+        noreply: parse_noreply(args, 2)?,
+    })
+}
+
+// incr|decr <key> <value> [noreply]
+fn parse_incr(args: &[&[u8]]) -> Result<Command, CommandError> {
+    if args.len() < 3 || args.len() > 4 {
+        return Err(CommandError::Unknown);
+    }
+    Ok(Command::Incr {
+        key: parse_key(args[1])?,
+        delta: parse_number::<u64>(args[2])
+            .map_err(|_| CommandError::Client("invalid numeric delta argument"))?,
+        decr: args[0] == b"decr",
+        noreply: parse_noreply(args, 3)?,
+    })
+}
+
+fn parse_key(key: &[u8]) -> Result<Bytes, CommandError> {
+    if key.len() > MAX_KEY_LEN || key.iter().any(|c| c.is_ascii_control()) {
+        return Err(CommandError::Client("bad command line format"));
+    }
+    Ok(Bytes::copy_from_slice(key))
+}
+
+fn parse_noreply(args: &[&[u8]], index: usize) -> Result<bool, CommandError> {
+    match args.get(index) {
+        None => Ok(false),
+        Some(&b"noreply") => Ok(true),
+        Some(_) => Err(CommandError::Client("bad command line format")),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(s: &[u8]) -> Result<T, CommandError> {
+    std::str::from_utf8(s)
+        .ok()
+        .and_then(|s| s.parse::<T>().ok())
+        .ok_or(CommandError::Client("bad command line format"))
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    let n = reader
+        .by_ref()
+        .take(MAX_LINE_LEN as u64 + 1)
+        .read_until(b'\n', &mut line)?;
+    if n == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return match line.len() > MAX_LINE_LEN {
+            true => Err(protocol_error("line too long")),
+            false => Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "unexpected end of stream",
+            )),
+        };
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("CLIENT_ERROR {}", msg))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(input: &[u8]) -> Vec<Result<Command, CommandError>> {
+        let mut reader = input;
+        let mut commands = Vec::new();
+        while let Some(command) = read_command(&mut reader).unwrap() {
+            commands.push(command);
+        }
+        commands
+    }
+
+    #[test]
+    fn test_read_command() {
+        let commands = parse(
+            b"get a b\r\ngets a\r\nset k 5 100 3\r\nabc\r\ncas k 0 0 2 42 noreply\r\nhi\r\n\
+              delete k\r\nincr n 10\r\ndecr n 1 noreply\r\nversion\r\nquit\r\n",
+        );
+        assert_eq!(
+            commands,
+            vec![
+                Ok(Command::Get {
+                    keys: vec![Bytes::from("a"), Bytes::from("b")],
+                    with_cas: false,
+                }),
+                Ok(Command::Get {
+                    keys: vec![Bytes::from("a")],
+                    with_cas: true,
+                }),
+                Ok(Command::Store {
+                    mode: StoreMode::Set,
+                    key: Bytes::from("k"),
+                    flags: 5,
+                    exptime: 100,
+                    data: Bytes::from("abc"),
+                    noreply: false,
+                }),
+                Ok(Command::Store {
+                    mode: StoreMode::Cas(42),
+                    key: Bytes::from("k"),
+                    flags: 0,
+                    exptime: 0,
+                    data: Bytes::from("hi"),
+                    noreply: true,
+                }),
+                Ok(Command::Delete {
+                    key: Bytes::from("k"),
+                    noreply: false,
+                }),
+                Ok(Command::Incr {
+                    key: Bytes::from("n"),
+                    delta: 10,
+                    decr: false,
+                    noreply: false,
+                }),
+                Ok(Command::Incr {
+                    key: Bytes::from("n"),
+                    delta: 1,
+                    decr: true,
+                    noreply: true,
+                }),
+                Ok(Command::Version),
+                Ok(Command::Quit),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_read_command_errors() {
+        let commands =
+            parse(b"foo\r\nget\r\nset k x 0 1\r\na\r\nset k 0 0 1\r\nab\r\nincr n x\r\n");
+        assert_eq!(
+            commands,
+            vec![
+                Err(CommandError::Unknown),
+                Err(CommandError::Unknown),
+                Err(CommandError::Client("bad command line format")),
+                Err(CommandError::Client("bad data chunk")),
+                Err(CommandError::Client("invalid numeric delta argument")),
+            ]
+        );
+
+        // 数据长度不合法时无法继续解析
+        let mut reader = &b"set k 0 0 x\r\n"[..];
+        assert!(read_command(&mut reader).is_err());
+    }
+}
@@ -0,0 +1,516 @@
+use crate::db::Engine;
+use crate::errors::Errors;
+use crate::memcached::protocol::{read_command, Command, CommandError, StoreMode};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::{info, warn};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// exptime 超过30天时表示绝对的 unix 时间戳，否则表示相对当前的秒数
+const MAX_RELATIVE_EXPTIME: i64 = 60 * 60 * 24 * 30;
+// 存储在引擎中的 value 头部：flags + 过期时间 + cas
+const ITEM_HEADER_SIZE: usize = 4 + 8 + 8;
+
+/// 兼容 memcached 文本协议的服务端，每个连接使用一个线程处理
+///
+/// 每个 value 在引擎中存储为 `flags | 过期时间 | cas | data`，
+/// 与其他前端写入的数据格式不同，建议使用单独的数据库目录。
+pub struct MemcachedServer {
+    store: Arc<ItemStore>,
+    listener: TcpListener,
+}
+
+// memcached 的数据项
+#[derive(Clone, Debug, PartialEq)]
+struct Item {
+    flags: u32,
+    expire_at: u64, // 过期时间，unix 秒级时间戳，0表示不过期
+    cas: u64,
+    data: Bytes,
+}
+
+impl Item {
+    fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(ITEM_HEADER_SIZE + self.data.len());
+        buf.put_u32(self.flags);
+        buf.put_u64(self.expire_at);
+        buf.put_u64(self.cas);
+        buf.extend_from_slice(&self.data);
+        buf.freeze()
+    }
+
+    fn decode(mut buf: Bytes) -> Option<Self> {
+        if buf.len() < ITEM_HEADER_SIZE {
+            return None;
+        }
+        Some(Item {
+            flags: buf.get_u32(),
+            expire_at: buf.get_u64(),
+            cas: buf.get_u64(),
+            data: buf,
+        })
+    }
+
+    fn is_expired(&self, now: u64) -> bool {
+        self.expire_at != 0 && self.expire_at <= now
+    }
+}
+
+// 所有连接共享的存储，add、replace、cas、incr 需要先读后写，在引擎的写锁内完成，
+// 与其他前端的写入之间也不会交错
+struct ItemStore {
+    engine: Arc<Engine>,
+}
+
+// 命令执行结果，对应协议中的回复
+type CmdResult = Result<Vec<u8>, String>;
+
+impl MemcachedServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A, engine: Arc<Engine>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        let store = Arc::new(ItemStore { engine });
+        Ok(Self { store, listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 循环接收客户端连接，直到监听出错
+    pub fn serve(&self) -> io::Result<()> {
+        info!("memcached server listening on {}", self.local_addr()?);
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let store = self.store.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_connection(store, stream) {
+                    warn!("memcached connection {:?} closed with error: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_connection(store: Arc<ItemStore>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+
+    loop {
+        let command = match read_command(&mut reader) {
+            Ok(Some(command)) => command,
+            Ok(None) => break,
+            // 无法继续解析时回复错误并断开连接
+            Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+                writer.write_all(format!("{}\r\n", e).as_bytes())?;
+                break;
+            }
+            Err(e) => return Err(e),
+        };
+        let reply = match command {
+            Ok(Command::Quit) => break,
+            Ok(command) => {
+                let noreply = is_noreply(&command);
+                let reply = store
+                    .execute(command)
+                    .unwrap_or_else(|msg| format!("SERVER_ERROR {}\r\n", msg).into_bytes());
+                match noreply {
+                    true => Vec::new(),
+                    false => reply,
+                }
+            }
+            Err(CommandError::Unknown) => b"ERROR\r\n".to_vec(),
+            Err(CommandError::Client(msg)) => format!("CLIENT_ERROR {}\r\n", msg).into_bytes(),
+        };
+        writer.write_all(&reply)?;
+        // 没有后续的流水线命令时再刷新，减少系统调用
+        if reader.buffer().is_empty() {
+            writer.flush()?;
+        }
+    }
+    writer.flush()
+}
+
+fn is_noreply(command: &Command) -> bool {
+    match command {
+        Command::Store { noreply, .. }
+        | Command::Delete { noreply, .. }
+        | Command::Incr { noreply, .. } => *noreply,
+        _ => false,
+    }
+}
+
+impl ItemStore {
+    fn execute(&self, command: Command) -> CmdResult {
+        match command {
+            Command::Get { keys, with_cas } => self.get(&keys, with_cas),
+            Command::Store {
+                mode,
+                key,
+                flags,
+                exptime,
+                data,
+                ..
+            } => self.store(mode, key, flags, exptime, data),
+            Command::Delete { key, .. } => self.delete(key),
+            Command::Incr {
+                key, delta, decr, ..
+            } => self.incr(key, delta, decr),
+            Command::Version => {
+                Ok(format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION")).into_bytes())
+            }
+            Command::Quit => Ok(Vec::new()),
+        }
+    }
+
+    // get|gets <key>*
+    fn get(&self, keys: &[Bytes], with_cas: bool) -> CmdResult {
+        let mut reply = Vec::new();
+        for key in keys {
+            let item = match self.get_item(key.clone())? {
+                Some(item) => item,
+                None => continue,
+            };
+            let header = match with_cas {
+                true => format!(" {} {} {}\r\n", item.flags, item.data.len(), item.cas),
+                false => format!(" {} {}\r\n", item.flags, item.data.len()),
+            };
+            reply.extend_from_slice(b"VALUE ");
+            reply.extend_from_slice(key);
+            reply.extend_from_slice(header.as_bytes());
+            reply.extend_from_slice(&item.data);
+            reply.extend_from_slice(b"\r\n");
+        }
+        reply.extend_from_slice(b"END\r\n");
+        Ok(reply)
+    }
+
+    // set|add|replace|cas
+    fn store(
+        &self,
+        mode: StoreMode,
+        key: Bytes,
+        flags: u32,
+        exptime: i64,
+        data: Bytes,
+    ) -> CmdResult {
+        self.check_writable()?;
+        let _guard = self.engine.lock_writes();
+        let old = match mode {
+            StoreMode::Set => None,
+            _ => self.get_item(key.clone())?,
+        };
+        let reply: &[u8] = match (mode, &old) {
+            (StoreMode::Add, Some(_)) | (StoreMode::Replace, None) => b"NOT_STORED\r\n",
+            (StoreMode::Cas(_), None) => b"NOT_FOUND\r\n",
+            (StoreMode::Cas(cas), Some(old)) if old.cas != cas => b"EXISTS\r\n",
+            _ => b"STORED\r\n",
+        };
+        if reply != b"STORED\r\n" {
+            return Ok(reply.to_vec());
+        }
+
+        let now = now_secs();
+        let expire_at = match exptime {
+            0 => 0,
+            // 负数表示立即过期
+            exptime if exptime < 0 => 1,
+            exptime if exptime > MAX_RELATIVE_EXPTIME => exptime as u64,
+            exptime => now + exptime as u64,
+        };
+        let item = Item {
+            flags,
+            expire_at,
+            cas: self.next_cas(),
+            data,
+        };
+        self.put_item(key, &item)?;
+        Ok(reply.to_vec())
+    }
+
+    // delete <key>
+    fn delete(&self, key: Bytes) -> CmdResult {
+        self.check_writable()?;
+        let _guard = self.engine.lock_writes();
+        if self.get_item(key.clone())?.is_none() {
+            return Ok(b"NOT_FOUND\r\n".to_vec());
+        }
+        self.engine.delete_value(&key).map_err(|e| e.to_string())?;
+        Ok(b"DELETED\r\n".to_vec())
+    }
+
+    // incr|decr <key> <value>，incr 在 64 位溢出时回绕，decr 最小减到 0
+    fn incr(&self, key: Bytes, delta: u64, decr: bool) -> CmdResult {
+        self.check_writable()?;
+        let _guard = self.engine.lock_writes();
+        let mut item = match self.get_item(key.clone())? {
+            Some(item) => item,
+            None => return Ok(b"NOT_FOUND\r\n".to_vec()),
+        };
+        let value = std::str::from_utf8(&item.data)
+            .ok()
+            .and_then(|s| s.trim_end().parse::<u64>().ok());
+        let value = match value {
+            Some(value) => value,
+            None => {
+                return Ok(
+                    b"CLIENT_ERROR cannot increment or decrement non-numeric value\r\n".to_vec(),
+                )
+            }
+        };
+        let value = match decr {
+            true => value.saturating_sub(delta),
+            false => value.wrapping_add(delta),
+        };
+        item.data = Bytes::from(value.to_string());
+        item.cas = self.next_cas();
+        self.put_item(key, &item)?;
+        Ok(format!("{}\r\n", value).into_bytes())
+    }
+
+    // 读取未过期的数据项
+    fn get_item(&self, key: Bytes) -> Result<Option<Item>, String> {
+        let value = match self.engine.get(key) {
+            Ok(value) => value,
+            Err(Errors::KeyNotFound) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let item = Item::decode(value).ok_or("invalid item stored in database")?;
+        match item.is_expired(now_secs()) {
+            true => Ok(None),
+            false => Ok(Some(item)),
+        }
+    }
+
+    // 写入数据项，调用方需要持有引擎的写锁
+    fn put_item(&self, key: Bytes, item: &Item) -> Result<(), String> {
+        self.engine
+            .put_value(&key, &item.encode())
+            .map_err(|e| e.to_string())
+    }
+
+    // 只读模式下不能写入，在获取写锁之前检查
+    fn check_writable(&self) -> Result<(), String> {
+        match self.engine.options().read_only {
+            true => Err(Errors::DatabaseIsReadOnly.to_string()),
+            false => Ok(()),
+        }
+    }
+
+    // 生成新的 cas 值，使用引擎的序列号。数据项记录的序列号大于其中的 cas，
+    // 重新打开时恢复的序列号不会小于已经使用过的 cas，重启之后仍然保持递增
+    fn next_cas(&self) -> u64 {
+        self.engine.next_seq()
+    }
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+    use std::io::{BufRead, Read};
+
+    fn start_server(name: &str) -> (SocketAddr, std::path::PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let server = MemcachedServer::bind("127.0.0.1:0", Arc::new(engine)).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+        (addr, dir_path)
+    }
+
+    struct Conn {
+        reader: BufReader<TcpStream>,
+        writer: TcpStream,
+    }
+
+    impl Conn {
+        fn connect(addr: SocketAddr) -> Self {
+            let stream = TcpStream::connect(addr).unwrap();
+            Conn {
+                reader: BufReader::new(stream.try_clone().unwrap()),
+                writer: stream,
+            }
+        }
+
+        fn send(&mut self, request: &str) {
+            self.writer.write_all(request.as_bytes()).unwrap();
+        }
+
+        fn line(&mut self) -> String {
+            let mut line = String::new();
+            self.reader.read_line(&mut line).unwrap();
+            line
+        }
+
+        // 发送请求并读取一行回复
+        fn call(&mut self, request: &str) -> String {
+            self.send(request);
+            self.line()
+        }
+    }
+
+    #[test]
+    fn test_item_encode_and_decode() {
+        let item = Item {
+            flags: 7,
+            expire_at: 100,
+            cas: 42,
+            data: Bytes::from("value"),
+        };
+        assert_eq!(Item::decode(item.encode()), Some(item));
+        assert_eq!(Item::decode(Bytes::from("bad")), None);
+    }
+
+    #[test]
+    fn test_memcached_storage_commands() {
+        let (addr, dir_path) = start_server("fdb-memcached-storage");
+        let mut conn = Conn::connect(addr);
+
+        assert_eq!(conn.call("get name\r\n"), "END\r\n");
+        assert_eq!(conn.call("set name 5 0 3\r\nfdb\r\n"), "STORED\r\n");
+        assert_eq!(conn.call("add name 0 0 1\r\nx\r\n"), "NOT_STORED\r\n");
+        assert_eq!(conn.call("replace none 0 0 1\r\nx\r\n"), "NOT_STORED\r\n");
+        assert_eq!(conn.call("add other 0 0 1\r\nx\r\n"), "STORED\r\n");
+
+        conn.send("get name other missing\r\n");
+        assert_eq!(conn.line(), "VALUE name 5 3\r\n");
+        assert_eq!(conn.line(), "fdb\r\n");
+        assert_eq!(conn.line(), "VALUE other 0 1\r\n");
+        assert_eq!(conn.line(), "x\r\n");
+        assert_eq!(conn.line(), "END\r\n");
+
+        // cas 值不匹配时不会写入
+        conn.send("gets name\r\n");
+        let header = conn.line();
+        let cas: u64 = header
+            .trim_end()
+            .rsplit(' ')
+            .next()
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(conn.line(), "fdb\r\n");
+        assert_eq!(conn.line(), "END\r\n");
+        let request = format!("cas name 1 0 2 {}\r\nv2\r\n", cas + 1);
+        assert_eq!(conn.call(&request), "EXISTS\r\n");
+        let request = format!("cas name 1 0 2 {}\r\nv2\r\n", cas);
+        assert_eq!(conn.call(&request), "STORED\r\n");
+        assert_eq!(conn.call(&request), "EXISTS\r\n");
+        assert_eq!(conn.call("cas none 0 0 1 1\r\nx\r\n"), "NOT_FOUND\r\n");
+
+        assert_eq!(conn.call("delete name\r\n"), "DELETED\r\n");
+        assert_eq!(conn.call("delete name\r\n"), "NOT_FOUND\r\n");
+        assert_eq!(conn.call("get name\r\n"), "END\r\n");
+
+        // noreply 不返回结果
+        conn.send("set quiet 0 0 1 noreply\r\nq\r\n");
+        assert_eq!(
+            conn.call("version\r\n"),
+            format!("VERSION {}\r\n", env!("CARGO_PKG_VERSION"))
+        );
+        assert_eq!(conn.call("bogus\r\n"), "ERROR\r\n");
+        assert_eq!(
+            conn.call("set k 0 0 1\r\nxy\r\n"),
+            "CLIENT_ERROR bad data chunk\r\n"
+        );
+
+        // 数据已持久化，新连接也可以读到
+        let mut conn = Conn::connect(addr);
+        conn.send("get quiet\r\n");
+        assert_eq!(conn.line(), "VALUE quiet 0 1\r\n");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_memcached_incr_and_expire() {
+        let (addr, dir_path) = start_server("fdb-memcached-incr");
+        let mut conn = Conn::connect(addr);
+
+        assert_eq!(conn.call("incr n 1\r\n"), "NOT_FOUND\r\n");
+        assert_eq!(conn.call("set n 3 0 2\r\n10\r\n"), "STORED\r\n");
+        assert_eq!(conn.call("incr n 5\r\n"), "15\r\n");
+        assert_eq!(conn.call("decr n 100\r\n"), "0\r\n");
+        assert_eq!(
+            conn.call("incr n 18446744073709551615\r\n"),
+            "18446744073709551615\r\n"
+        );
+        assert_eq!(conn.call("incr n 2\r\n"), "1\r\n");
+        conn.send("get n\r\n");
+        assert_eq!(conn.line(), "VALUE n 3 1\r\n");
+        assert_eq!(conn.line(), "1\r\n");
+        assert_eq!(conn.line(), "END\r\n");
+
+        assert_eq!(conn.call("set s 0 0 3\r\nabc\r\n"), "STORED\r\n");
+        assert_eq!(
+            conn.call("incr s 1\r\n"),
+            "CLIENT_ERROR cannot increment or decrement non-numeric value\r\n"
+        );
+
+        // 负数的过期时间表示立即过期，过期的数据不可见
+        assert_eq!(conn.call("set tmp 0 -1 1\r\nx\r\n"), "STORED\r\n");
+        assert_eq!(conn.call("get tmp\r\n"), "END\r\n");
+        assert_eq!(conn.call("add tmp 0 100 1\r\ny\r\n"), "STORED\r\n");
+        conn.send("get tmp\r\n");
+        assert_eq!(conn.line(), "VALUE tmp 0 1\r\n");
+
+        // 数据长度不合法时断开连接
+        let mut conn = Conn::connect(addr);
+        assert_eq!(
+            conn.call("set k 0 0 x\r\n"),
+            "CLIENT_ERROR bad data chunk length\r\n"
+        );
+        let mut rest = Vec::new();
+        conn.reader.read_to_end(&mut rest).unwrap();
+        assert!(rest.is_empty());
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_memcached_cas_after_reopen() {
+        let dir_path = std::env::temp_dir().join("fdb-memcached-cas");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        };
+        let store = ItemStore {
+            engine: Arc::new(Engine::open(opts.clone()).unwrap()),
+        };
+        store
+            .store(StoreMode::Set, Bytes::from("k"), 0, 0, Bytes::from("v"))
+            .unwrap();
+        let cas = store.get_item(Bytes::from("k")).unwrap().unwrap().cas;
+        drop(store);
+
+        // 重新打开之后生成的 cas 仍然大于之前的值
+        let store = ItemStore {
+            engine: Arc::new(Engine::open(opts).unwrap()),
+        };
+        store
+            .store(StoreMode::Set, Bytes::from("k"), 0, 0, Bytes::from("v"))
+            .unwrap();
+        assert!(store.get_item(Bytes::from("k")).unwrap().unwrap().cas > cas);
+
+        drop(store);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
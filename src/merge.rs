@@ -0,0 +1,352 @@
+use crate::column_family::DEFAULT_CF_ID;
+use crate::data::log_record::{LogRecord, LogRecordPos, LogRecordType};
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::options::Compression;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+
+// 合并链超过该长度时写入合并后的完整值，避免读取时遍历过长的链
+const MAX_MERGE_DEPTH: u32 = 64;
+// MERGE 记录 value 中操作数之前的部分的长度
+const MERGE_HEADER_SIZE: usize = 4 + 1 + 4 + 8;
+
+/// 合并操作，把 merge_value 写入的操作数合并到 key 原来的值上，在 Options 中配置
+///
+/// 同一个数据目录每次打开都需要配置相同的合并操作。
+pub trait MergeOperator: Send + Sync {
+    /// 将 operands 按写入顺序依次合并到 existing 上，existing 为 None 表示 key 不存在，
+    /// 返回 None 表示操作数或者原来的值无效
+    fn full_merge(
+        &self,
+        key: &[u8],
+        existing: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>>;
+}
+
+/// 计数器，值和操作数均为 8 字节小端编码的 u64，相加时溢出回绕
+pub struct U64AddOperator;
+
+impl MergeOperator for U64AddOperator {
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let decode = |v: &[u8]| v.try_into().ok().map(u64::from_le_bytes);
+        let mut sum = match existing {
+            Some(v) => decode(v)?,
+            None => 0,
+        };
+        for operand in operands {
+            sum = sum.wrapping_add(decode(operand)?);
+        }
+        Some(sum.to_le_bytes().to_vec())
+    }
+}
+
+/// 追加字节，非空的值之间插入分隔符
+#[derive(Default)]
+pub struct AppendOperator {
+    delimiter: Vec<u8>,
+}
+
+impl AppendOperator {
+    pub fn new(delimiter: &[u8]) -> Self {
+        Self {
+            delimiter: delimiter.to_vec(),
+        }
+    }
+}
+
+impl MergeOperator for AppendOperator {
+    fn full_merge(
+        &self,
+        _key: &[u8],
+        existing: Option<&[u8]>,
+        operands: &[&[u8]],
+    ) -> Option<Vec<u8>> {
+        let mut value = existing.map(|v| v.to_vec()).unwrap_or_default();
+        for operand in operands {
+            if !value.is_empty() {
+                value.extend_from_slice(&self.delimiter);
+            }
+            value.extend_from_slice(operand);
+        }
+        Some(value)
+    }
+}
+
+// MERGE 记录的 value，prev 指向同一个 key 的上一条记录，读取时沿着 prev 找到原来的值
+struct MergeRecord {
+    depth: u32, // 合并链中操作数的数量，包含当前记录
+    prev: Option<LogRecordPos>,
+    operand: Vec<u8>,
+}
+
+impl MergeRecord {
+    //  +-------------+-------------+-------------+-------------+-------------+
+    //  |    depth    |  has prev   | prev file id| prev offset |   operand   |
+    //  +-------------+-------------+-------------+-------------+-------------+
+    //      4字节         1字节          4字节         8字节         变长
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = BytesMut::with_capacity(MERGE_HEADER_SIZE + self.operand.len());
+        buf.put_u32(self.depth);
+        let prev = self.prev.unwrap_or_default();
+        buf.put_u8(self.prev.is_some() as u8);
+        buf.put_u32(prev.file_id);
+        buf.put_u64(prev.offset);
+        buf.extend_from_slice(&self.operand);
+        buf.to_vec()
+    }
+
+    fn decode(mut buf: &[u8]) -> Result<Self> {
+        if buf.len() < MERGE_HEADER_SIZE {
+            return Err(Errors::InvalidMergeRecord);
+        }
+        let depth = buf.get_u32();
+        let has_prev = buf.get_u8() == 1;
+        let prev = LogRecordPos::new(buf.get_u32(), buf.get_u64());
+        Ok(MergeRecord {
+            depth,
+            prev: has_prev.then_some(prev),
+            operand: buf.to_vec(),
+        })
+    }
+}
+
+impl Engine {
+    /// 追加一个合并操作数，读取时使用 Options 中配置的合并操作合并到 key 原来的值上
+    ///
+    /// 写入时不读取原来的值，合并链超过一定长度时才合并为完整的值写入。
+    pub fn merge_value(&self, key: Bytes, operand: Bytes) -> Result<()> {
+        if self.options().read_only {
+            return Err(Errors::DatabaseIsReadOnly);
+        }
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        if self.options().merge_operator.is_none() {
+            return Err(Errors::MergeOperatorNotConfigured);
+        }
+
+        let _guard = self.lock_writes();
+        let prev = self.index.get(key.to_vec());
+        let depth = match prev {
+            Some(pos) => match self.read_merge_record(&pos)? {
+                Some(merge) => merge.depth + 1,
+                None => 1,
+            },
+            None => 1,
+        };
+        let mut record = LogRecord {
+            key: key.to_vec(),
+            value: MergeRecord {
+                depth,
+                prev,
+                operand: operand.to_vec(),
+            }
+            .encode(),
+            rec_type: LogRecordType::MERGE,
+            compression: Compression::None,
+            seq: 0,
+            cf: DEFAULT_CF_ID,
+            expire: 0,
+        };
+        // 合并链过长，合并后作为完整的值写入
+        if depth > MAX_MERGE_DEPTH {
+            let value = self.fold_merge(&record)?;
+            return self.put_value(&key, &value);
+        }
+
+        let pos = self.append_log_record(&mut record)?;
+        if !self.index.put(key.to_vec(), pos) {
+            return Err(Errors::IndexUpdateFailed);
+        }
+        Ok(())
+    }
+
+    // 读取 pos 处的记录，是 MERGE 记录时返回解码后的内容
+    fn read_merge_record(&self, pos: &LogRecordPos) -> Result<Option<MergeRecord>> {
+        // 只读取 header，避免读取大 value
+        let header = self.with_data_file(pos.file_id, |data_file| {
+            data_file.read_log_record_header(pos.offset)
+        })?;
+        if header.rec_type() != LogRecordType::MERGE {
+            return Ok(None);
+        }
+        let record = self.read_log_record_at(pos)?.record;
+        MergeRecord::decode(&record.value).map(Some)
+    }
+
+    // 沿着合并链找到原来的值，并将链上的操作数按写入顺序合并
+    pub(crate) fn fold_merge(&self, record: &LogRecord) -> Result<Bytes> {
+        let Some(operator) = self.options().merge_operator.as_ref() else {
+            return Err(Errors::MergeOperatorNotConfigured);
+        };
+
+        let mut merge = MergeRecord::decode(&record.value)?;
+        let mut operands = Vec::with_capacity(merge.depth as usize);
+        let existing = loop {
+            operands.push(std::mem::take(&mut merge.operand));
+            let Some(prev) = merge.prev else {
+                break None;
+            };
+            let prev_record = self.read_log_record_at(&prev)?.record;
+            match prev_record.rec_type {
+                LogRecordType::MERGE => merge = MergeRecord::decode(&prev_record.value)?,
+                LogRecordType::NORMAL => break Some(Bytes::from(prev_record.value)),
+                LogRecordType::BLOB => break Some(self.read_blob(&prev_record.value)?),
+                LogRecordType::DELETE => break None,
+                LogRecordType::BATCH => return Err(Errors::InvalidMergeRecord),
+            }
+        };
+        operands.reverse();
+
+        let operands: Vec<&[u8]> = operands.iter().map(|operand| operand.as_slice()).collect();
+        match operator.full_merge(&record.key, existing.as_deref(), &operands) {
+            Some(value) => Ok(Bytes::from(value)),
+            None => Err(Errors::MergeFailed),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+    use std::sync::Arc;
+
+    fn counter(n: u64) -> Bytes {
+        Bytes::copy_from_slice(&n.to_le_bytes())
+    }
+
+    #[test]
+    fn test_builtin_merge_operators() {
+        let add = U64AddOperator;
+        let one = 1u64.to_le_bytes();
+        let two = 2u64.to_le_bytes();
+        assert_eq!(
+            add.full_merge(b"k", None, &[&one, &two]),
+            Some(3u64.to_le_bytes().to_vec())
+        );
+        assert_eq!(
+            add.full_merge(b"k", Some(&u64::MAX.to_le_bytes()), &[&two]),
+            Some(1u64.to_le_bytes().to_vec())
+        );
+        assert_eq!(add.full_merge(b"k", Some(b"abc"), &[&one]), None);
+
+        let append = AppendOperator::new(b",");
+        assert_eq!(
+            append.full_merge(b"k", None, &[b"a", b"b"]),
+            Some(b"a,b".to_vec())
+        );
+        assert_eq!(
+            append.full_merge(b"k", Some(b"x"), &[b"y"]),
+            Some(b"x,y".to_vec())
+        );
+    }
+
+    #[test]
+    fn test_engine_merge_value() {
+        let dir_path = std::env::temp_dir().join("fdb-merge-value");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let opts = Options {
+            dir_path: dir_path.clone(),
+            merge_operator: Some(Arc::new(U64AddOperator)),
+            ..Default::default()
+        };
+        let engine = Engine::open(opts.clone()).unwrap();
+
+        // 没有原来的值时从 0 开始累加
+        for _ in 0..10 {
+            engine.merge_value(Bytes::from("hits"), counter(1)).unwrap();
+        }
+        assert_eq!(engine.get(Bytes::from("hits")).unwrap(), counter(10));
+        // 合并到 put 写入的值上
+        engine.put(Bytes::from("total"), counter(100)).unwrap();
+        engine
+            .merge_value(Bytes::from("total"), counter(5))
+            .unwrap();
+        assert_eq!(engine.get(Bytes::from("total")).unwrap(), counter(105));
+        // 删除之后重新开始
+        engine.delete(Bytes::from("total")).unwrap();
+        engine
+            .merge_value(Bytes::from("total"), counter(7))
+            .unwrap();
+        assert_eq!(engine.get(Bytes::from("total")).unwrap(), counter(7));
+        // 无效的操作数在读取时返回错误
+        engine
+            .merge_value(Bytes::from("bad"), Bytes::from("x"))
+            .unwrap();
+        assert_eq!(engine.get(Bytes::from("bad")), Err(Errors::MergeFailed));
+
+        // 合并链过长时写入完整的值
+        for _ in 0..MAX_MERGE_DEPTH + 10 {
+            engine.merge_value(Bytes::from("long"), counter(2)).unwrap();
+        }
+        let pos = engine.index.get(b"long".to_vec()).unwrap();
+        assert!(engine.read_merge_record(&pos).unwrap().unwrap().depth <= 10);
+        let expected = counter(2 * (MAX_MERGE_DEPTH as u64 + 10));
+        assert_eq!(engine.get(Bytes::from("long")).unwrap(), expected);
+        drop(engine);
+
+        // 重新打开之后合并结果不变
+        let engine = Engine::open(opts).unwrap();
+        assert_eq!(engine.get(Bytes::from("hits")).unwrap(), counter(10));
+        assert_eq!(engine.get(Bytes::from("total")).unwrap(), counter(7));
+        assert_eq!(engine.get(Bytes::from("long")).unwrap(), expected);
+        drop(engine);
+
+        // 没有配置合并操作时不能写入和读取操作数
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(
+            engine.merge_value(Bytes::from("hits"), counter(1)),
+            Err(Errors::MergeOperatorNotConfigured)
+        );
+        assert_eq!(
+            engine.get(Bytes::from("hits")),
+            Err(Errors::MergeOperatorNotConfigured)
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_engine_merge_concurrent() {
+        let dir_path = std::env::temp_dir().join("fdb-merge-concurrent");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            merge_operator: Some(Arc::new(AppendOperator::default())),
+            ..Default::default()
+        })
+        .unwrap();
+
+        // 并发追加不会丢失操作数
+        std::thread::scope(|s| {
+            for i in 0..4 {
+                let engine = &engine;
+                s.spawn(move || {
+                    for _ in 0..50 {
+                        let operand = Bytes::from(vec![b'a' + i]);
+                        engine.merge_value(Bytes::from("log"), operand).unwrap();
+                    }
+                });
+            }
+        });
+        let value = engine.get(Bytes::from("log")).unwrap();
+        assert_eq!(value.len(), 200);
+        for i in 0..4 {
+            assert_eq!(value.iter().filter(|b| **b == b'a' + i).count(), 50);
+        }
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -1,4 +1,7 @@
+use crate::merge::MergeOperator;
 use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
 
 #[derive(Clone)]
 pub struct Options {
@@ -6,10 +9,28 @@ pub struct Options {
     pub dir_path: PathBuf,
     // 数据文件大小
     pub data_file_size: u64,
-    // 是否每次写都持久化
-    pub sync_writes: bool,
+    // 持久化策略
+    pub sync_policy: SyncPolicy,
     // 索引类型
     pub index_type: IndexType,
+    // value 压缩算法
+    pub compression: Compression,
+    // value 大小达到该阈值时才进行压缩
+    pub compression_threshold: usize,
+    // 数据文件加密密钥，为 None 时不加密
+    pub key_provider: Option<Arc<dyn KeyProvider>>,
+    // value 缓存的字节数上限，为 0 时不开启缓存
+    pub value_cache_size: usize,
+    // 旧数据文件最多同时打开的文件句柄数，为 0 时不限制
+    pub max_open_files: usize,
+    // 只读打开，不会创建或写入任何文件，可以与写入进程同时打开同一个目录
+    pub read_only: bool,
+    // value 大小达到该阈值时单独存放到 blob 文件中，为 0 时不开启
+    pub blob_threshold: usize,
+    // 单个 blob 文件的大小
+    pub blob_file_size: u64,
+    // merge_value 使用的合并操作，为 None 时不能写入合并操作数
+    pub merge_operator: Option<Arc<dyn MergeOperator>>,
 }
 
 #[derive(Clone)]
@@ -19,3 +40,97 @@ pub enum IndexType {
     // 跳表索引
     SkipList,
 }
+
+/// 列族配置项
+#[derive(Clone)]
+pub struct ColumnFamilyOptions {
+    // 列族的索引类型
+    pub index_type: IndexType,
+    // 写入的 key 默认的过期时间，为 None 时不过期
+    pub default_ttl: Option<Duration>,
+}
+
+impl Default for ColumnFamilyOptions {
+    fn default() -> Self {
+        Self {
+            index_type: IndexType::Btree,
+            default_ttl: None,
+        }
+    }
+}
+
+/// 索引迭代器配置项
+#[derive(Clone, Default)]
+pub struct IteratorOptions {
+    // 只遍历指定前缀的key
+    pub prefix: Vec<u8>,
+    // 是否反向遍历
+    pub reverse: bool,
+}
+
+/// 数据持久化策略，决定活跃文件何时调用 sync 刷盘
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyncPolicy {
+    // 每次写入后都持久化
+    Always,
+    // 未持久化的字节数达到阈值后持久化
+    EveryNBytes(u64),
+    // 由后台线程按固定时间间隔持久化
+    Interval(Duration),
+    // 不主动持久化，仅在切换活跃文件或手动调用 sync 时刷盘
+    Never,
+}
+
+/// value 压缩算法，压缩类型会记录在每条日志记录的 header 中，
+/// 因此不同配置写入的数据可以混合读取
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Compression {
+    // 不压缩
+    None = 0,
+    Lz4 = 1,
+    Zstd = 2,
+    Snappy = 3,
+}
+
+/// 数据文件加密密钥的提供者，可以从配置、环境变量或者密钥管理服务中获取密钥
+pub trait KeyProvider: Sync + Send {
+    /// 返回 32 字节的 ChaCha20-Poly1305 密钥
+    fn key(&self) -> [u8; 32];
+}
+
+/// 使用固定密钥的 KeyProvider
+pub struct StaticKeyProvider {
+    key: [u8; 32],
+}
+
+impl StaticKeyProvider {
+    pub fn new(key: [u8; 32]) -> Self {
+        Self { key }
+    }
+}
+
+impl KeyProvider for StaticKeyProvider {
+    fn key(&self) -> [u8; 32] {
+        self.key
+    }
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            dir_path: std::env::temp_dir().join("fdb"),
+            data_file_size: 256 * 1024 * 1024, // 256MB
+            sync_policy: SyncPolicy::Never,
+            index_type: IndexType::Btree,
+            compression: Compression::None,
+            compression_threshold: 4 * 1024, // 4KB
+            key_provider: None,
+            value_cache_size: 0,
+            max_open_files: 0,
+            read_only: false,
+            blob_threshold: 0,
+            blob_file_size: 256 * 1024 * 1024, // 256MB
+            merge_operator: None,
+        }
+    }
+}
@@ -0,0 +1,238 @@
+use bytes::Bytes;
+use std::io::{self, BufRead, Read, Write};
+
+// 单个参数的最大长度
+const MAX_BULK_LEN: usize = 64 * 1024 * 1024;
+// 单条命令的最大参数个数
+const MAX_ARGS: usize = 1024 * 1024;
+// 参数个数来自客户端，预先分配的容量不超过这个值
+const ARGS_CAPACITY_HINT: usize = 1024;
+
+/// RESP 协议的数据类型
+#[derive(Clone, Debug, PartialEq)]
+pub enum RespValue {
+    SimpleString(String),
+    Error(String),
+    Integer(i64),
+    BulkString(Bytes),
+    Array(Vec<RespValue>),
+    // RESP2 中编码为 null bulk string，RESP3 中为 `_`
+    Null,
+    // RESP3 的 map 类型，RESP2 中编码为扁平数组
+    Map(Vec<(RespValue, RespValue)>),
+}
+
+impl RespValue {
+    pub fn ok() -> Self {
+        RespValue::SimpleString("OK".to_string())
+    }
+
+    pub fn error(msg: impl Into<String>) -> Self {
+        RespValue::Error(msg.into())
+    }
+
+    pub fn bulk(value: impl Into<Bytes>) -> Self {
+        RespValue::BulkString(value.into())
+    }
+
+    /// 按照协议版本（2或3）编码
+    pub fn encode(&self, protocol: u8, buf: &mut Vec<u8>) {
+        match self {
+            RespValue::SimpleString(s) => write_line(buf, b'+', s.as_bytes()),
+            RespValue::Error(s) => write_line(buf, b'-', s.as_bytes()),
+            RespValue::Integer(n) => write_line(buf, b':', n.to_string().as_bytes()),
+            RespValue::BulkString(b) => {
+                write_line(buf, b'$', b.len().to_string().as_bytes());
+                buf.extend_from_slice(b);
+                buf.extend_from_slice(b"\r\n");
+            }
+            RespValue::Array(items) => {
+                write_line(buf, b'*', items.len().to_string().as_bytes());
+                for item in items {
+                    item.encode(protocol, buf);
+                }
+            }
+            RespValue::Null => match protocol {
+                3 => buf.extend_from_slice(b"_\r\n"),
+                _ => buf.extend_from_slice(b"$-1\r\n"),
+            },
+            RespValue::Map(pairs) => {
+                match protocol {
+                    3 => write_line(buf, b'%', pairs.len().to_string().as_bytes()),
+                    _ => write_line(buf, b'*', (pairs.len() * 2).to_string().as_bytes()),
+                }
+                for (k, v) in pairs {
+                    k.encode(protocol, buf);
+                    v.encode(protocol, buf);
+                }
+            }
+        }
+    }
+}
+
+fn write_line(buf: &mut Vec<u8>, prefix: u8, content: &[u8]) {
+    buf.push(prefix);
+    buf.extend_from_slice(content);
+    buf.extend_from_slice(b"\r\n");
+}
+
+/// 读取一条客户端命令，支持 multibulk 格式和 inline 格式
+/// 连接关闭时返回 None
+pub fn read_command<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<Bytes>>> {
+    loop {
+        let line = match read_line(reader)? {
+            Some(line) => line,
+            None => return Ok(None),
+        };
+        if line.is_empty() {
+            continue;
+        }
+        if line[0] != b'*' {
+            // inline 命令，按空白字符分割
+            let args: Vec<Bytes> = line
+                .split(|c| c.is_ascii_whitespace())
+                .filter(|s| !s.is_empty())
+                .map(Bytes::copy_from_slice)
+                .collect();
+            if args.is_empty() {
+                continue;
+            }
+            return Ok(Some(args));
+        }
+
+        let count = parse_len(&line[1..], MAX_ARGS)?;
+        let mut args = Vec::with_capacity(count.min(ARGS_CAPACITY_HINT));
+        for _ in 0..count {
+            let header = read_line(reader)?.ok_or_else(unexpected_eof)?;
+            if header.first() != Some(&b'$') {
+                return Err(protocol_error("expected '$'"));
+            }
+            let len = parse_len(&header[1..], MAX_BULK_LEN)?;
+            // 按实际收到的数据增长缓冲区，不根据声明的长度预先分配
+            let mut arg = Vec::new();
+            reader.by_ref().take(len as u64 + 2).read_to_end(&mut arg)?;
+            if arg.len() != len + 2 {
+                return Err(unexpected_eof());
+            }
+            if &arg[len..] != b"\r\n" {
+                return Err(protocol_error("bulk string not terminated by CRLF"));
+            }
+            arg.truncate(len);
+            args.push(Bytes::from(arg));
+        }
+        if args.is_empty() {
+            continue;
+        }
+        return Ok(Some(args));
+    }
+}
+
+/// 将回复写入到连接中
+pub fn write_value<W: Write>(writer: &mut W, value: &RespValue, protocol: u8) -> io::Result<()> {
+    let mut buf = Vec::new();
+    value.encode(protocol, &mut buf);
+    writer.write_all(&buf)
+}
+
+// 读取一行数据，去掉末尾的 CRLF
+fn read_line<R: BufRead>(reader: &mut R) -> io::Result<Option<Vec<u8>>> {
+    let mut line = Vec::new();
+    if reader.read_until(b'\n', &mut line)? == 0 {
+        return Ok(None);
+    }
+    if line.last() != Some(&b'\n') {
+        return Err(unexpected_eof());
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+    Ok(Some(line))
+}
+
+fn parse_len(s: &[u8], max: usize) -> io::Result<usize> {
+    let len = std::str::from_utf8(s)
+        .ok()
+        .and_then(|s| s.parse::<usize>().ok())
+        .ok_or_else(|| protocol_error("invalid length"))?;
+    if len > max {
+        return Err(protocol_error("length exceeds limit"));
+    }
+    Ok(len)
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Protocol error: {}", msg),
+    )
+}
+
+fn unexpected_eof() -> io::Error {
+    io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "connection closed mid command",
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    #[test]
+    fn test_read_command() {
+        let mut reader = Cursor::new(b"*2\r\n$3\r\nGET\r\n$4\r\nname\r\nPING hello\r\n".to_vec());
+        let cmd1 = read_command(&mut reader).unwrap().unwrap();
+        assert_eq!(cmd1, vec![Bytes::from("GET"), Bytes::from("name")]);
+        let cmd2 = read_command(&mut reader).unwrap().unwrap();
+        assert_eq!(cmd2, vec![Bytes::from("PING"), Bytes::from("hello")]);
+        assert!(read_command(&mut reader).unwrap().is_none());
+
+        // 二进制安全
+        let mut reader = Cursor::new(b"*1\r\n$4\r\na\r\nb\r\n".to_vec());
+        let cmd3 = read_command(&mut reader).unwrap().unwrap();
+        assert_eq!(cmd3, vec![Bytes::from("a\r\nb")]);
+
+        let mut reader = Cursor::new(b"*1\r\n$x\r\n".to_vec());
+        assert!(read_command(&mut reader).is_err());
+        let mut reader = Cursor::new(b"*2\r\n$3\r\nGET\r\n".to_vec());
+        assert!(read_command(&mut reader).is_err());
+
+        // 声明的长度超过实际数据时不会按声明的长度分配内存
+        let mut reader = Cursor::new(b"*1048576\r\n$67108864\r\nabc".to_vec());
+        let err = read_command(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+        let mut reader = Cursor::new(b"*1\r\n$67108865\r\n".to_vec());
+        let err = read_command(&mut reader).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_encode() {
+        let value = RespValue::Array(vec![
+            RespValue::ok(),
+            RespValue::Integer(-3),
+            RespValue::bulk("fdb"),
+            RespValue::Null,
+            RespValue::error("ERR boom"),
+        ]);
+        let mut buf2 = Vec::new();
+        value.encode(2, &mut buf2);
+        assert_eq!(
+            buf2,
+            b"*5\r\n+OK\r\n:-3\r\n$3\r\nfdb\r\n$-1\r\n-ERR boom\r\n"
+        );
+        let mut buf3 = Vec::new();
+        value.encode(3, &mut buf3);
+        assert_eq!(buf3, b"*5\r\n+OK\r\n:-3\r\n$3\r\nfdb\r\n_\r\n-ERR boom\r\n");
+
+        let map = RespValue::Map(vec![(RespValue::bulk("k"), RespValue::Integer(1))]);
+        let mut buf4 = Vec::new();
+        map.encode(2, &mut buf4);
+        assert_eq!(buf4, b"*2\r\n$1\r\nk\r\n:1\r\n");
+        let mut buf5 = Vec::new();
+        map.encode(3, &mut buf5);
+        assert_eq!(buf5, b"%1\r\n$1\r\nk\r\n:1\r\n");
+    }
+}
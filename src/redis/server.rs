@@ -0,0 +1,596 @@
+use crate::db::Engine;
+use crate::errors::Errors;
+use crate::glob::glob_match;
+use crate::redis::resp::{read_command, write_value, RespValue};
+use bytes::Bytes;
+use log::{error, info, warn};
+use std::collections::VecDeque;
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+
+// SCAN 默认每次返回的key数量
+const DEFAULT_SCAN_COUNT: usize = 10;
+// 每个连接保留的 SCAN 游标数量，超过之后淘汰最早的游标
+const MAX_SCAN_CURSORS: usize = 16;
+// KEYS 每次从索引中取出的key数量
+const KEYS_BATCH_SIZE: usize = 1024;
+
+/// 兼容 Redis 协议的服务端，每个连接使用一个线程处理
+pub struct RedisServer {
+    engine: Arc<Engine>,
+    listener: TcpListener,
+}
+
+// 每个连接的会话状态
+struct Session {
+    protocol: u8, // RESP 协议版本，通过 HELLO 命令切换
+    quit: bool,
+    // SCAN 游标以及对应的上一批返回的最后一个key
+    scan_cursors: VecDeque<(u64, Bytes)>,
+    next_cursor: u64,
+}
+
+impl RedisServer {
+    pub fn bind<A: ToSocketAddrs>(addr: A, engine: Arc<Engine>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self { engine, listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 循环接收客户端连接，直到监听出错
+    pub fn serve(&self) -> io::Result<()> {
+        info!("redis server listening on {}", self.local_addr()?);
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let engine = self.engine.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_connection(engine, stream) {
+                    warn!("redis connection {:?} closed with error: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_connection(engine: Arc<Engine>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let mut session = Session {
+        protocol: 2,
+        quit: false,
+        scan_cursors: VecDeque::new(),
+        next_cursor: 1,
+    };
+
+    while let Some(args) = read_command(&mut reader)? {
+        let reply = execute(&engine, &mut session, &args);
+        write_value(&mut writer, &reply, session.protocol)?;
+        // 没有后续的流水线命令时再刷新，减少系统调用
+        if reader.buffer().is_empty() || session.quit {
+            writer.flush()?;
+        }
+        if session.quit {
+            break;
+        }
+    }
+    writer.flush()
+}
+
+// 执行一条命令，返回回复内容
+fn execute(engine: &Engine, session: &mut Session, args: &[Bytes]) -> RespValue {
+    let name = String::from_utf8_lossy(&args[0]).to_ascii_uppercase();
+    let args = &args[1..];
+    let res = match name.as_str() {
+        "PING" => ping(args),
+        "ECHO" => arity(&name, args, 1, 1).map(|_| RespValue::BulkString(args[0].clone())),
+        "GET" => arity(&name, args, 1, 1).and_then(|_| get(engine, &args[0])),
+        "SET" => set(engine, args),
+        "DEL" => arity(&name, args, 1, usize::MAX).and_then(|_| del(engine, args)),
+        "EXISTS" => arity(&name, args, 1, usize::MAX).and_then(|_| exists(engine, args)),
+        "MGET" => arity(&name, args, 1, usize::MAX).and_then(|_| mget(engine, args)),
+        "MSET" => mset(engine, args),
+        "KEYS" => arity(&name, args, 1, 1).and_then(|_| keys(engine, &args[0])),
+        "SCAN" => scan(engine, session, args),
+        "DBSIZE" => arity(&name, args, 0, 0).and_then(|_| dbsize(engine)),
+        "INFO" => Ok(info(engine)),
+        "HELLO" => hello(session, args),
+        "SELECT" => arity(&name, args, 1, 1).and_then(|_| select(&args[0])),
+        // 客户端连接时发送的命令，直接返回成功
+        "COMMAND" => Ok(RespValue::Array(vec![])),
+        "CLIENT" => Ok(RespValue::ok()),
+        "QUIT" => {
+            session.quit = true;
+            Ok(RespValue::ok())
+        }
+        _ => Err(format!(
+            "ERR unknown command '{}'",
+            name.to_ascii_lowercase()
+        )),
+    };
+    res.unwrap_or_else(RespValue::Error)
+}
+
+type CmdResult = std::result::Result<RespValue, String>;
+
+// 校验参数个数
+fn arity(name: &str, args: &[Bytes], min: usize, max: usize) -> std::result::Result<(), String> {
+    if args.len() < min || args.len() > max {
+        return Err(wrong_args(name));
+    }
+    Ok(())
+}
+
+fn wrong_args(name: &str) -> String {
+    format!(
+        "ERR wrong number of arguments for '{}' command",
+        name.to_ascii_lowercase()
+    )
+}
+
+fn engine_error(e: Errors) -> String {
+    error!("redis command failed: {}", e);
+    format!("ERR {}", e)
+}
+
+fn ping(args: &[Bytes]) -> CmdResult {
+    match args.len() {
+        0 => Ok(RespValue::SimpleString("PONG".to_string())),
+        1 => Ok(RespValue::BulkString(args[0].clone())),
+        _ => Err(wrong_args("PING")),
+    }
+}
+
+// 读取key，不存在时返回 None
+fn get_value(engine: &Engine, key: &Bytes) -> std::result::Result<Option<Bytes>, String> {
+    match engine.get(key.clone()) {
+        Ok(value) => Ok(Some(value)),
+        Err(Errors::KeyNotFound) => Ok(None),
+        Err(e) => Err(engine_error(e)),
+    }
+}
+
+fn get(engine: &Engine, key: &Bytes) -> CmdResult {
+    match get_value(engine, key)? {
+        Some(value) => Ok(RespValue::BulkString(value)),
+        None => Ok(RespValue::Null),
+    }
+}
+
+// SET key value [NX | XX] [GET]
+fn set(engine: &Engine, args: &[Bytes]) -> CmdResult {
+    if args.len() < 2 {
+        return Err(wrong_args("SET"));
+    }
+    let (key, value) = (&args[0], &args[1]);
+    let (mut nx, mut xx, mut get_old) = (false, false, false);
+    for opt in &args[2..] {
+        match opt.to_ascii_uppercase().as_slice() {
+            b"NX" => nx = true,
+            b"XX" => xx = true,
+            b"GET" => get_old = true,
+            b"EX" | b"PX" | b"EXAT" | b"PXAT" | b"KEEPTTL" => {
+                return Err("ERR key expiration is not supported".to_string())
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+    if nx && xx {
+        return Err("ERR syntax error".to_string());
+    }
+
+    if !nx && !xx && !get_old {
+        engine
+            .put(key.clone(), value.clone())
+            .map_err(engine_error)?;
+        return Ok(RespValue::ok());
+    }
+    if engine.options().read_only {
+        return Err(engine_error(Errors::DatabaseIsReadOnly));
+    }
+
+    // 读取旧值和写入在写锁内完成，与其他写入之间不会交错
+    let _guard = engine.lock_writes();
+    let old = get_value(engine, key)?;
+    let skip = (nx && old.is_some()) || (xx && old.is_none());
+    if !skip {
+        engine.put_value(key, value).map_err(engine_error)?;
+    }
+
+    match (get_old, skip) {
+        (true, _) => Ok(old.map(RespValue::BulkString).unwrap_or(RespValue::Null)),
+        (false, true) => Ok(RespValue::Null),
+        (false, false) => Ok(RespValue::ok()),
+    }
+}
+
+// 判断key是否存在，不读取value
+fn key_exists(engine: &Engine, key: &Bytes) -> std::result::Result<bool, String> {
+    engine.exists(key.clone()).map_err(engine_error)
+}
+
+fn del(engine: &Engine, keys: &[Bytes]) -> CmdResult {
+    if engine.options().read_only {
+        return Err(engine_error(Errors::DatabaseIsReadOnly));
+    }
+    // 并发删除同一个key时只有一个计数
+    let _guard = engine.lock_writes();
+    let mut count = 0;
+    for key in keys {
+        if key_exists(engine, key)? {
+            engine.delete_value(key).map_err(engine_error)?;
+            count += 1;
+        }
+    }
+    Ok(RespValue::Integer(count))
+}
+
+fn exists(engine: &Engine, keys: &[Bytes]) -> CmdResult {
+    let mut count = 0;
+    for key in keys {
+        if key_exists(engine, key)? {
+            count += 1;
+        }
+    }
+    Ok(RespValue::Integer(count))
+}
+
+fn mget(engine: &Engine, keys: &[Bytes]) -> CmdResult {
+    let mut values = Vec::with_capacity(keys.len());
+    for key in keys {
+        values.push(match get_value(engine, key)? {
+            Some(value) => RespValue::BulkString(value),
+            None => RespValue::Null,
+        });
+    }
+    Ok(RespValue::Array(values))
+}
+
+fn mset(engine: &Engine, args: &[Bytes]) -> CmdResult {
+    if args.is_empty() || !args.len().is_multiple_of(2) {
+        return Err(wrong_args("MSET"));
+    }
+    for pair in args.chunks(2) {
+        engine
+            .put(pair[0].clone(), pair[1].clone())
+            .map_err(engine_error)?;
+    }
+    Ok(RespValue::ok())
+}
+
+fn keys(engine: &Engine, pattern: &Bytes) -> CmdResult {
+    // 分批从索引中取出key，不一次性拷贝所有的key
+    let mut matched = Vec::new();
+    let mut last: Option<Bytes> = None;
+    loop {
+        let keys = engine
+            .list_keys_after(last.as_deref(), KEYS_BATCH_SIZE)
+            .map_err(engine_error)?;
+        let done = keys.len() < KEYS_BATCH_SIZE;
+        last = keys.last().cloned();
+        matched.extend(
+            keys.into_iter()
+                .filter(|key| glob_match(pattern, key))
+                .map(RespValue::BulkString),
+        );
+        if done {
+            break;
+        }
+    }
+    Ok(RespValue::Array(matched))
+}
+
+// SCAN cursor [MATCH pattern] [COUNT count]
+// 游标对应上一批返回的最后一个key，保存在连接中，下一批从这个key之后继续遍历，
+// 遍历期间没有被删除的key都会被返回且只返回一次
+fn scan(engine: &Engine, session: &mut Session, args: &[Bytes]) -> CmdResult {
+    if args.len().is_multiple_of(2) {
+        return Err(wrong_args("SCAN"));
+    }
+    let cursor = parse_number::<u64>(&args[0], "ERR invalid cursor")?;
+    let start = match cursor {
+        0 => None,
+        cursor => match session.scan_cursors.iter().find(|(c, _)| *c == cursor) {
+            Some((_, key)) => Some(key.clone()),
+            None => return Err("ERR invalid cursor".to_string()),
+        },
+    };
+    let mut pattern: Option<&Bytes> = None;
+    let mut count = DEFAULT_SCAN_COUNT;
+    for opt in args[1..].chunks(2) {
+        match opt[0].to_ascii_uppercase().as_slice() {
+            b"MATCH" => pattern = Some(&opt[1]),
+            b"COUNT" => {
+                count =
+                    parse_number::<usize>(&opt[1], "ERR value is not an integer or out of range")?;
+                if count == 0 {
+                    return Err("ERR syntax error".to_string());
+                }
+            }
+            _ => return Err("ERR syntax error".to_string()),
+        }
+    }
+
+    let keys = engine
+        .list_keys_after(start.as_deref(), count)
+        .map_err(engine_error)?;
+    let next_cursor = match keys.last() {
+        Some(last) if keys.len() == count => {
+            let cursor = session.next_cursor;
+            session.next_cursor += 1;
+            if session.scan_cursors.len() >= MAX_SCAN_CURSORS {
+                session.scan_cursors.pop_front();
+            }
+            session.scan_cursors.push_back((cursor, last.clone()));
+            cursor
+        }
+        _ => 0,
+    };
+    let page: Vec<RespValue> = keys
+        .into_iter()
+        .filter(|key| pattern.is_none_or(|p| glob_match(p, key)))
+        .map(RespValue::BulkString)
+        .collect();
+
+    Ok(RespValue::Array(vec![
+        RespValue::bulk(next_cursor.to_string()),
+        RespValue::Array(page),
+    ]))
+}
+
+fn dbsize(engine: &Engine) -> CmdResult {
+    Ok(RespValue::Integer(engine.key_count() as i64))
+}
+
+fn info(engine: &Engine) -> RespValue {
+    let key_count = engine.key_count();
+    let mut info = String::new();
+    info.push_str("# Server\r\n");
+    info.push_str(&format!("fdb_version:{}\r\n", env!("CARGO_PKG_VERSION")));
+    info.push_str("redis_version:7.0.0\r\n");
+    info.push_str("\r\n# Keyspace\r\n");
+    info.push_str(&format!("db0:keys={},expires=0\r\n", key_count));
+    if let Some(stats) = engine.cache_stats() {
+        info.push_str("\r\n# Cache\r\n");
+        info.push_str(&format!("cache_hits:{}\r\n", stats.hits));
+        info.push_str(&format!("cache_misses:{}\r\n", stats.misses));
+        info.push_str(&format!("cache_entries:{}\r\n", stats.entries));
+        info.push_str(&format!("cache_bytes:{}\r\n", stats.size));
+    }
+    RespValue::bulk(info)
+}
+
+// HELLO [protover [AUTH username password] [SETNAME clientname]]
+fn hello(session: &mut Session, args: &[Bytes]) -> CmdResult {
+    if let Some(version) = args.first() {
+        let version = parse_number::<u8>(
+            version,
+            "ERR Protocol version is not an integer or out of range",
+        )?;
+        if version != 2 && version != 3 {
+            return Err("NOPROTO unsupported protocol version".to_string());
+        }
+        session.protocol = version;
+    }
+    Ok(RespValue::Map(vec![
+        (RespValue::bulk("server"), RespValue::bulk("fdb")),
+        (
+            RespValue::bulk("version"),
+            RespValue::bulk(env!("CARGO_PKG_VERSION")),
+        ),
+        (
+            RespValue::bulk("proto"),
+            RespValue::Integer(session.protocol as i64),
+        ),
+        (RespValue::bulk("mode"), RespValue::bulk("standalone")),
+        (RespValue::bulk("role"), RespValue::bulk("master")),
+        (RespValue::bulk("modules"), RespValue::Array(vec![])),
+    ]))
+}
+
+fn select(db: &Bytes) -> CmdResult {
+    match db.as_ref() {
+        b"0" => Ok(RespValue::ok()),
+        _ => Err("ERR DB index is out of range".to_string()),
+    }
+}
+
+fn parse_number<T: std::str::FromStr>(arg: &Bytes, err: &str) -> std::result::Result<T, String> {
+    std::str::from_utf8(arg)
+        .ok()
+        .and_then(|s| s.parse::<T>().ok())
+        .ok_or_else(|| err.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use redis::Commands;
+    use std::fs;
+    use std::io::{BufRead, Read};
+
+    fn start_server(name: &str) -> (SocketAddr, std::path::PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let server = RedisServer::bind("127.0.0.1:0", Arc::new(engine)).unwrap();
+        let addr = server.local_addr().unwrap();
+        std::thread::spawn(move || server.serve());
+        (addr, dir_path)
+    }
+
+    #[test]
+    fn test_redis_server_with_client() {
+        let (addr, dir_path) = start_server("fdb-redis-client");
+        let client = redis::Client::open(format!("redis://{}/", addr)).unwrap();
+        let mut conn = client.get_connection().unwrap();
+
+        let pong: String = redis::cmd("PING").query(&mut conn).unwrap();
+        assert_eq!(pong, "PONG");
+
+        let _: () = conn.set("name", "fdb").unwrap();
+        let name: String = conn.get("name").unwrap();
+        assert_eq!(name, "fdb");
+        let missing: Option<String> = conn.get("missing").unwrap();
+        assert!(missing.is_none());
+
+        let _: () = conn.mset(&[("k1", "v1"), ("k2", "v2")]).unwrap();
+        let values: Vec<Option<String>> = conn.mget(&["k1", "missing", "k2"]).unwrap();
+        assert_eq!(
+            values,
+            vec![Some("v1".to_string()), None, Some("v2".to_string())]
+        );
+
+        let exists: i64 = conn.exists(&["k1", "k2", "missing"]).unwrap();
+        assert_eq!(exists, 2);
+        let mut keys: Vec<String> = conn.keys("k*").unwrap();
+        keys.sort();
+        assert_eq!(keys, vec!["k1", "k2"]);
+
+        let deleted: i64 = conn.del(&["k1", "missing"]).unwrap();
+        assert_eq!(deleted, 1);
+        let exists: bool = conn.exists("k1").unwrap();
+        assert!(!exists);
+
+        // SCAN 分页遍历全部key
+        for i in 0..25 {
+            let _: () = conn.set(format!("scan:{:02}", i), i).unwrap();
+        }
+        let mut scanned: Vec<String> = conn.scan_match("scan:*").unwrap().collect();
+        scanned.sort();
+        assert_eq!(scanned.len(), 25);
+        assert_eq!(scanned[0], "scan:00");
+
+        let info: String = redis::cmd("INFO").query(&mut conn).unwrap();
+        assert!(info.contains("db0:keys=27"));
+
+        // 遍历期间删除已经返回的key，不会导致后面的key被跳过
+        let scan = |conn: &mut redis::Connection, cursor: u64| -> (u64, Vec<String>) {
+            redis::cmd("SCAN")
+                .arg(cursor)
+                .arg("MATCH")
+                .arg("scan:*")
+                .arg("COUNT")
+                .arg(10)
+                .query(conn)
+                .unwrap()
+        };
+        let (mut cursor, mut scanned) = scan(&mut conn, 0);
+        let _: i64 = conn.del(&scanned).unwrap();
+        while cursor != 0 {
+            let (next, keys) = scan(&mut conn, cursor);
+            scanned.extend(keys);
+            cursor = next;
+        }
+        assert_eq!(scanned.len(), 25);
+        assert_eq!(scanned[24], "scan:24");
+        let res: redis::RedisResult<(u64, Vec<String>)> =
+            redis::cmd("SCAN").arg(12345).query(&mut conn);
+        assert!(res.is_err());
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_redis_server_set_nx_concurrent() {
+        let (addr, dir_path) = start_server("fdb-redis-set-nx");
+        let client = redis::Client::open(format!("redis://{}/", addr)).unwrap();
+
+        // 并发的 SET NX 只有一个写入成功
+        for round in 0..10 {
+            let key = format!("lock:{}", round);
+            let handles: Vec<_> = (0..8)
+                .map(|i| {
+                    let mut conn = client.get_connection().unwrap();
+                    let key = key.clone();
+                    std::thread::spawn(move || {
+                        let res: Option<String> = redis::cmd("SET")
+                            .arg(&key)
+                            .arg(i)
+                            .arg("NX")
+                            .query(&mut conn)
+                            .unwrap();
+                        res.is_some()
+                    })
+                })
+                .collect();
+            let written = handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .filter(|ok| *ok)
+                .count();
+            assert_eq!(written, 1);
+        }
+
+        let mut conn = client.get_connection().unwrap();
+        let res: Option<String> = redis::cmd("SET")
+            .arg("missing")
+            .arg("v")
+            .arg("XX")
+            .query(&mut conn)
+            .unwrap();
+        assert_eq!(res, None);
+        let exists: i64 = conn.exists(&["lock:0", "lock:1", "missing"]).unwrap();
+        assert_eq!(exists, 2);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_redis_server_resp3() {
+        let (addr, dir_path) = start_server("fdb-redis-resp3");
+        let stream = TcpStream::connect(addr).unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut writer = stream;
+
+        // RESP2 下不存在的key返回 null bulk string
+        writer.write_all(b"*2\r\n$3\r\nGET\r\n$1\r\nx\r\n").unwrap();
+        let mut line = String::new();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "$-1\r\n");
+
+        // 切换到 RESP3，HELLO 返回 map
+        writer.write_all(b"HELLO 3\r\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "%6\r\n");
+        let mut rest = vec![0u8; 4096];
+        let _ = reader.read(&mut rest).unwrap();
+
+        writer
+            .write_all(b"GET x\r\nSET x 1 NX\r\nSET x 2 NX\r\nFOO\r\n")
+            .unwrap();
+        let mut replies = Vec::new();
+        for _ in 0..4 {
+            line.clear();
+            reader.read_line(&mut line).unwrap();
+            replies.push(line.clone());
+        }
+        assert_eq!(
+            replies,
+            vec![
+                "_\r\n",
+                "+OK\r\n",
+                "_\r\n",
+                "-ERR unknown command 'foo'\r\n"
+            ]
+        );
+
+        writer.write_all(b"HELLO 4\r\nQUIT\r\n").unwrap();
+        line.clear();
+        reader.read_line(&mut line).unwrap();
+        assert_eq!(line, "-NOPROTO unsupported protocol version\r\n");
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,382 @@
+use crate::cdc::ChangeTarget;
+use crate::column_family::decode_cf_meta;
+use crate::data::log_record::{LogRecordPos, LogRecordType};
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::replication::protocol::{read_frame, write_handshake, Frame};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use log::{info, warn};
+use parking_lot::Mutex;
+use std::fs;
+use std::io::{self, BufReader};
+use std::net::{Shutdown, SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+// 已复制位置的文件名，保存在 follower 的数据目录中
+pub const REPLICATION_POS_FILE_NAME: &str = "replication-pos";
+// 连接断开之后的重连间隔
+const RECONNECT_INTERVAL: Duration = Duration::from_millis(200);
+// 超过该时间没有收到任何数据（包括心跳）时认为 leader 已失效
+const READ_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// 复制的 follower 端
+///
+/// 从 leader 接收日志记录并写入本地的存储引擎，每批数据应用并 sync 之后，
+/// 将 leader 端的位置保存到数据目录中，断开重连或者重启之后从该位置继续复制。
+/// 重复应用同一条记录是幂等的，因此崩溃时最多重新复制最后一批数据。
+pub struct Follower {
+    engine: Arc<Engine>,
+    leader: SocketAddr,
+    pos_path: PathBuf,
+    pos: Mutex<LogRecordPos>,
+    stopped: AtomicBool,
+    // 当前的连接，用于 stop 时中断阻塞的读取
+    stream: Mutex<Option<TcpStream>>,
+}
+
+impl Follower {
+    /// 创建 follower，从数据目录中读取上次复制到的位置
+    pub fn new<A: ToSocketAddrs>(engine: Arc<Engine>, leader: A) -> io::Result<Self> {
+        let leader = leader
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::other("invalid leader address"))?;
+        let pos_path = engine.options().dir_path.join(REPLICATION_POS_FILE_NAME);
+        let pos = load_position(&pos_path)?;
+        Ok(Self {
+            engine,
+            leader,
+            pos_path,
+            pos: Mutex::new(pos),
+            stopped: AtomicBool::new(false),
+            stream: Mutex::new(None),
+        })
+    }
+
+    /// 已应用并持久化的 leader 端位置
+    pub fn position(&self) -> LogRecordPos {
+        *self.pos.lock()
+    }
+
+    /// 持续从 leader 复制数据，连接断开时自动重连，直到调用 stop
+    pub fn run(&self) {
+        while !self.stopped.load(Ordering::SeqCst) {
+            if let Err(e) = self.replicate() {
+                if self.stopped.load(Ordering::SeqCst) {
+                    break;
+                }
+                warn!("replication from {} interrupted: {}", self.leader, e);
+                std::thread::sleep(RECONNECT_INTERVAL);
+            }
+        }
+    }
+
+    /// 停止复制，正在进行的 run 会尽快返回
+    pub fn stop(&self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        if let Some(stream) = self.stream.lock().take() {
+            let _ = stream.shutdown(Shutdown::Both);
+        }
+    }
+
+    fn replicate(&self) -> io::Result<()> {
+        let stream = TcpStream::connect(self.leader)?;
+        stream.set_read_timeout(Some(READ_TIMEOUT))?;
+        *self.stream.lock() = Some(stream.try_clone()?);
+        // 发布连接之前可能已经调用了 stop
+        if self.stopped.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let pos = self.position();
+        info!(
+            "replicating from {} at file {} offset {}",
+            self.leader, pos.file_id, pos.offset
+        );
+        write_handshake(&mut &stream, pos)?;
+        let mut reader = BufReader::new(stream);
+        let mut applied = None;
+        loop {
+            if let Frame::Record {
+                next,
+                target,
+                rec_type,
+                key,
+                value,
+                expire,
+            } = read_frame(&mut reader)?
+            {
+                self.apply(target, rec_type, key, value, expire)
+                    .map_err(io::Error::other)?;
+                applied = Some(next);
+            }
+            // 一批数据应用完之后再持久化位置
+            if reader.buffer().is_empty() {
+                if let Some(pos) = applied.take() {
+                    self.save_position(pos)?;
+                }
+            }
+        }
+    }
+
+    // leader 发送的 value 都已经从 blob 文件中读出，合并操作数也已经合并为完整的值，
+    // 过期时间使用 leader 记录中的值，不按照本地列族的默认过期时间重新计算
+    fn apply(
+        &self,
+        target: ChangeTarget,
+        rec_type: LogRecordType,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        expire: u64,
+    ) -> Result<()> {
+        let (key, value) = (Bytes::from(key), Bytes::from(value));
+        let is_delete = rec_type == LogRecordType::DELETE;
+        match target {
+            ChangeTarget::Default if is_delete => self.engine.delete(key),
+            ChangeTarget::Default => self.engine.put_with_expire(key, value, expire),
+            // 重新复制时列族可能已经被之后的删除记录删除，这些记录可以忽略
+            ChangeTarget::ColumnFamily(name) => match self.engine.cf(&name) {
+                Ok(cf) if is_delete => cf.delete(key),
+                Ok(cf) => cf.put_with_expire(key, value, expire),
+                Err(Errors::ColumnFamilyNotFound) => Ok(()),
+                Err(e) => Err(e),
+            },
+            ChangeTarget::Catalog => {
+                let name = String::from_utf8(key.to_vec())
+                    .map_err(|_| Errors::InvalidColumnFamilyRecord)?;
+                let res = match is_delete {
+                    true => self.engine.drop_cf(&name),
+                    false => self.engine.create_cf(&name, decode_cf_meta(&value)?.1),
+                };
+                // 重复应用创建和删除是幂等的
+                match res {
+                    Err(Errors::ColumnFamilyExists) | Err(Errors::ColumnFamilyNotFound) => Ok(()),
+                    res => res,
+                }
+            }
+        }
+    }
+
+    // 先 sync 数据再保存位置，保证位置之前的数据都已持久化
+    fn save_position(&self, pos: LogRecordPos) -> io::Result<()> {
+        self.engine.sync().map_err(io::Error::other)?;
+        let mut buf = BytesMut::with_capacity(12);
+        buf.put_u32(pos.file_id);
+        buf.put_u64(pos.offset);
+        // 先写临时文件再重命名，避免写了一半的位置文件
+        let tmp_path = self.pos_path.with_extension("tmp");
+        fs::write(&tmp_path, &buf)?;
+        fs::File::open(&tmp_path)?.sync_all()?;
+        fs::rename(&tmp_path, &self.pos_path)?;
+        *self.pos.lock() = pos;
+        Ok(())
+    }
+}
+
+fn load_position(path: &PathBuf) -> io::Result<LogRecordPos> {
+    let buf = match fs::read(path) {
+        Ok(buf) => buf,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(LogRecordPos::default()),
+        Err(e) => return Err(e),
+    };
+    if buf.len() != 12 {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "replication position file corrupted",
+        ));
+    }
+    let mut buf = &buf[..];
+    Ok(LogRecordPos::new(buf.get_u32(), buf.get_u64()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::{ColumnFamilyOptions, Options};
+    use crate::replication::leader::ReplicationLeader;
+    use std::thread::JoinHandle;
+    use std::time::Instant;
+
+    fn open_engine(name: &str, data_file_size: u64) -> (Arc<Engine>, PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            data_file_size,
+            ..Default::default()
+        })
+        .unwrap();
+        (Arc::new(engine), dir_path)
+    }
+
+    // 等待 follower 追上 leader 当前的写入位置
+    fn wait_caught_up(follower: &Follower, leader: &Engine) {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while follower.position() != leader.write_pos() {
+            assert!(Instant::now() < deadline, "follower did not catch up");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+
+    fn start_follower(engine: Arc<Engine>, addr: SocketAddr) -> (Arc<Follower>, JoinHandle<()>) {
+        let follower = Arc::new(Follower::new(engine, addr).unwrap());
+        let runner = follower.clone();
+        let handle = std::thread::spawn(move || runner.run());
+        (follower, handle)
+    }
+
+    #[test]
+    fn test_replication_resume() {
+        let (leader_engine, leader_dir) = open_engine("fdb-replication-leader-e2e", 256);
+        let (follower_engine, follower_dir) = open_engine("fdb-replication-follower", 1024);
+        for i in 0..30 {
+            let key = Bytes::from(format!("key-{:02}", i));
+            leader_engine.put(key, Bytes::from("value")).unwrap();
+        }
+        leader_engine.delete(Bytes::from("key-00")).unwrap();
+        // 数据跨越了多个数据文件
+        assert!(leader_engine.write_pos().file_id > 0);
+
+        let leader = ReplicationLeader::bind("127.0.0.1:0", leader_engine.clone()).unwrap();
+        let addr = leader.local_addr().unwrap();
+        std::thread::spawn(move || leader.serve());
+
+        let (follower, handle) = start_follower(follower_engine.clone(), addr);
+        wait_caught_up(&follower, &leader_engine);
+        assert_eq!(follower_engine.list_keys().unwrap().len(), 29);
+        assert!(follower_engine.get(Bytes::from("key-00")).is_err());
+        assert_eq!(
+            follower_engine.get(Bytes::from("key-29")).unwrap(),
+            Bytes::from("value")
+        );
+
+        // follower 停止并重启之后，从保存的位置继续复制
+        follower.stop();
+        handle.join().unwrap();
+        let stopped_pos = follower.position();
+        drop(follower);
+        drop(follower_engine);
+        leader_engine
+            .put(Bytes::from("key-30"), Bytes::from("value"))
+            .unwrap();
+        leader_engine.delete(Bytes::from("key-01")).unwrap();
+
+        let follower_engine = Arc::new(
+            Engine::open(Options {
+                dir_path: follower_dir.clone(),
+                data_file_size: 1024,
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        let (follower, handle) = start_follower(follower_engine.clone(), addr);
+        assert_eq!(follower.position(), stopped_pos);
+        wait_caught_up(&follower, &leader_engine);
+        assert_eq!(follower_engine.list_keys().unwrap().len(), 29);
+        assert!(follower_engine.get(Bytes::from("key-01")).is_err());
+        assert_eq!(
+            follower_engine.get(Bytes::from("key-30")).unwrap(),
+            Bytes::from("value")
+        );
+
+        // 之后的写入实时复制
+        leader_engine
+            .put(Bytes::from("key-31"), Bytes::from("live"))
+            .unwrap();
+        wait_caught_up(&follower, &leader_engine);
+        assert_eq!(
+            follower_engine.get(Bytes::from("key-31")).unwrap(),
+            Bytes::from("live")
+        );
+
+        follower.stop();
+        handle.join().unwrap();
+        fs::remove_dir_all(leader_dir).unwrap();
+        fs::remove_dir_all(follower_dir).unwrap();
+    }
+
+    #[test]
+    fn test_replication_column_family() {
+        let (leader_engine, leader_dir) = open_engine("fdb-replication-cf-leader", 1024);
+        let (follower_engine, follower_dir) = open_engine("fdb-replication-cf-follower", 1024);
+        leader_engine
+            .create_cf("users", ColumnFamilyOptions::default())
+            .unwrap();
+        leader_engine
+            .create_cf("tmp", ColumnFamilyOptions::default())
+            .unwrap();
+        leader_engine
+            .create_cf(
+                "sessions",
+                ColumnFamilyOptions {
+                    default_ttl: Some(Duration::from_millis(200)),
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+        let sessions = leader_engine.cf("sessions").unwrap();
+        sessions.put(Bytes::from("s"), Bytes::from("v")).unwrap();
+        let users = leader_engine.cf("users").unwrap();
+        users.put(Bytes::from("k"), Bytes::from("alice")).unwrap();
+        users.put(Bytes::from("gone"), Bytes::from("v")).unwrap();
+        users.delete(Bytes::from("gone")).unwrap();
+        let tmp = leader_engine.cf("tmp").unwrap();
+        tmp.put(Bytes::from("k"), Bytes::from("v")).unwrap();
+        leader_engine.drop_cf("tmp").unwrap();
+        leader_engine
+            .put(Bytes::from("k"), Bytes::from("default"))
+            .unwrap();
+
+        let leader = ReplicationLeader::bind("127.0.0.1:0", leader_engine.clone()).unwrap();
+        let addr = leader.local_addr().unwrap();
+        std::thread::spawn(move || leader.serve());
+        // 复制时使用 leader 记录中的过期时间，在 leader 上已经过期的 key 在 follower 上也已经过期
+        std::thread::sleep(Duration::from_millis(300));
+        let (follower, handle) = start_follower(follower_engine.clone(), addr);
+        wait_caught_up(&follower, &leader_engine);
+
+        let sessions = follower_engine.cf("sessions").unwrap();
+        assert_eq!(sessions.get(Bytes::from("s")), Err(Errors::KeyNotFound));
+        assert_eq!(follower_engine.list_cfs(), vec!["sessions", "users"]);
+        let users = follower_engine.cf("users").unwrap();
+        assert_eq!(users.get(Bytes::from("k")).unwrap(), "alice");
+        assert_eq!(users.get(Bytes::from("gone")), Err(Errors::KeyNotFound));
+        assert_eq!(follower_engine.get(Bytes::from("k")).unwrap(), "default");
+
+        // 重复应用已经复制过的记录不影响结果
+        follower.stop();
+        handle.join().unwrap();
+        for (target, rec_type, key, value) in [
+            (
+                ChangeTarget::Catalog,
+                LogRecordType::NORMAL,
+                "users",
+                vec![0u8; 13],
+            ),
+            (
+                ChangeTarget::ColumnFamily("tmp".to_string()),
+                LogRecordType::NORMAL,
+                "k",
+                b"v".to_vec(),
+            ),
+            (
+                ChangeTarget::Catalog,
+                LogRecordType::DELETE,
+                "tmp",
+                Vec::new(),
+            ),
+        ] {
+            follower
+                .apply(target, rec_type, key.as_bytes().to_vec(), value, 0)
+                .unwrap();
+        }
+        assert_eq!(follower_engine.list_cfs(), vec!["sessions", "users"]);
+        assert_eq!(users.get(Bytes::from("k")).unwrap(), "alice");
+
+        fs::remove_dir_all(leader_dir).unwrap();
+        fs::remove_dir_all(follower_dir).unwrap();
+    }
+}
@@ -0,0 +1,149 @@
+use crate::data::log_record::LogRecordType;
+use crate::db::Engine;
+use crate::replication::protocol::{read_handshake, write_frame, Frame, HEARTBEAT_INTERVAL};
+use log::{info, warn};
+use std::io::{self, BufReader, BufWriter, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 复制的 leader 端，每个 follower 连接使用一个线程
+///
+/// follower 连接后发送开始复制的位置，leader 从该位置开始按顺序读取数据文件，
+/// 读到最新位置之后等待新的写入，空闲时定期发送心跳。
+pub struct ReplicationLeader {
+    engine: Arc<Engine>,
+    listener: TcpListener,
+}
+
+impl ReplicationLeader {
+    pub fn bind<A: ToSocketAddrs>(addr: A, engine: Arc<Engine>) -> io::Result<Self> {
+        let listener = TcpListener::bind(addr)?;
+        Ok(Self { engine, listener })
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.listener.local_addr()
+    }
+
+    /// 循环接收 follower 连接，直到监听出错
+    pub fn serve(&self) -> io::Result<()> {
+        info!("replication leader listening on {}", self.local_addr()?);
+        for stream in self.listener.incoming() {
+            let stream = stream?;
+            let engine = self.engine.clone();
+            std::thread::spawn(move || {
+                let peer = stream.peer_addr().ok();
+                if let Err(e) = handle_follower(engine, stream) {
+                    warn!("replication follower {:?} disconnected: {}", peer, e);
+                }
+            });
+        }
+        Ok(())
+    }
+}
+
+fn handle_follower(engine: Arc<Engine>, stream: TcpStream) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = BufWriter::new(stream);
+    let pos = read_handshake(&mut reader)?;
+    info!(
+        "replication follower {:?} starts from file {} offset {}",
+        writer.get_ref().peer_addr().ok(),
+        pos.file_id,
+        pos.offset
+    );
+
+    let mut sub = engine.subscribe_from(pos).map_err(io::Error::other)?;
+    loop {
+        let event = match sub.next_timeout(Duration::ZERO) {
+            Ok(Some(event)) => event,
+            // 已经追上最新的写入，先发送缓冲的数据再等待新数据
+            Ok(None) => {
+                writer.flush()?;
+                match sub.next_timeout(HEARTBEAT_INTERVAL) {
+                    Ok(Some(event)) => event,
+                    Ok(None) => {
+                        let write_pos = sub.position();
+                        write_frame(&mut writer, &Frame::Heartbeat { write_pos })?;
+                        writer.flush()?;
+                        continue;
+                    }
+                    Err(e) => return Err(io::Error::other(e)),
+                }
+            }
+            Err(e) => return Err(io::Error::other(e)),
+        };
+
+        let (rec_type, value) = match event.value {
+            Some(value) => (LogRecordType::NORMAL, value.to_vec()),
+            None => (LogRecordType::DELETE, Vec::new()),
+        };
+        let frame = Frame::Record {
+            next: event.next,
+            target: event.target,
+            rec_type,
+            key: event.key.to_vec(),
+            value,
+            expire: event.expire,
+        };
+        write_frame(&mut writer, &frame)?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::log_record::LogRecordPos;
+    use crate::options::Options;
+    use crate::replication::protocol::{read_frame, write_handshake};
+    use bytes::Bytes;
+    use std::fs;
+
+    #[test]
+    fn test_leader_streams_records() {
+        let dir_path = std::env::temp_dir().join("fdb-replication-leader");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Arc::new(
+            Engine::open(Options {
+                dir_path: dir_path.clone(),
+                ..Default::default()
+            })
+            .unwrap(),
+        );
+        engine.put(Bytes::from("a"), Bytes::from("1")).unwrap();
+        engine.delete(Bytes::from("a")).unwrap();
+
+        let leader = ReplicationLeader::bind("127.0.0.1:0", engine.clone()).unwrap();
+        let addr = leader.local_addr().unwrap();
+        std::thread::spawn(move || leader.serve());
+
+        let mut stream = TcpStream::connect(addr).unwrap();
+        write_handshake(&mut stream, LogRecordPos::default()).unwrap();
+        let mut reader = BufReader::new(stream);
+        let frame = read_frame(&mut reader).unwrap();
+        assert!(matches!(
+            frame,
+            Frame::Record { rec_type: LogRecordType::NORMAL, ref key, .. } if key == b"a"
+        ));
+        let frame = read_frame(&mut reader).unwrap();
+        assert!(matches!(
+            frame,
+            Frame::Record { rec_type: LogRecordType::DELETE, next, .. } if next == engine.write_pos()
+        ));
+
+        // 没有新数据时发送心跳，有新数据时立即发送
+        let frame = read_frame(&mut reader).unwrap();
+        assert_eq!(
+            frame,
+            Frame::Heartbeat {
+                write_pos: engine.write_pos()
+            }
+        );
+        engine.put(Bytes::from("b"), Bytes::from("2")).unwrap();
+        let frame = read_frame(&mut reader).unwrap();
+        assert!(matches!(frame, Frame::Record { ref value, .. } if value == b"2"));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
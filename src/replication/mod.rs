@@ -0,0 +1,3 @@
+pub mod follower;
+pub mod leader;
+pub mod protocol;
@@ -0,0 +1,211 @@
+use crate::cdc::ChangeTarget;
+use crate::data::log_record::{LogRecordPos, LogRecordType};
+use bytes::{Buf, BufMut, BytesMut};
+use std::io::{self, Read, Write};
+use std::time::Duration;
+
+// 握手时 follower 发送的魔数，后面跟着开始复制的位置
+const HANDSHAKE_MAGIC: &[u8; 8] = b"FDBREPL3";
+// key 和 value 的最大长度，超过时视为协议错误
+const MAX_FIELD_LEN: u32 = 512 * 1024 * 1024;
+
+const FRAME_RECORD: u8 = 1;
+const FRAME_HEARTBEAT: u8 = 2;
+
+const TARGET_DEFAULT: u8 = 0;
+const TARGET_COLUMN_FAMILY: u8 = 1;
+const TARGET_CATALOG: u8 = 2;
+
+/// leader 没有新数据时发送心跳的间隔
+pub const HEARTBEAT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// leader 发送给 follower 的数据帧
+#[derive(Clone, Debug, PartialEq)]
+pub enum Frame {
+    // 一条日志记录，next 为该记录之后的位置，follower 下次从这里继续复制
+    Record {
+        next: LogRecordPos,
+        target: ChangeTarget,
+        rec_type: LogRecordType,
+        key: Vec<u8>,
+        value: Vec<u8>,
+        expire: u64, // 过期时间，毫秒时间戳，0 表示不过期
+    },
+    // 心跳，携带 leader 当前的写入位置
+    Heartbeat {
+        write_pos: LogRecordPos,
+    },
+}
+
+pub fn write_handshake<W: Write>(writer: &mut W, pos: LogRecordPos) -> io::Result<()> {
+    let mut buf = BytesMut::with_capacity(HANDSHAKE_MAGIC.len() + 12);
+    buf.extend_from_slice(HANDSHAKE_MAGIC);
+    put_pos(&mut buf, pos);
+    writer.write_all(&buf)
+}
+
+pub fn read_handshake<R: Read>(reader: &mut R) -> io::Result<LogRecordPos> {
+    let mut buf = [0u8; HANDSHAKE_MAGIC.len() + 12];
+    reader.read_exact(&mut buf)?;
+    if &buf[..HANDSHAKE_MAGIC.len()] != HANDSHAKE_MAGIC {
+        return Err(protocol_error("invalid handshake"));
+    }
+    Ok(get_pos(&mut &buf[HANDSHAKE_MAGIC.len()..]))
+}
+
+// 帧格式：类型 | 位置 [| 记录类型 | 目标类型 | 列族名称长度 | key长度 | value长度 | 过期时间 | 列族名称 | key | value]
+pub fn write_frame<W: Write>(writer: &mut W, frame: &Frame) -> io::Result<()> {
+    let mut buf = BytesMut::new();
+    match frame {
+        Frame::Record {
+            next,
+            target,
+            rec_type,
+            key,
+            value,
+            expire,
+        } => {
+            let (target_type, cf_name) = match target {
+                ChangeTarget::Default => (TARGET_DEFAULT, ""),
+                ChangeTarget::ColumnFamily(name) => (TARGET_COLUMN_FAMILY, name.as_str()),
+                ChangeTarget::Catalog => (TARGET_CATALOG, ""),
+            };
+            buf.reserve(1 + 12 + 2 + 12 + 8 + cf_name.len() + key.len() + value.len());
+            buf.put_u8(FRAME_RECORD);
+            put_pos(&mut buf, *next);
+            buf.put_u8(*rec_type as u8);
+            buf.put_u8(target_type);
+            buf.put_u32(cf_name.len() as u32);
+            buf.put_u32(key.len() as u32);
+            buf.put_u32(value.len() as u32);
+            buf.put_u64(*expire);
+            buf.extend_from_slice(cf_name.as_bytes());
+            buf.extend_from_slice(key);
+            buf.extend_from_slice(value);
+        }
+        Frame::Heartbeat { write_pos } => {
+            buf.put_u8(FRAME_HEARTBEAT);
+            put_pos(&mut buf, *write_pos);
+        }
+    }
+    writer.write_all(&buf)
+}
+
+pub fn read_frame<R: Read>(reader: &mut R) -> io::Result<Frame> {
+    let mut header = [0u8; 1 + 12];
+    reader.read_exact(&mut header)?;
+    let pos = get_pos(&mut &header[1..]);
+    match header[0] {
+        FRAME_RECORD => {
+            let mut record_header = [0u8; 2 + 12 + 8];
+            reader.read_exact(&mut record_header)?;
+            let mut buf = &record_header[..];
+            let rec_type = match buf.get_u8() {
+                1 => LogRecordType::NORMAL,
+                2 => LogRecordType::DELETE,
+                _ => return Err(protocol_error("unknown log record type")),
+            };
+            let target_type = buf.get_u8();
+            let name_len = buf.get_u32();
+            let key_len = buf.get_u32();
+            let value_len = buf.get_u32();
+            let expire = buf.get_u64();
+            if name_len > MAX_FIELD_LEN || key_len > MAX_FIELD_LEN || value_len > MAX_FIELD_LEN {
+                return Err(protocol_error("record too large"));
+            }
+            let mut name = vec![0u8; name_len as usize];
+            reader.read_exact(&mut name)?;
+            let target = match target_type {
+                TARGET_DEFAULT => ChangeTarget::Default,
+                TARGET_COLUMN_FAMILY => ChangeTarget::ColumnFamily(
+                    String::from_utf8(name)
+                        .map_err(|_| protocol_error("invalid column family name"))?,
+                ),
+                TARGET_CATALOG => ChangeTarget::Catalog,
+                _ => return Err(protocol_error("unknown record target")),
+            };
+            let mut key = vec![0u8; key_len as usize];
+            reader.read_exact(&mut key)?;
+            let mut value = vec![0u8; value_len as usize];
+            reader.read_exact(&mut value)?;
+            Ok(Frame::Record {
+                next: pos,
+                target,
+                rec_type,
+                key,
+                value,
+                expire,
+            })
+        }
+        FRAME_HEARTBEAT => Ok(Frame::Heartbeat { write_pos: pos }),
+        _ => Err(protocol_error("unknown frame type")),
+    }
+}
+
+fn put_pos(buf: &mut BytesMut, pos: LogRecordPos) {
+    buf.put_u32(pos.file_id);
+    buf.put_u64(pos.offset);
+}
+
+fn get_pos(buf: &mut &[u8]) -> LogRecordPos {
+    LogRecordPos::new(buf.get_u32(), buf.get_u64())
+}
+
+fn protocol_error(msg: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("replication protocol error: {}", msg),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_encode_and_decode() {
+        let frames = vec![
+            Frame::Record {
+                next: LogRecordPos::new(3, 128),
+                target: ChangeTarget::Default,
+                rec_type: LogRecordType::NORMAL,
+                key: b"key".to_vec(),
+                value: b"value".to_vec(),
+                expire: 1_700_000_000_000,
+            },
+            Frame::Record {
+                next: LogRecordPos::new(3, 140),
+                target: ChangeTarget::ColumnFamily("users".to_string()),
+                rec_type: LogRecordType::DELETE,
+                key: b"key".to_vec(),
+                value: Vec::new(),
+                expire: 0,
+            },
+            Frame::Record {
+                next: LogRecordPos::new(3, 160),
+                target: ChangeTarget::Catalog,
+                rec_type: LogRecordType::NORMAL,
+                key: b"users".to_vec(),
+                value: b"meta".to_vec(),
+                expire: 0,
+            },
+            Frame::Heartbeat {
+                write_pos: LogRecordPos::new(4, 0),
+            },
+        ];
+        let mut buf = Vec::new();
+        let pos = LogRecordPos::new(1, 2);
+        write_handshake(&mut buf, pos).unwrap();
+        for frame in frames.iter() {
+            write_frame(&mut buf, frame).unwrap();
+        }
+
+        let mut reader = &buf[..];
+        assert_eq!(read_handshake(&mut reader).unwrap(), pos);
+        for frame in frames {
+            assert_eq!(read_frame(&mut reader).unwrap(), frame);
+        }
+        assert!(read_frame(&mut reader).is_err());
+        assert!(read_handshake(&mut &b"BADMAGIC000000000000"[..]).is_err());
+    }
+}
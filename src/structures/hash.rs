@@ -0,0 +1,128 @@
+use crate::errors::Result;
+use crate::options::IteratorOptions;
+use crate::structures::{data_key, DataType, RedisDataStructure};
+use bytes::Bytes;
+
+impl RedisDataStructure {
+    /// 设置 hash 中 field 的值，field 不存在时返回 true
+    pub fn hset(&self, key: &[u8], field: &[u8], value: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = self.find_or_new_meta(key, DataType::Hash)?;
+            let field_key = data_key(key, meta.version, field);
+            let exists = self.get_data(field_key.clone())?.is_some();
+
+            batch.put(field_key, Bytes::copy_from_slice(value))?;
+            if !exists {
+                meta.size += 1;
+                self.put_meta(batch, key, &meta)?;
+            }
+            Ok(!exists)
+        })
+    }
+
+    /// 获取 hash 中 field 的值
+    pub fn hget(&self, key: &[u8], field: &[u8]) -> Result<Option<Bytes>> {
+        match self.find_typed_meta(key, DataType::Hash)? {
+            Some(meta) => self.get_data(data_key(key, meta.version, field)),
+            None => Ok(None),
+        }
+    }
+
+    /// 删除 hash 中的 field，field 存在时返回 true
+    pub fn hdel(&self, key: &[u8], field: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = match self.find_typed_meta(key, DataType::Hash)? {
+                Some(meta) => meta,
+                None => return Ok(false),
+            };
+            let field_key = data_key(key, meta.version, field);
+            if self.get_data(field_key.clone())?.is_none() {
+                return Ok(false);
+            }
+
+            meta.size -= 1;
+            self.put_meta(batch, key, &meta)?;
+            batch.delete(field_key)?;
+            Ok(true)
+        })
+    }
+
+    /// hash 中 field 的个数
+    pub fn hlen(&self, key: &[u8]) -> Result<u32> {
+        let meta = self.find_typed_meta(key, DataType::Hash)?;
+        Ok(meta.map_or(0, |meta| meta.size))
+    }
+
+    /// 获取 hash 中所有的 field 和 value，按 field 排序
+    pub fn hgetall(&self, key: &[u8]) -> Result<Vec<(Bytes, Bytes)>> {
+        let meta = match self.find_typed_meta(key, DataType::Hash)? {
+            Some(meta) => meta,
+            None => return Ok(Vec::new()),
+        };
+        let prefix = data_key(key, meta.version, &[]);
+        let iter = self.engine.iter(IteratorOptions {
+            prefix: prefix.to_vec(),
+            reverse: false,
+        });
+        let mut pairs = Vec::new();
+        while let Some((field_key, value)) = iter.try_next()? {
+            pairs.push((field_key.slice(prefix.len()..), value));
+        }
+        Ok(pairs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structures::tests::open_structure;
+    use bytes::Bytes;
+    use std::fs;
+
+    #[test]
+    fn test_hash_set_get_del() {
+        let (rds, dir_path) = open_structure("fdb-structures-hash");
+        assert_eq!(rds.hget(b"user", b"name").unwrap(), None);
+
+        assert!(rds.hset(b"user", b"name", b"fdb").unwrap());
+        assert!(rds.hset(b"user", b"lang", b"rust").unwrap());
+        assert!(!rds.hset(b"user", b"name", b"fdb-rs").unwrap());
+        assert_eq!(
+            rds.hget(b"user", b"name").unwrap(),
+            Some(Bytes::from("fdb-rs"))
+        );
+        assert_eq!(rds.hlen(b"user").unwrap(), 2);
+        assert_eq!(
+            rds.hgetall(b"user").unwrap(),
+            vec![
+                (Bytes::from("lang"), Bytes::from("rust")),
+                (Bytes::from("name"), Bytes::from("fdb-rs")),
+            ]
+        );
+
+        assert!(rds.hdel(b"user", b"lang").unwrap());
+        assert!(!rds.hdel(b"user", b"lang").unwrap());
+        assert_eq!(rds.hlen(b"user").unwrap(), 1);
+
+        // 删除最后一个 field 之后 key 不再存在
+        assert!(rds.hdel(b"user", b"name").unwrap());
+        assert_eq!(rds.key_type(b"user").unwrap(), None);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_hash_del_bumps_version() {
+        let (rds, dir_path) = open_structure("fdb-structures-hash-version");
+        rds.hset(b"user", b"name", b"fdb").unwrap();
+        assert!(rds.del(b"user").unwrap());
+        assert_eq!(rds.hget(b"user", b"name").unwrap(), None);
+
+        // 重新创建之后看不到旧版本的 field
+        rds.hset(b"user", b"lang", b"rust").unwrap();
+        assert_eq!(rds.hget(b"user", b"name").unwrap(), None);
+        assert_eq!(rds.hlen(b"user").unwrap(), 1);
+        assert_eq!(rds.hgetall(b"user").unwrap().len(), 1);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
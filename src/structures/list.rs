@@ -0,0 +1,134 @@
+use crate::errors::Result;
+use crate::structures::{data_key, DataType, RedisDataStructure};
+use bytes::Bytes;
+
+impl RedisDataStructure {
+    /// 从左侧插入元素，返回插入后 list 的长度
+    pub fn lpush(&self, key: &[u8], element: &[u8]) -> Result<u32> {
+        self.push_inner(key, element, true)
+    }
+
+    /// 从右侧插入元素，返回插入后 list 的长度
+    pub fn rpush(&self, key: &[u8], element: &[u8]) -> Result<u32> {
+        self.push_inner(key, element, false)
+    }
+
+    /// 从左侧弹出元素
+    pub fn lpop(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        self.pop_inner(key, true)
+    }
+
+    /// 从右侧弹出元素
+    pub fn rpop(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        self.pop_inner(key, false)
+    }
+
+    /// list 的长度
+    pub fn llen(&self, key: &[u8]) -> Result<u32> {
+        let meta = self.find_typed_meta(key, DataType::List)?;
+        Ok(meta.map_or(0, |meta| meta.size))
+    }
+
+    // list 的元素存放在 [head, tail) 区间中，子key为元素的下标
+    fn push_inner(&self, key: &[u8], element: &[u8], is_left: bool) -> Result<u32> {
+        self.update(|batch| {
+            let mut meta = self.find_or_new_meta(key, DataType::List)?;
+            let index = match is_left {
+                true => meta.head - 1,
+                false => meta.tail,
+            };
+
+            batch.put(
+                data_key(key, meta.version, &index.to_be_bytes()),
+                Bytes::copy_from_slice(element),
+            )?;
+            meta.size += 1;
+            match is_left {
+                true => meta.head -= 1,
+                false => meta.tail += 1,
+            }
+            self.put_meta(batch, key, &meta)?;
+            Ok(meta.size)
+        })
+    }
+
+    fn pop_inner(&self, key: &[u8], is_left: bool) -> Result<Option<Bytes>> {
+        self.update(|batch| {
+            let mut meta = match self.find_typed_meta(key, DataType::List)? {
+                Some(meta) => meta,
+                None => return Ok(None),
+            };
+            let index = match is_left {
+                true => meta.head,
+                false => meta.tail - 1,
+            };
+            let element_key = data_key(key, meta.version, &index.to_be_bytes());
+            let element = self.get_data(element_key.clone())?;
+
+            meta.size -= 1;
+            match is_left {
+                true => meta.head += 1,
+                false => meta.tail -= 1,
+            }
+            self.put_meta(batch, key, &meta)?;
+            batch.delete(element_key)?;
+            Ok(element)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structures::tests::open_structure;
+    use crate::structures::RedisDataStructure;
+    use bytes::Bytes;
+    use std::fs;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_list_push_pop() {
+        let (rds, dir_path) = open_structure("fdb-structures-list");
+        assert_eq!(rds.lpop(b"queue").unwrap(), None);
+
+        assert_eq!(rds.lpush(b"queue", b"b").unwrap(), 1);
+        assert_eq!(rds.lpush(b"queue", b"a").unwrap(), 2);
+        assert_eq!(rds.rpush(b"queue", b"c").unwrap(), 3);
+        assert_eq!(rds.llen(b"queue").unwrap(), 3);
+
+        assert_eq!(rds.rpop(b"queue").unwrap(), Some(Bytes::from("c")));
+        assert_eq!(rds.lpop(b"queue").unwrap(), Some(Bytes::from("a")));
+        assert_eq!(rds.rpop(b"queue").unwrap(), Some(Bytes::from("b")));
+        assert_eq!(rds.rpop(b"queue").unwrap(), None);
+        assert_eq!(rds.llen(b"queue").unwrap(), 0);
+        assert_eq!(rds.key_type(b"queue").unwrap(), None);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_list_push_concurrent() {
+        let (rds, dir_path) = open_structure("fdb-structures-list-concurrent");
+        // 共享同一个引擎的多个实例同时写入同一个 list
+        let handles: Vec<_> = (0..4)
+            .map(|i| {
+                let rds = RedisDataStructure::new(Arc::clone(&rds.engine));
+                std::thread::spawn(move || {
+                    for j in 0..50 {
+                        rds.rpush(b"queue", format!("{}-{}", i, j).as_bytes())
+                            .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+        assert_eq!(rds.llen(b"queue").unwrap(), 200);
+        for _ in 0..200 {
+            assert!(rds.lpop(b"queue").unwrap().is_some());
+        }
+        assert_eq!(rds.lpop(b"queue").unwrap(), None);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
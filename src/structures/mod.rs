@@ -0,0 +1,439 @@
+pub mod hash;
+pub mod list;
+pub mod rdb;
+pub mod set;
+pub mod zset;
+
+use crate::batch::WriteBatch;
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+// 元数据key的前缀，元数据key为 前缀 + 用户key
+const META_KEY_PREFIX: u8 = b'm';
+// 数据部分key的前缀
+const DATA_KEY_PREFIX: u8 = b'd';
+// 元数据header长度：type + expire + version + size
+const META_HEADER_SIZE: usize = 1 + 8 + 8 + 4;
+// list 初始的头尾位置，从中间开始，两端都可以继续写入
+pub(crate) const INITIAL_LIST_MARK: u64 = u64::MAX / 2;
+
+/// 数据结构类型
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DataType {
+    String = 1,
+    Hash = 2,
+    Set = 3,
+    List = 4,
+    ZSet = 5,
+}
+
+impl DataType {
+    fn from_u8(v: u8) -> Result<Self> {
+        match v {
+            1 => Ok(DataType::String),
+            2 => Ok(DataType::Hash),
+            3 => Ok(DataType::Set),
+            4 => Ok(DataType::List),
+            5 => Ok(DataType::ZSet),
+            _ => Err(Errors::InvalidDataStructureMetadata),
+        }
+    }
+}
+
+/// 基于存储引擎实现的 Redis 数据结构
+///
+/// 每个集合类型的 key 都有一条元数据记录，记录类型、过期时间、版本号和元素个数，
+/// 集合中的每个元素单独存储为一条记录，其 key 中包含元数据的版本号：
+///
+/// ```text
+/// 元数据：'m' + key                              => type | expire | version | size [| head | tail]
+/// 元素：  'd' + key长度 + key + version + 子key  => value
+/// ```
+///
+/// 删除集合时只需要删除元数据，再次创建时会使用新的版本号，旧版本的元素不再可见。
+/// 每个写操作在引擎的写锁内读取元数据，元素和元数据的修改作为一个批量写入。
+/// 数据结构的 key 与直接写入引擎的 key 共享同一个命名空间，建议使用单独的数据库目录。
+pub struct RedisDataStructure {
+    pub(crate) engine: Arc<Engine>,
+}
+
+/// 元数据
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Metadata {
+    pub(crate) data_type: DataType,
+    pub(crate) expire: u64, // 过期时间，毫秒时间戳，0表示不过期
+    pub(crate) version: u64,
+    pub(crate) size: u32,
+    pub(crate) head: u64, // 仅 list 使用
+    pub(crate) tail: u64, // 仅 list 使用
+}
+
+impl Metadata {
+    fn encode(&self) -> Bytes {
+        let mut buf = BytesMut::with_capacity(META_HEADER_SIZE + 16);
+        buf.put_u8(self.data_type as u8);
+        buf.put_u64(self.expire);
+        buf.put_u64(self.version);
+        buf.put_u32(self.size);
+        if self.data_type == DataType::List {
+            buf.put_u64(self.head);
+            buf.put_u64(self.tail);
+        }
+        buf.freeze()
+    }
+
+    fn decode(mut buf: Bytes) -> Result<Self> {
+        if buf.len() < META_HEADER_SIZE {
+            return Err(Errors::InvalidDataStructureMetadata);
+        }
+        let data_type = DataType::from_u8(buf.get_u8())?;
+        let expire = buf.get_u64();
+        let version = buf.get_u64();
+        let size = buf.get_u32();
+        let (mut head, mut tail) = (0, 0);
+        if data_type == DataType::List {
+            if buf.len() < 16 {
+                return Err(Errors::InvalidDataStructureMetadata);
+            }
+            head = buf.get_u64();
+            tail = buf.get_u64();
+        }
+        Ok(Metadata {
+            data_type,
+            expire,
+            version,
+            size,
+            head,
+            tail,
+        })
+    }
+
+    fn is_expired(&self) -> bool {
+        self.expire != 0 && self.expire <= now_millis()
+    }
+}
+
+impl RedisDataStructure {
+    pub fn new(engine: Arc<Engine>) -> Self {
+        Self { engine }
+    }
+
+    // ============================ 通用操作 ============================
+
+    /// 删除key，对集合类型只删除元数据，时间复杂度为 O(1)
+    pub fn del(&self, key: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            if self.find_meta(key)?.is_none() {
+                return Ok(false);
+            }
+            batch.delete(meta_key(key))?;
+            Ok(true)
+        })
+    }
+
+    /// 获取key的类型，不存在时返回 None
+    pub fn key_type(&self, key: &[u8]) -> Result<Option<DataType>> {
+        Ok(self.find_meta(key)?.map(|meta| meta.data_type))
+    }
+
+    /// 设置过期时间，key不存在时返回 false
+    pub fn expire(&self, key: &[u8], ttl: Duration) -> Result<bool> {
+        self.expire_at(key, now_millis() + ttl.as_millis() as u64)
+    }
+
+    // 设置过期时间为指定的毫秒时间戳
+    fn expire_at(&self, key: &[u8], expire: u64) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = match self.find_meta(key)? {
+                Some(meta) => meta,
+                None => return Ok(false),
+            };
+            meta.expire = expire;
+            match meta.data_type {
+                // string 的 value 和元数据存放在一起，需要一起重写
+                DataType::String => {
+                    let value = self.get(key)?.unwrap_or_default();
+                    self.put_string(batch, key, &meta, &value)?;
+                }
+                _ => self.put_meta(batch, key, &meta)?,
+            }
+            Ok(true)
+        })
+    }
+
+    /// 剩余的过期时间，key不存在或者没有设置过期时间时返回 None
+    pub fn ttl(&self, key: &[u8]) -> Result<Option<Duration>> {
+        Ok(self.find_meta(key)?.and_then(|meta| match meta.expire {
+            0 => None,
+            expire => Some(Duration::from_millis(expire.saturating_sub(now_millis()))),
+        }))
+    }
+
+    // ============================ String ============================
+
+    /// 写入字符串，ttl 为 None 时不过期
+    pub fn set(&self, key: &[u8], ttl: Option<Duration>, value: &[u8]) -> Result<()> {
+        let meta = Metadata {
+            data_type: DataType::String,
+            expire: ttl.map_or(0, |ttl| now_millis() + ttl.as_millis() as u64),
+            version: 0,
+            size: 0,
+            head: 0,
+            tail: 0,
+        };
+        self.update(|batch| self.put_string(batch, key, &meta, value))
+    }
+
+    /// 读取字符串，key不存在或者已过期时返回 None
+    pub fn get(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        let mut buf = match self.get_raw_meta(key)? {
+            Some(buf) => buf,
+            None => return Ok(None),
+        };
+        let meta = Metadata::decode(buf.clone())?;
+        if meta.is_expired() {
+            return Ok(None);
+        }
+        if meta.data_type != DataType::String {
+            return Err(Errors::WrongTypeOperation);
+        }
+        buf.advance(META_HEADER_SIZE);
+        Ok(Some(buf))
+    }
+
+    // string 类型的 value 直接存放在元数据之后
+    fn put_string(
+        &self,
+        batch: &mut WriteBatch,
+        key: &[u8],
+        meta: &Metadata,
+        value: &[u8],
+    ) -> Result<()> {
+        let mut buf = BytesMut::from(meta.encode().as_ref());
+        buf.extend_from_slice(value);
+        batch.put(meta_key(key), buf.freeze())
+    }
+
+    // ============================ 内部方法 ============================
+
+    // 持有引擎的写锁执行 f，f 放入批量中的写操作在返回之后一起提交
+    pub(crate) fn update<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&mut WriteBatch) -> Result<T>,
+    {
+        let _guard = self.engine.lock_writes();
+        let mut batch = self.engine.write_batch();
+        let res = f(&mut batch)?;
+        if !batch.is_empty() {
+            batch.commit_locked()?;
+        }
+        Ok(res)
+    }
+
+    // 读取未过期的元数据
+    pub(crate) fn find_meta(&self, key: &[u8]) -> Result<Option<Metadata>> {
+        let meta = match self.get_raw_meta(key)? {
+            Some(buf) => Metadata::decode(buf)?,
+            None => return Ok(None),
+        };
+        match meta.is_expired() {
+            true => Ok(None),
+            false => Ok(Some(meta)),
+        }
+    }
+
+    // 读取指定类型的元数据，不存在时返回 None，类型不匹配时返回错误
+    pub(crate) fn find_typed_meta(
+        &self,
+        key: &[u8],
+        data_type: DataType,
+    ) -> Result<Option<Metadata>> {
+        match self.find_meta(key)? {
+            Some(meta) if meta.data_type != data_type => Err(Errors::WrongTypeOperation),
+            meta => Ok(meta),
+        }
+    }
+
+    // 读取指定类型的元数据，不存在时创建一个新版本的空元数据
+    pub(crate) fn find_or_new_meta(&self, key: &[u8], data_type: DataType) -> Result<Metadata> {
+        if let Some(meta) = self.find_typed_meta(key, data_type)? {
+            return Ok(meta);
+        }
+        let (head, tail) = match data_type {
+            DataType::List => (INITIAL_LIST_MARK, INITIAL_LIST_MARK),
+            _ => (0, 0),
+        };
+        Ok(Metadata {
+            data_type,
+            expire: 0,
+            version: self.next_version(),
+            size: 0,
+            head,
+            tail,
+        })
+    }
+
+    pub(crate) fn put_meta(
+        &self,
+        batch: &mut WriteBatch,
+        key: &[u8],
+        meta: &Metadata,
+    ) -> Result<()> {
+        // 集合为空时删除元数据
+        if meta.size == 0 {
+            return batch.delete(meta_key(key));
+        }
+        batch.put(meta_key(key), meta.encode())
+    }
+
+    pub(crate) fn get_data(&self, key: Bytes) -> Result<Option<Bytes>> {
+        match self.engine.get(key) {
+            Ok(value) => Ok(Some(value)),
+            Err(Errors::KeyNotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn get_raw_meta(&self, key: &[u8]) -> Result<Option<Bytes>> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        self.get_data(meta_key(key))
+    }
+
+    // 生成新的版本号，使用引擎的序列号。元数据记录的序列号大于版本号，
+    // 重新打开时恢复的序列号不会小于已经使用过的版本号，版本号不会重复
+    fn next_version(&self) -> u64 {
+        self.engine.next_seq()
+    }
+}
+
+fn meta_key(key: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(key.len() + 1);
+    buf.put_u8(META_KEY_PREFIX);
+    buf.extend_from_slice(key);
+    buf.freeze()
+}
+
+// 元素的key：前缀 + key长度 + key + 版本号 + 子key
+pub(crate) fn data_key(key: &[u8], version: u64, sub_key: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + 4 + key.len() + 8 + sub_key.len());
+    buf.put_u8(DATA_KEY_PREFIX);
+    buf.put_u32(key.len() as u32);
+    buf.extend_from_slice(key);
+    buf.put_u64(version);
+    buf.extend_from_slice(sub_key);
+    buf.freeze()
+}
+
+pub(crate) fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis() as u64
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+    use std::path::PathBuf;
+
+    pub(crate) fn open_structure(name: &str) -> (RedisDataStructure, PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        (RedisDataStructure::new(Arc::new(engine)), dir_path)
+    }
+
+    #[test]
+    fn test_metadata_encode_and_decode() {
+        let meta = Metadata {
+            data_type: DataType::List,
+            expire: 100,
+            version: 200,
+            size: 3,
+            head: 10,
+            tail: 13,
+        };
+        assert_eq!(Metadata::decode(meta.encode()).unwrap(), meta);
+
+        let res = Metadata::decode(Bytes::from("bad"));
+        assert_eq!(res.err(), Some(Errors::InvalidDataStructureMetadata));
+    }
+
+    #[test]
+    fn test_string_get_set_del() {
+        let (rds, dir_path) = open_structure("fdb-structures-string");
+        assert_eq!(rds.get(b"name").unwrap(), None);
+
+        rds.set(b"name", None, b"fdb").unwrap();
+        assert_eq!(rds.get(b"name").unwrap(), Some(Bytes::from("fdb")));
+        assert_eq!(rds.key_type(b"name").unwrap(), Some(DataType::String));
+        assert_eq!(rds.ttl(b"name").unwrap(), None);
+
+        // 过期之后不可见
+        rds.set(b"tmp", Some(Duration::from_millis(1)), b"v")
+            .unwrap();
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(rds.get(b"tmp").unwrap(), None);
+        assert_eq!(rds.key_type(b"tmp").unwrap(), None);
+
+        assert!(rds.expire(b"name", Duration::from_secs(100)).unwrap());
+        assert!(rds.ttl(b"name").unwrap().unwrap() > Duration::from_secs(90));
+        assert_eq!(rds.get(b"name").unwrap(), Some(Bytes::from("fdb")));
+
+        assert!(rds.del(b"name").unwrap());
+        assert!(!rds.del(b"name").unwrap());
+        assert_eq!(rds.get(b"name").unwrap(), None);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_wrong_type() {
+        let (rds, dir_path) = open_structure("fdb-structures-wrong-type");
+        rds.hset(b"h", b"f", b"v").unwrap();
+        assert_eq!(rds.get(b"h"), Err(Errors::WrongTypeOperation));
+        assert_eq!(rds.sadd(b"h", b"m"), Err(Errors::WrongTypeOperation));
+        assert_eq!(rds.lpush(b"h", b"e"), Err(Errors::WrongTypeOperation));
+        assert_eq!(rds.zadd(b"h", 1.0, b"m"), Err(Errors::WrongTypeOperation));
+
+        // set 会覆盖其他类型
+        rds.set(b"h", None, b"v").unwrap();
+        assert_eq!(rds.hget(b"h", b"f"), Err(Errors::WrongTypeOperation));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_version_after_reopen() {
+        let (rds, dir_path) = open_structure("fdb-structures-version");
+        rds.sadd(b"s", b"old").unwrap();
+        let version = rds.find_meta(b"s").unwrap().unwrap().version;
+        drop(rds);
+
+        // 重新打开之后分配的版本号仍然大于之前使用过的版本号
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let rds = RedisDataStructure::new(Arc::new(engine));
+        assert!(rds.del(b"s").unwrap());
+        rds.sadd(b"s", b"new").unwrap();
+        assert!(rds.find_meta(b"s").unwrap().unwrap().version > version);
+        assert_eq!(rds.smembers(b"s").unwrap(), vec![Bytes::from("new")]);
+
+        drop(rds);
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,1170 @@
+use crate::errors::{Errors, Result};
+use crate::structures::zset::{zset_member_sub_key, zset_score_sub_key};
+use crate::structures::{
+    data_key, now_millis, DataType, Metadata, RedisDataStructure, INITIAL_LIST_MARK,
+};
+use bytes::Bytes;
+use std::collections::HashMap;
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+
+// 支持的最高 RDB 版本（Redis 7.4）
+const RDB_MAX_VERSION: u32 = 12;
+
+// 操作码
+const OPCODE_SLOT_INFO: u8 = 0xF4;
+const OPCODE_FUNCTION2: u8 = 0xF5;
+const OPCODE_FUNCTION_PRE_GA: u8 = 0xF6;
+const OPCODE_MODULE_AUX: u8 = 0xF7;
+const OPCODE_IDLE: u8 = 0xF8;
+const OPCODE_FREQ: u8 = 0xF9;
+const OPCODE_AUX: u8 = 0xFA;
+const OPCODE_RESIZEDB: u8 = 0xFB;
+const OPCODE_EXPIRETIME_MS: u8 = 0xFC;
+const OPCODE_EXPIRETIME: u8 = 0xFD;
+const OPCODE_SELECTDB: u8 = 0xFE;
+const OPCODE_EOF: u8 = 0xFF;
+
+// value 类型
+const TYPE_STRING: u8 = 0;
+const TYPE_LIST: u8 = 1;
+const TYPE_SET: u8 = 2;
+const TYPE_ZSET: u8 = 3;
+const TYPE_HASH: u8 = 4;
+const TYPE_ZSET_2: u8 = 5;
+const TYPE_HASH_ZIPMAP: u8 = 9;
+const TYPE_LIST_ZIPLIST: u8 = 10;
+const TYPE_SET_INTSET: u8 = 11;
+const TYPE_ZSET_ZIPLIST: u8 = 12;
+const TYPE_HASH_ZIPLIST: u8 = 13;
+const TYPE_LIST_QUICKLIST: u8 = 14;
+const TYPE_HASH_LISTPACK: u8 = 16;
+const TYPE_ZSET_LISTPACK: u8 = 17;
+const TYPE_LIST_QUICKLIST_2: u8 = 18;
+const TYPE_SET_LISTPACK: u8 = 20;
+
+// 字符串的特殊编码
+const ENC_INT8: u64 = 0;
+const ENC_INT16: u64 = 1;
+const ENC_INT32: u64 = 2;
+const ENC_LZF: u64 = 3;
+
+// 校验文件时每次读取的数据大小
+const CHECKSUM_CHUNK_SIZE: u64 = 64 * 1024;
+
+// quicklist 2 的节点类型
+const QUICKLIST_NODE_PLAIN: u64 = 1;
+const QUICKLIST_NODE_PACKED: u64 = 2;
+
+/// RDB 导入的统计信息
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct RdbImportStat {
+    pub keys: usize,    // 导入的 key 的数量
+    pub expired: usize, // 已经过期被跳过的 key 的数量
+}
+
+// 从 RDB 文件中解析出的 value
+enum RdbValue {
+    String(Vec<u8>),
+    List(Vec<Vec<u8>>),
+    Set(Vec<Vec<u8>>),
+    ZSet(Vec<(Vec<u8>, f64)>),
+    Hash(Vec<(Vec<u8>, Vec<u8>)>),
+}
+
+impl RedisDataStructure {
+    /// 导入 Redis 的 RDB 快照文件（例如 `dump.rdb`）
+    ///
+    /// string、hash、set、list、zset 分别写入为对应的数据结构，编码方式见 [`RedisDataStructure`]，
+    /// 导入之后可以直接通过 `get`、`hgetall`、`smembers`、`lpop`、`zrange` 等方法访问。
+    /// 过期时间保持不变，导入时已经过期的 key 会被跳过；已经存在的同名 key 会被覆盖。
+    /// 所有逻辑数据库（SELECT 的编号）中的 key 都导入到同一个命名空间。
+    /// stream 和 module 类型不支持，遇到时返回 `UnsupportedRdbType`。
+    ///
+    /// 写入之前会先读一遍整个文件检查校验和，校验失败时不会导入任何数据。
+    /// 每个 key 的所有元素和元数据在一个批量中原子写入。
+    pub fn import_rdb<R: Read + Seek>(&self, mut reader: R) -> Result<RdbImportStat> {
+        verify_checksum(&mut reader)?;
+        let mut reader = RdbReader::new(reader);
+        let version = reader.read_header()?;
+
+        let mut stat = RdbImportStat::default();
+        // 下一个 key 的过期时间，毫秒时间戳
+        let mut expire = 0;
+        loop {
+            match reader.read_u8()? {
+                OPCODE_EOF => break,
+                OPCODE_SELECTDB => {
+                    reader.read_length()?;
+                }
+                OPCODE_RESIZEDB => {
+                    reader.read_length()?;
+                    reader.read_length()?;
+                }
+                OPCODE_AUX => {
+                    reader.read_string()?;
+                    reader.read_string()?;
+                }
+                OPCODE_EXPIRETIME => {
+                    expire = u32::from_le_bytes(reader.read_array()?) as u64 * 1000;
+                }
+                OPCODE_EXPIRETIME_MS => expire = u64::from_le_bytes(reader.read_array()?),
+                OPCODE_FREQ => {
+                    reader.read_u8()?;
+                }
+                OPCODE_IDLE => {
+                    reader.read_length()?;
+                }
+                OPCODE_SLOT_INFO => {
+                    // slot 编号、key 数量、带过期时间的 key 数量
+                    for _ in 0..3 {
+                        reader.read_length()?;
+                    }
+                }
+                OPCODE_FUNCTION2 => {
+                    reader.read_string()?;
+                }
+                OPCODE_FUNCTION_PRE_GA | OPCODE_MODULE_AUX => {
+                    return Err(Errors::UnsupportedRdbType)
+                }
+                value_type => {
+                    let key = reader.read_string()?;
+                    let value = reader.read_value(value_type)?;
+                    // 与 Redis 加载 RDB 文件时一样，跳过已经过期的 key
+                    if expire != 0 && expire <= now_millis() {
+                        stat.expired += 1;
+                    } else {
+                        self.import_value(&key, value, expire)?;
+                        stat.keys += 1;
+                    }
+                    expire = 0;
+                }
+            }
+        }
+
+        // 文件末尾的校验和已经检查过，这里确认 EOF 之后紧跟着的就是校验和
+        if version >= 5 {
+            let crc = reader.crc;
+            let checksum = u64::from_le_bytes(reader.read_array()?);
+            if checksum != 0 && checksum != crc {
+                return Err(Errors::RdbChecksumMismatch);
+            }
+        }
+        Ok(stat)
+    }
+
+    // 集合类型使用新的版本号写入所有元素，覆盖元数据之后已有的同名 key 的元素不再可见
+    fn import_value(&self, key: &[u8], value: RdbValue, expire: u64) -> Result<()> {
+        let data_type = match &value {
+            RdbValue::String(_) => DataType::String,
+            RdbValue::List(_) => DataType::List,
+            RdbValue::Set(_) => DataType::Set,
+            RdbValue::ZSet(_) => DataType::ZSet,
+            RdbValue::Hash(_) => DataType::Hash,
+        };
+        let mark = match data_type {
+            DataType::List => INITIAL_LIST_MARK,
+            _ => 0,
+        };
+        let mut meta = Metadata {
+            data_type,
+            expire,
+            version: 0,
+            size: 0,
+            head: mark,
+            tail: mark,
+        };
+        self.update(|batch| {
+            if data_type != DataType::String {
+                meta.version = self.next_version();
+            }
+            match value {
+                RdbValue::String(value) => return self.put_string(batch, key, &meta, &value),
+                RdbValue::List(elements) => {
+                    for element in elements {
+                        let index = meta.tail.to_be_bytes();
+                        batch.put(data_key(key, meta.version, &index), Bytes::from(element))?;
+                        meta.tail += 1;
+                        meta.size += 1;
+                    }
+                }
+                RdbValue::Set(members) => {
+                    // 去掉重复的元素，保证元数据中的元素个数正确
+                    let members: HashMap<_, _> = members.into_iter().map(|m| (m, ())).collect();
+                    for member in members.into_keys() {
+                        batch.put(data_key(key, meta.version, &member), Bytes::new())?;
+                        meta.size += 1;
+                    }
+                }
+                RdbValue::ZSet(entries) => {
+                    let entries: HashMap<_, _> = entries.into_iter().collect();
+                    for (member, score) in entries {
+                        batch.put(
+                            data_key(key, meta.version, &zset_score_sub_key(score, &member)),
+                            Bytes::new(),
+                        )?;
+                        batch.put(
+                            data_key(key, meta.version, &zset_member_sub_key(&member)),
+                            Bytes::copy_from_slice(&score.to_be_bytes()),
+                        )?;
+                        meta.size += 1;
+                    }
+                }
+                RdbValue::Hash(pairs) => {
+                    let pairs: HashMap<_, _> = pairs.into_iter().collect();
+                    for (field, value) in pairs {
+                        batch.put(data_key(key, meta.version, &field), Bytes::from(value))?;
+                        meta.size += 1;
+                    }
+                }
+            }
+            // 空集合会删除元数据
+            self.put_meta(batch, key, &meta)
+        })
+    }
+}
+
+// 读取 RDB 文件，同时计算读过的数据的校验和
+struct RdbReader<R> {
+    inner: BufReader<R>,
+    crc: u64,
+}
+
+impl<R: Read> RdbReader<R> {
+    fn new(reader: R) -> Self {
+        Self {
+            inner: BufReader::new(reader),
+            crc: 0,
+        }
+    }
+
+    // 读取文件头，返回 RDB 版本
+    fn read_header(&mut self) -> Result<u32> {
+        let header: [u8; 9] = self.read_array()?;
+        if &header[..5] != b"REDIS" {
+            return Err(Errors::InvalidRdbFile);
+        }
+        let version = std::str::from_utf8(&header[5..])
+            .ok()
+            .and_then(|v| v.parse::<u32>().ok())
+            .ok_or(Errors::InvalidRdbFile)?;
+        if !(1..=RDB_MAX_VERSION).contains(&version) {
+            return Err(Errors::UnsupportedRdbVersion);
+        }
+        Ok(version)
+    }
+
+    fn read_array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        let mut buf = [0u8; N];
+        self.inner.read_exact(&mut buf).map_err(read_error)?;
+        self.crc = crc64(self.crc, &buf);
+        Ok(buf)
+    }
+
+    fn read_u8(&mut self) -> Result<u8> {
+        Ok(self.read_array::<1>()?[0])
+    }
+
+    fn read_bytes(&mut self, len: u64) -> Result<Vec<u8>> {
+        // 长度来自文件内容，不预先分配，避免损坏的文件导致分配过多内存
+        let mut buf = Vec::new();
+        (&mut self.inner)
+            .take(len)
+            .read_to_end(&mut buf)
+            .map_err(read_error)?;
+        if buf.len() as u64 != len {
+            return Err(Errors::InvalidRdbFile);
+        }
+        self.crc = crc64(self.crc, &buf);
+        Ok(buf)
+    }
+
+    // 长度编码，最高两位为 11 时表示特殊编码的字符串，返回 (编码类型, true)
+    fn read_length_or_encoding(&mut self) -> Result<(u64, bool)> {
+        let first = self.read_u8()?;
+        let len = match first >> 6 {
+            0 => (first & 0x3F) as u64,
+            1 => ((first & 0x3F) as u64) << 8 | self.read_u8()? as u64,
+            2 => match first {
+                0x80 => u32::from_be_bytes(self.read_array()?) as u64,
+                0x81 => u64::from_be_bytes(self.read_array()?),
+                _ => return Err(Errors::InvalidRdbFile),
+            },
+            _ => return Ok(((first & 0x3F) as u64, true)),
+        };
+        Ok((len, false))
+    }
+
+    fn read_length(&mut self) -> Result<u64> {
+        match self.read_length_or_encoding()? {
+            (len, false) => Ok(len),
+            _ => Err(Errors::InvalidRdbFile),
+        }
+    }
+
+    fn read_string(&mut self) -> Result<Vec<u8>> {
+        let (len, encoded) = self.read_length_or_encoding()?;
+        if !encoded {
+            return self.read_bytes(len);
+        }
+        let value = match len {
+            ENC_INT8 => self.read_u8()? as i8 as i64,
+            ENC_INT16 => i16::from_le_bytes(self.read_array()?) as i64,
+            ENC_INT32 => i32::from_le_bytes(self.read_array()?) as i64,
+            ENC_LZF => {
+                let compressed_len = self.read_length()?;
+                let len = self.read_length()?;
+                let compressed = self.read_bytes(compressed_len)?;
+                return lzf_decompress(&compressed, len as usize);
+            }
+            _ => return Err(Errors::InvalidRdbFile),
+        };
+        Ok(value.to_string().into_bytes())
+    }
+
+    // 旧格式的 zset 分数以字符串存储，长度 253、254、255 分别表示 NaN、正无穷、负无穷
+    fn read_string_score(&mut self) -> Result<f64> {
+        match self.read_u8()? {
+            253 => Ok(f64::NAN),
+            254 => Ok(f64::INFINITY),
+            255 => Ok(f64::NEG_INFINITY),
+            len => parse_score(&self.read_bytes(len as u64)?),
+        }
+    }
+
+    fn read_value(&mut self, value_type: u8) -> Result<RdbValue> {
+        let value = match value_type {
+            TYPE_STRING => RdbValue::String(self.read_string()?),
+            TYPE_LIST => RdbValue::List(self.read_strings()?),
+            TYPE_SET => RdbValue::Set(self.read_strings()?),
+            TYPE_ZSET | TYPE_ZSET_2 => {
+                let len = self.read_length()?;
+                let mut entries = Vec::new();
+                for _ in 0..len {
+                    let member = self.read_string()?;
+                    let score = match value_type {
+                        TYPE_ZSET => self.read_string_score()?,
+                        _ => f64::from_le_bytes(self.read_array()?),
+                    };
+                    entries.push((member, score));
+                }
+                RdbValue::ZSet(entries)
+            }
+            TYPE_HASH => {
+                let len = self.read_length()?;
+                let mut pairs = Vec::new();
+                for _ in 0..len {
+                    pairs.push((self.read_string()?, self.read_string()?));
+                }
+                RdbValue::Hash(pairs)
+            }
+            TYPE_HASH_ZIPMAP => RdbValue::Hash(parse_zipmap(&self.read_string()?)?),
+            TYPE_LIST_ZIPLIST => RdbValue::List(parse_ziplist(&self.read_string()?)?),
+            TYPE_SET_INTSET => RdbValue::Set(parse_intset(&self.read_string()?)?),
+            TYPE_ZSET_ZIPLIST => RdbValue::ZSet(into_scores(parse_ziplist(&self.read_string()?)?)?),
+            TYPE_HASH_ZIPLIST => RdbValue::Hash(into_pairs(parse_ziplist(&self.read_string()?)?)?),
+            TYPE_HASH_LISTPACK => {
+                RdbValue::Hash(into_pairs(parse_listpack(&self.read_string()?)?)?)
+            }
+            TYPE_ZSET_LISTPACK => {
+                RdbValue::ZSet(into_scores(parse_listpack(&self.read_string()?)?)?)
+            }
+            TYPE_SET_LISTPACK => RdbValue::Set(parse_listpack(&self.read_string()?)?),
+            TYPE_LIST_QUICKLIST | TYPE_LIST_QUICKLIST_2 => {
+                let len = self.read_length()?;
+                let mut elements = Vec::new();
+                for _ in 0..len {
+                    if value_type == TYPE_LIST_QUICKLIST {
+                        elements.extend(parse_ziplist(&self.read_string()?)?);
+                        continue;
+                    }
+                    match self.read_length()? {
+                        QUICKLIST_NODE_PLAIN => elements.push(self.read_string()?),
+                        QUICKLIST_NODE_PACKED => {
+                            elements.extend(parse_listpack(&self.read_string()?)?)
+                        }
+                        _ => return Err(Errors::InvalidRdbFile),
+                    }
+                }
+                RdbValue::List(elements)
+            }
+            _ => return Err(Errors::UnsupportedRdbType),
+        };
+        Ok(value)
+    }
+
+    fn read_strings(&mut self) -> Result<Vec<Vec<u8>>> {
+        let len = self.read_length()?;
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(self.read_string()?);
+        }
+        Ok(values)
+    }
+}
+
+fn read_error(e: io::Error) -> Errors {
+    match e.kind() {
+        io::ErrorKind::UnexpectedEof => Errors::InvalidRdbFile,
+        _ => Errors::FailedToReadImport,
+    }
+}
+
+// ziplist、listpack 等紧凑编码的读取游标
+struct Cursor<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn new(buf: &'a [u8]) -> Self {
+        Self { buf, pos: 0 }
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        let end = self.pos.checked_add(len).ok_or(Errors::InvalidRdbFile)?;
+        let bytes = self.buf.get(self.pos..end).ok_or(Errors::InvalidRdbFile)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N]> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    // 3 字节的有符号整数，小端序
+    fn i24(&mut self) -> Result<i64> {
+        let b = self.take(3)?;
+        Ok((i32::from_le_bytes([0, b[0], b[1], b[2]]) >> 8) as i64)
+    }
+}
+
+fn int_entry(value: i64) -> Vec<u8> {
+    value.to_string().into_bytes()
+}
+
+// ziplist：zlbytes(4) | zltail(4) | zllen(2) | entry... | 0xFF
+// entry：prevlen | encoding | data
+fn parse_ziplist(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut cursor = Cursor::new(buf);
+    cursor.take(10)?;
+    let mut entries = Vec::new();
+    loop {
+        match cursor.u8()? {
+            0xFF => break,
+            0xFE => {
+                cursor.take(4)?;
+            }
+            _ => {}
+        }
+        let encoding = cursor.u8()?;
+        let entry = match encoding >> 6 {
+            0 => cursor.take((encoding & 0x3F) as usize)?.to_vec(),
+            1 => {
+                let len = ((encoding & 0x3F) as usize) << 8 | cursor.u8()? as usize;
+                cursor.take(len)?.to_vec()
+            }
+            2 => {
+                let len = u32::from_be_bytes(cursor.array()?) as usize;
+                cursor.take(len)?.to_vec()
+            }
+            _ => int_entry(match encoding {
+                0xC0 => i16::from_le_bytes(cursor.array()?) as i64,
+                0xD0 => i32::from_le_bytes(cursor.array()?) as i64,
+                0xE0 => i64::from_le_bytes(cursor.array()?),
+                0xF0 => cursor.i24()?,
+                0xFE => cursor.u8()? as i8 as i64,
+                // 1111xxxx，xxxx 减一即为 0 到 12 的整数
+                0xF1..=0xFD => (encoding & 0x0F) as i64 - 1,
+                _ => return Err(Errors::InvalidRdbFile),
+            }),
+        };
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// listpack：total bytes(4) | num elements(2) | entry... | 0xFF
+// entry：encoding | data | backlen，backlen 为 encoding 和 data 的总长度
+fn parse_listpack(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut cursor = Cursor::new(buf);
+    cursor.take(6)?;
+    let mut entries = Vec::new();
+    loop {
+        let encoding = cursor.u8()?;
+        if encoding == 0xFF {
+            break;
+        }
+        let start = cursor.pos - 1;
+        let entry = if encoding & 0x80 == 0 {
+            int_entry(encoding as i64)
+        } else if encoding & 0xC0 == 0x80 {
+            cursor.take((encoding & 0x3F) as usize)?.to_vec()
+        } else if encoding & 0xE0 == 0xC0 {
+            // 13 位有符号整数
+            let value = ((encoding & 0x1F) as i64) << 8 | cursor.u8()? as i64;
+            int_entry(match value >= 1 << 12 {
+                true => value - (1 << 13),
+                false => value,
+            })
+        } else if encoding & 0xF0 == 0xE0 {
+            let len = ((encoding & 0x0F) as usize) << 8 | cursor.u8()? as usize;
+            cursor.take(len)?.to_vec()
+        } else {
+            match encoding {
+                0xF0 => {
+                    let len = u32::from_le_bytes(cursor.array()?) as usize;
+                    cursor.take(len)?.to_vec()
+                }
+                0xF1 => int_entry(i16::from_le_bytes(cursor.array()?) as i64),
+                0xF2 => int_entry(cursor.i24()?),
+                0xF3 => int_entry(i32::from_le_bytes(cursor.array()?) as i64),
+                0xF4 => int_entry(i64::from_le_bytes(cursor.array()?)),
+                _ => return Err(Errors::InvalidRdbFile),
+            }
+        };
+        let backlen = match cursor.pos - start {
+            0..=127 => 1,
+            128..=16382 => 2,
+            16383..=2097150 => 3,
+            2097151..=268435454 => 4,
+            _ => 5,
+        };
+        cursor.take(backlen)?;
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+// intset：encoding(4) | length(4) | 按 encoding 字节宽度存储的有符号整数
+fn parse_intset(buf: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut cursor = Cursor::new(buf);
+    let width = u32::from_le_bytes(cursor.array()?);
+    let len = u32::from_le_bytes(cursor.array()?);
+    let mut members = Vec::new();
+    for _ in 0..len {
+        let value = match width {
+            2 => i16::from_le_bytes(cursor.array()?) as i64,
+            4 => i32::from_le_bytes(cursor.array()?) as i64,
+            8 => i64::from_le_bytes(cursor.array()?),
+            _ => return Err(Errors::InvalidRdbFile),
+        };
+        members.push(int_entry(value));
+    }
+    Ok(members)
+}
+
+// zipmap：zmlen(1) | len | key | len | free | value | 空闲字节 ... | 0xFF
+fn parse_zipmap(buf: &[u8]) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    fn zipmap_len(cursor: &mut Cursor) -> Result<Option<usize>> {
+        match cursor.u8()? {
+            0xFF => Ok(None),
+            0xFE => Ok(Some(u32::from_le_bytes(cursor.array()?) as usize)),
+            len => Ok(Some(len as usize)),
+        }
+    }
+
+    let mut cursor = Cursor::new(buf);
+    cursor.u8()?;
+    let mut pairs = Vec::new();
+    while let Some(len) = zipmap_len(&mut cursor)? {
+        let field = cursor.take(len)?.to_vec();
+        let len = zipmap_len(&mut cursor)?.ok_or(Errors::InvalidRdbFile)?;
+        let free = cursor.u8()? as usize;
+        let value = cursor.take(len)?.to_vec();
+        cursor.take(free)?;
+        pairs.push((field, value));
+    }
+    Ok(pairs)
+}
+
+// 紧凑编码的 hash 中 field 和 value 交替存放
+fn into_pairs(entries: Vec<Vec<u8>>) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+    if !entries.len().is_multiple_of(2) {
+        return Err(Errors::InvalidRdbFile);
+    }
+    let mut iter = entries.into_iter();
+    let mut pairs = Vec::new();
+    while let (Some(field), Some(value)) = (iter.next(), iter.next()) {
+        pairs.push((field, value));
+    }
+    Ok(pairs)
+}
+
+// 紧凑编码的 zset 中 member 和 score 交替存放
+fn into_scores(entries: Vec<Vec<u8>>) -> Result<Vec<(Vec<u8>, f64)>> {
+    into_pairs(entries)?
+        .into_iter()
+        .map(|(member, score)| Ok((member, parse_score(&score)?)))
+        .collect()
+}
+
+fn parse_score(buf: &[u8]) -> Result<f64> {
+    std::str::from_utf8(buf)
+        .ok()
+        .and_then(|s| s.parse::<f64>().ok())
+        .ok_or(Errors::InvalidRdbFile)
+}
+
+// LZF 解压，控制字节小于 32 时后面是 ctrl + 1 字节的字面量，否则是对已解压数据的反向引用
+fn lzf_decompress(input: &[u8], len: usize) -> Result<Vec<u8>> {
+    let mut cursor = Cursor::new(input);
+    let mut output: Vec<u8> = Vec::new();
+    while cursor.pos < input.len() {
+        let ctrl = cursor.u8()? as usize;
+        if ctrl < 32 {
+            output.extend_from_slice(cursor.take(ctrl + 1)?);
+            continue;
+        }
+        let mut ref_len = ctrl >> 5;
+        if ref_len == 7 {
+            ref_len += cursor.u8()? as usize;
+        }
+        ref_len += 2;
+        let offset = ((ctrl & 0x1F) << 8 | cursor.u8()? as usize) + 1;
+        let start = output
+            .len()
+            .checked_sub(offset)
+            .ok_or(Errors::InvalidRdbFile)?;
+        // 引用的区间可能与正在写入的部分重叠，需要逐字节复制
+        for i in start..start + ref_len {
+            output.push(output[i]);
+        }
+    }
+    if output.len() != len {
+        return Err(Errors::InvalidRdbFile);
+    }
+    Ok(output)
+}
+
+// Redis 使用的 CRC64（Jones 多项式，反射输入输出）
+const CRC64_TABLE: [u64; 256] = {
+    let mut table = [0u64; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u64;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = match crc & 1 {
+                1 => (crc >> 1) ^ 0x95AC_9329_AC4B_C9B5,
+                _ => crc >> 1,
+            };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+// 从版本 5 开始文件末尾是 8 字节的 CRC64 校验和，为 0 表示生成时没有开启校验。
+// 检查完成之后 reader 回到文件的起始位置
+fn verify_checksum<R: Read + Seek>(reader: &mut R) -> Result<()> {
+    let size = reader.seek(SeekFrom::End(0)).map_err(read_error)?;
+    reader.rewind().map_err(read_error)?;
+    let mut rdb = RdbReader::new(&mut *reader);
+    let version = rdb.read_header()?;
+    if version >= 5 {
+        // 文件头、EOF 操作码以及校验和
+        if size < 9 + 1 + 8 {
+            return Err(Errors::InvalidRdbFile);
+        }
+        let mut remaining = size - 9 - 8;
+        while remaining > 0 {
+            let len = remaining.min(CHECKSUM_CHUNK_SIZE);
+            rdb.read_bytes(len)?;
+            remaining -= len;
+        }
+        let crc = rdb.crc;
+        let checksum = u64::from_le_bytes(rdb.read_array()?);
+        if checksum != 0 && checksum != crc {
+            return Err(Errors::RdbChecksumMismatch);
+        }
+    }
+    drop(rdb);
+    reader.rewind().map_err(read_error)
+}
+
+fn crc64(mut crc: u64, buf: &[u8]) -> u64 {
+    for &b in buf {
+        crc = CRC64_TABLE[((crc ^ b as u64) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::tests::open_structure;
+    use bytes::Bytes;
+    use std::fs;
+    use std::io::Cursor;
+    use std::time::Duration;
+
+    // 按照 Redis 的编码规则生成测试用的 RDB 文件
+    struct RdbFixture {
+        buf: Vec<u8>,
+    }
+
+    impl RdbFixture {
+        fn new(version: u32) -> Self {
+            Self {
+                buf: format!("REDIS{:04}", version).into_bytes(),
+            }
+        }
+
+        fn raw(&mut self, bytes: &[u8]) -> &mut Self {
+            self.buf.extend_from_slice(bytes);
+            self
+        }
+
+        fn length(&mut self, len: usize) -> &mut Self {
+            match len {
+                0..=63 => self.raw(&[len as u8]),
+                64..=16383 => self.raw(&[0x40 | (len >> 8) as u8, len as u8]),
+                _ => self.raw(&[0x80]).raw(&(len as u32).to_be_bytes()),
+            }
+        }
+
+        fn string(&mut self, s: &[u8]) -> &mut Self {
+            self.length(s.len()).raw(s)
+        }
+
+        fn strings(&mut self, values: &[&str]) -> &mut Self {
+            self.length(values.len());
+            for value in values {
+                self.string(value.as_bytes());
+            }
+            self
+        }
+
+        fn key(&mut self, value_type: u8, key: &str) -> &mut Self {
+            self.raw(&[value_type]).string(key.as_bytes())
+        }
+
+        fn finish(&mut self) -> Vec<u8> {
+            self.raw(&[OPCODE_EOF]);
+            let crc = crc64(0, &self.buf);
+            self.raw(&crc.to_le_bytes());
+            self.buf.clone()
+        }
+    }
+
+    // 生成 ziplist，能解析为整数的元素使用整数编码，元素长度都小于 64 字节
+    fn ziplist(entries: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+        let mut prev_len = 0;
+        for entry in entries {
+            let mut encoded = vec![prev_len as u8];
+            match entry.parse::<i64>() {
+                Ok(v @ 0..=12) => encoded.push(0xF1 + v as u8),
+                Ok(v) if i8::try_from(v).is_ok() => encoded.extend([0xFE, v as u8]),
+                Ok(v) if i16::try_from(v).is_ok() => {
+                    encoded.push(0xC0);
+                    encoded.extend((v as i16).to_le_bytes());
+                }
+                Ok(v) => {
+                    encoded.push(0xE0);
+                    encoded.extend(v.to_le_bytes());
+                }
+                Err(_) => {
+                    encoded.push(entry.len() as u8);
+                    encoded.extend(entry.as_bytes());
+                }
+            }
+            prev_len = encoded.len();
+            body.extend(encoded);
+        }
+        let mut buf = Vec::new();
+        buf.extend((10 + body.len() as u32 + 1).to_le_bytes());
+        buf.extend((10 + (body.len() - prev_len) as u32).to_le_bytes());
+        buf.extend((entries.len() as u16).to_le_bytes());
+        buf.extend(body);
+        buf.push(0xFF);
+        buf
+    }
+
+    // 生成 listpack，能解析为整数的元素使用整数编码，元素长度都小于 64 字节
+    fn listpack(entries: &[&str]) -> Vec<u8> {
+        let mut body = Vec::new();
+        for entry in entries {
+            let mut encoded = Vec::new();
+            match entry.parse::<i64>() {
+                Ok(v @ 0..=127) => encoded.push(v as u8),
+                Ok(v @ -4096..=4095) => {
+                    let v = (v & 0x1FFF) as u16;
+                    encoded.extend([0xC0 | (v >> 8) as u8, v as u8]);
+                }
+                Ok(v) => {
+                    encoded.push(0xF4);
+                    encoded.extend(v.to_le_bytes());
+                }
+                Err(_) => {
+                    encoded.push(0x80 | entry.len() as u8);
+                    encoded.extend(entry.as_bytes());
+                }
+            }
+            encoded.push(encoded.len() as u8);
+            body.extend(encoded);
+        }
+        let mut buf = Vec::new();
+        buf.extend((6 + body.len() as u32 + 1).to_le_bytes());
+        buf.extend((entries.len() as u16).to_le_bytes());
+        buf.extend(body);
+        buf.push(0xFF);
+        buf
+    }
+
+    fn intset(members: &[i16]) -> Vec<u8> {
+        let mut buf = Vec::new();
+        buf.extend(2u32.to_le_bytes());
+        buf.extend((members.len() as u32).to_le_bytes());
+        for member in members {
+            buf.extend(member.to_le_bytes());
+        }
+        buf
+    }
+
+    fn zipmap(pairs: &[(&str, &str)]) -> Vec<u8> {
+        let mut buf = vec![pairs.len() as u8];
+        for (field, value) in pairs {
+            buf.push(field.len() as u8);
+            buf.extend(field.as_bytes());
+            // 每个 value 之后留一个空闲字节
+            buf.extend([value.len() as u8, 1]);
+            buf.extend(value.as_bytes());
+            buf.push(0);
+        }
+        buf.push(0xFF);
+        buf
+    }
+
+    fn sample_rdb() -> Vec<u8> {
+        let hour_later = now_millis() + 3600 * 1000;
+        let mut rdb = RdbFixture::new(11);
+        rdb.raw(&[OPCODE_AUX])
+            .string(b"redis-ver")
+            .string(b"7.2.4")
+            .raw(&[OPCODE_AUX])
+            .string(b"ctime")
+            .raw(&[0xC2])
+            .raw(&1_700_000_000i32.to_le_bytes())
+            .raw(&[OPCODE_SELECTDB])
+            .length(0)
+            .raw(&[OPCODE_RESIZEDB])
+            .length(20)
+            .length(3);
+
+        // string
+        rdb.key(TYPE_STRING, "name").string(b"fdb");
+        rdb.key(TYPE_STRING, "counter")
+            .raw(&[0xC1])
+            .raw(&12345i16.to_le_bytes());
+        rdb.key(TYPE_STRING, "negative").raw(&[0xC0, (-7i8) as u8]);
+        // LZF 压缩：字面量 'a'，再引用前一个字节 9 次
+        rdb.key(TYPE_STRING, "compressed")
+            .raw(&[0xC3])
+            .length(5)
+            .length(10)
+            .raw(&[0x00, b'a', 0xE0, 0x00, 0x00]);
+        rdb.raw(&[OPCODE_EXPIRETIME_MS])
+            .raw(&hour_later.to_le_bytes())
+            .raw(&[OPCODE_FREQ, 3])
+            .key(TYPE_STRING, "session")
+            .string(b"token");
+        rdb.raw(&[OPCODE_EXPIRETIME_MS])
+            .raw(&1000u64.to_le_bytes())
+            .key(TYPE_STRING, "expired")
+            .string(b"gone");
+        rdb.raw(&[OPCODE_EXPIRETIME])
+            .raw(&((hour_later / 1000) as u32).to_le_bytes())
+            .raw(&[OPCODE_IDLE])
+            .length(10)
+            .key(TYPE_STRING, "legacy")
+            .string(b"seconds");
+
+        // list
+        rdb.key(TYPE_LIST_QUICKLIST_2, "list")
+            .length(2)
+            .length(QUICKLIST_NODE_PACKED as usize)
+            .string(&listpack(&["a", "b", "1", "-300"]))
+            .length(QUICKLIST_NODE_PLAIN as usize)
+            .string(b"plain");
+        rdb.key(TYPE_LIST_QUICKLIST, "old-list")
+            .length(1)
+            .string(&ziplist(&["x", "5", "-5", "300", "100000"]));
+        rdb.key(TYPE_LIST_ZIPLIST, "ziplist")
+            .string(&ziplist(&["z1", "z2"]));
+        rdb.key(TYPE_LIST, "linked").strings(&["l1", "l2"]);
+
+        // set
+        rdb.key(TYPE_SET, "set").strings(&["m1", "m2"]);
+        rdb.key(TYPE_SET_INTSET, "intset")
+            .string(&intset(&[-1, 2, 300]));
+        rdb.key(TYPE_SET_LISTPACK, "set-listpack")
+            .string(&listpack(&["s1", "7"]));
+
+        // hash
+        rdb.key(TYPE_HASH, "hash")
+            .length(2)
+            .string(b"f1")
+            .string(b"v1")
+            .string(b"f2")
+            .string(b"v2");
+        rdb.raw(&[OPCODE_EXPIRETIME_MS])
+            .raw(&hour_later.to_le_bytes())
+            .key(TYPE_HASH_LISTPACK, "hash-listpack")
+            .string(&listpack(&["f", "v", "n", "42"]));
+        rdb.key(TYPE_HASH_ZIPLIST, "hash-ziplist")
+            .string(&ziplist(&["f", "v"]));
+        rdb.key(TYPE_HASH_ZIPMAP, "hash-zipmap")
+            .string(&zipmap(&[("f", "v"), ("g", "w")]));
+
+        // zset
+        rdb.key(TYPE_ZSET_2, "zset")
+            .length(2)
+            .string(b"one")
+            .raw(&1.5f64.to_le_bytes())
+            .string(b"two")
+            .raw(&2.0f64.to_le_bytes());
+        rdb.key(TYPE_ZSET_LISTPACK, "zset-listpack")
+            .string(&listpack(&["a", "1", "b", "2.5"]));
+        rdb.key(TYPE_ZSET_ZIPLIST, "zset-ziplist")
+            .string(&ziplist(&["a", "-3"]));
+        rdb.key(TYPE_ZSET, "zset-old")
+            .length(2)
+            .string(b"low")
+            .raw(&[4])
+            .raw(b"-0.5")
+            .string(b"high")
+            .raw(&[254]);
+
+        // 其他逻辑数据库中的 key 导入到同一个命名空间
+        rdb.raw(&[OPCODE_SELECTDB])
+            .length(1)
+            .key(TYPE_STRING, "other-db")
+            .string(b"v");
+        rdb.finish()
+    }
+
+    fn strings(values: Vec<Bytes>) -> Vec<String> {
+        let mut values: Vec<_> = values
+            .into_iter()
+            .map(|v| String::from_utf8(v.to_vec()).unwrap())
+            .collect();
+        values.sort();
+        values
+    }
+
+    #[test]
+    fn test_import_rdb() {
+        let (rds, dir_path) = open_structure("fdb-structures-rdb");
+        // 已有的同名 key 被覆盖
+        rds.hset(b"name", b"f", b"v").unwrap();
+        rds.sadd(b"set", b"stale").unwrap();
+
+        let stat = rds.import_rdb(Cursor::new(sample_rdb())).unwrap();
+        assert_eq!(
+            stat,
+            RdbImportStat {
+                keys: 22,
+                expired: 1
+            }
+        );
+
+        let get = |key: &[u8]| rds.get(key).unwrap().map(|v| v.to_vec());
+        assert_eq!(get(b"name"), Some(b"fdb".to_vec()));
+        assert_eq!(get(b"counter"), Some(b"12345".to_vec()));
+        assert_eq!(get(b"negative"), Some(b"-7".to_vec()));
+        assert_eq!(get(b"compressed"), Some(vec![b'a'; 10]));
+        assert_eq!(get(b"session"), Some(b"token".to_vec()));
+        assert_eq!(get(b"expired"), None);
+        assert_eq!(get(b"legacy"), Some(b"seconds".to_vec()));
+        assert_eq!(get(b"other-db"), Some(b"v".to_vec()));
+        assert!(rds.ttl(b"session").unwrap().unwrap() > Duration::from_secs(3500));
+        assert!(rds.ttl(b"legacy").unwrap().unwrap() > Duration::from_secs(3500));
+        assert_eq!(rds.ttl(b"name").unwrap(), None);
+
+        let pop_all = |key: &[u8]| {
+            let mut elements = Vec::new();
+            while let Some(element) = rds.lpop(key).unwrap() {
+                elements.push(String::from_utf8(element.to_vec()).unwrap());
+            }
+            elements
+        };
+        assert_eq!(pop_all(b"list"), ["a", "b", "1", "-300", "plain"]);
+        assert_eq!(pop_all(b"old-list"), ["x", "5", "-5", "300", "100000"]);
+        assert_eq!(pop_all(b"ziplist"), ["z1", "z2"]);
+        assert_eq!(pop_all(b"linked"), ["l1", "l2"]);
+
+        assert_eq!(strings(rds.smembers(b"set").unwrap()), ["m1", "m2"]);
+        assert_eq!(rds.scard(b"set").unwrap(), 2);
+        assert_eq!(rds.hlen(b"hash-zipmap").unwrap(), 2);
+        assert_eq!(rds.zcard(b"zset-old").unwrap(), 2);
+        assert_eq!(
+            strings(rds.smembers(b"intset").unwrap()),
+            ["-1", "2", "300"]
+        );
+        assert_eq!(strings(rds.smembers(b"set-listpack").unwrap()), ["7", "s1"]);
+
+        let hash = |key: &[u8]| {
+            let mut pairs: Vec<_> = rds
+                .hgetall(key)
+                .unwrap()
+                .into_iter()
+                .map(|(f, v)| {
+                    format!(
+                        "{}={}",
+                        String::from_utf8_lossy(&f),
+                        String::from_utf8_lossy(&v)
+                    )
+                })
+                .collect();
+            pairs.sort();
+            pairs
+        };
+        assert_eq!(hash(b"hash"), ["f1=v1", "f2=v2"]);
+        assert_eq!(hash(b"hash-listpack"), ["f=v", "n=42"]);
+        assert_eq!(hash(b"hash-ziplist"), ["f=v"]);
+        assert_eq!(hash(b"hash-zipmap"), ["f=v", "g=w"]);
+        assert!(rds.ttl(b"hash-listpack").unwrap().unwrap() > Duration::from_secs(3500));
+
+        let zset = |key: &[u8]| {
+            rds.zrange(key, 0, -1)
+                .unwrap()
+                .into_iter()
+                .map(|(m, s)| (String::from_utf8(m.to_vec()).unwrap(), s))
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(
+            zset(b"zset"),
+            [("one".to_string(), 1.5), ("two".to_string(), 2.0)]
+        );
+        assert_eq!(
+            zset(b"zset-listpack"),
+            [("a".to_string(), 1.0), ("b".to_string(), 2.5)]
+        );
+        assert_eq!(zset(b"zset-ziplist"), [("a".to_string(), -3.0)]);
+        assert_eq!(
+            zset(b"zset-old"),
+            [
+                ("low".to_string(), -0.5),
+                ("high".to_string(), f64::INFINITY)
+            ]
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_import_rdb_invalid() {
+        let (rds, dir_path) = open_structure("fdb-structures-rdb-invalid");
+
+        // Redis 源码中 crc64 的测试向量
+        assert_eq!(crc64(0, b"123456789"), 0xE9C6_D914_C4B8_D9CA);
+
+        let res = rds.import_rdb(Cursor::new(b"RIDES0011\xFF"));
+        assert_eq!(res, Err(Errors::InvalidRdbFile));
+        let res = rds.import_rdb(Cursor::new(RdbFixture::new(99).finish()));
+        assert_eq!(res, Err(Errors::UnsupportedRdbVersion));
+
+        let mut rdb = sample_rdb();
+        let len = rdb.len();
+        rdb[len - 1] ^= 0xFF;
+        assert_eq!(
+            rds.import_rdb(Cursor::new(&rdb)),
+            Err(Errors::RdbChecksumMismatch)
+        );
+        // 校验失败时没有导入任何数据
+        assert_eq!(rds.get(b"name").unwrap(), None);
+        rdb.truncate(len - 20);
+        assert_eq!(
+            rds.import_rdb(Cursor::new(&rdb)),
+            Err(Errors::RdbChecksumMismatch)
+        );
+        assert_eq!(
+            rds.import_rdb(Cursor::new(b"REDIS0011\xFF")),
+            Err(Errors::InvalidRdbFile)
+        );
+
+        // 校验和为 0 时不校验
+        let mut rdb = RdbFixture::new(9);
+        rdb.key(TYPE_STRING, "k")
+            .string(b"v")
+            .raw(&[OPCODE_EOF, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let stat = rds.import_rdb(Cursor::new(&rdb.buf)).unwrap();
+        assert_eq!(stat.keys, 1);
+
+        // stream 不支持
+        let rdb = RdbFixture::new(11).key(15, "stream").finish();
+        assert_eq!(
+            rds.import_rdb(Cursor::new(&rdb)),
+            Err(Errors::UnsupportedRdbType)
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    // 由 redis-server 生成的 dump 文件，需要先运行 tests/fixtures/generate_rdb.sh
+    #[test]
+    #[ignore]
+    fn test_import_redis_dump() {
+        let path = std::path::Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("tests")
+            .join("fixtures")
+            .join("redis-7.rdb");
+        let (rds, dir_path) = open_structure("fdb-structures-rdb-redis");
+        let stat = rds.import_rdb(fs::File::open(path).unwrap()).unwrap();
+        assert_eq!(
+            stat,
+            RdbImportStat {
+                keys: 13,
+                expired: 0
+            }
+        );
+
+        let get = |key: &[u8]| rds.get(key).unwrap().map(|v| v.to_vec());
+        assert_eq!(get(b"int"), Some(b"12345".to_vec()));
+        assert_eq!(get(b"name"), Some(b"fdb".to_vec()));
+        assert_eq!(get(b"lzf"), Some(vec![b'a'; 100]));
+        assert_eq!(get(b"session"), Some(b"token".to_vec()));
+        assert!(rds.ttl(b"session").unwrap().is_some());
+
+        // quicklist 2
+        assert_eq!(rds.llen(b"list").unwrap(), 4);
+        assert_eq!(rds.lpop(b"list").unwrap().unwrap(), "a");
+        assert_eq!(rds.rpop(b"list").unwrap().unwrap(), "-300");
+        assert_eq!(rds.llen(b"list-large").unwrap(), 1000);
+        assert_eq!(rds.lpop(b"list-large").unwrap().unwrap(), "e1");
+        assert_eq!(rds.rpop(b"list-large").unwrap().unwrap(), "e1000");
+
+        // intset、listpack、hashtable
+        assert_eq!(
+            strings(rds.smembers(b"intset").unwrap()),
+            ["-1", "1", "2", "300"]
+        );
+        assert_eq!(
+            strings(rds.smembers(b"set-listpack").unwrap()),
+            ["7", "s1", "s2"]
+        );
+        assert_eq!(rds.scard(b"set-large").unwrap(), 200);
+        assert!(rds.sismember(b"set-large", b"m200").unwrap());
+
+        assert_eq!(rds.hget(b"hash-listpack", b"n").unwrap().unwrap(), "42");
+        assert_eq!(rds.hlen(b"hash-listpack").unwrap(), 2);
+        assert_eq!(rds.hlen(b"hash-large").unwrap(), 200);
+        assert_eq!(rds.hget(b"hash-large", b"f7").unwrap().unwrap(), "v7");
+
+        assert_eq!(
+            rds.zrange(b"zset-listpack", 0, -1).unwrap(),
+            [(Bytes::from("a"), 1.0), (Bytes::from("b"), 2.5)]
+        );
+        assert_eq!(rds.zcard(b"zset-large").unwrap(), 200);
+        assert_eq!(rds.zscore(b"zset-large", b"m42").unwrap(), Some(42.0));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
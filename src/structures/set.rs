@@ -0,0 +1,111 @@
+use crate::errors::Result;
+use crate::options::IteratorOptions;
+use crate::structures::{data_key, DataType, RedisDataStructure};
+use bytes::Bytes;
+
+impl RedisDataStructure {
+    /// 向集合中添加元素，元素不存在时返回 true
+    pub fn sadd(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = self.find_or_new_meta(key, DataType::Set)?;
+            let member_key = data_key(key, meta.version, member);
+            if self.get_data(member_key.clone())?.is_some() {
+                return Ok(false);
+            }
+
+            batch.put(member_key, Bytes::new())?;
+            meta.size += 1;
+            self.put_meta(batch, key, &meta)?;
+            Ok(true)
+        })
+    }
+
+    /// 判断元素是否在集合中
+    pub fn sismember(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        match self.find_typed_meta(key, DataType::Set)? {
+            Some(meta) => Ok(self
+                .get_data(data_key(key, meta.version, member))?
+                .is_some()),
+            None => Ok(false),
+        }
+    }
+
+    /// 从集合中删除元素，元素存在时返回 true
+    pub fn srem(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = match self.find_typed_meta(key, DataType::Set)? {
+                Some(meta) => meta,
+                None => return Ok(false),
+            };
+            let member_key = data_key(key, meta.version, member);
+            if self.get_data(member_key.clone())?.is_none() {
+                return Ok(false);
+            }
+
+            meta.size -= 1;
+            self.put_meta(batch, key, &meta)?;
+            batch.delete(member_key)?;
+            Ok(true)
+        })
+    }
+
+    /// 集合中元素的个数
+    pub fn scard(&self, key: &[u8]) -> Result<u32> {
+        let meta = self.find_typed_meta(key, DataType::Set)?;
+        Ok(meta.map_or(0, |meta| meta.size))
+    }
+
+    /// 集合中所有的元素，按字节序排序
+    pub fn smembers(&self, key: &[u8]) -> Result<Vec<Bytes>> {
+        let meta = match self.find_typed_meta(key, DataType::Set)? {
+            Some(meta) => meta,
+            None => return Ok(Vec::new()),
+        };
+        let prefix = data_key(key, meta.version, &[]);
+        let iter = self.engine.iter(IteratorOptions {
+            prefix: prefix.to_vec(),
+            reverse: false,
+        });
+        let mut members = Vec::new();
+        while let Some((member_key, _)) = iter.try_next()? {
+            members.push(member_key.slice(prefix.len()..));
+        }
+        Ok(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::structures::tests::open_structure;
+    use bytes::Bytes;
+    use std::fs;
+
+    #[test]
+    fn test_set_add_members_rem() {
+        let (rds, dir_path) = open_structure("fdb-structures-set");
+        assert!(!rds.sismember(b"tags", b"db").unwrap());
+
+        assert!(rds.sadd(b"tags", b"kv").unwrap());
+        assert!(rds.sadd(b"tags", b"db").unwrap());
+        assert!(!rds.sadd(b"tags", b"kv").unwrap());
+        assert!(rds.sismember(b"tags", b"db").unwrap());
+        assert_eq!(rds.scard(b"tags").unwrap(), 2);
+        assert_eq!(
+            rds.smembers(b"tags").unwrap(),
+            vec![Bytes::from("db"), Bytes::from("kv")]
+        );
+
+        assert!(rds.srem(b"tags", b"db").unwrap());
+        assert!(!rds.srem(b"tags", b"db").unwrap());
+        assert!(!rds.sismember(b"tags", b"db").unwrap());
+        assert_eq!(rds.smembers(b"tags").unwrap(), vec![Bytes::from("kv")]);
+
+        // 整体删除之后重新添加
+        assert!(rds.del(b"tags").unwrap());
+        assert!(rds.smembers(b"tags").unwrap().is_empty());
+        assert!(rds.sadd(b"tags", b"kv").unwrap());
+        assert_eq!(rds.scard(b"tags").unwrap(), 1);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,227 @@
+use crate::errors::{Errors, Result};
+use crate::options::IteratorOptions;
+use crate::structures::{data_key, DataType, RedisDataStructure};
+use bytes::{BufMut, Bytes, BytesMut};
+
+// 有序集合中每个成员有两条记录：
+//   'm' + member          => score，用于按成员查找分数
+//   's' + score + member  => 空，分数编码后按字节序即为数值顺序，用于范围查询
+const ZSET_MEMBER_PREFIX: u8 = b'm';
+const ZSET_SCORE_PREFIX: u8 = b's';
+
+impl RedisDataStructure {
+    /// 添加成员或者更新成员的分数，成员不存在时返回 true
+    pub fn zadd(&self, key: &[u8], score: f64, member: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = self.find_or_new_meta(key, DataType::ZSet)?;
+            let member_key = data_key(key, meta.version, &zset_member_sub_key(member));
+            let old_score = self
+                .get_data(member_key.clone())?
+                .map(decode_score)
+                .transpose()?;
+
+            if let Some(old_score) = old_score {
+                if old_score == score {
+                    return Ok(false);
+                }
+                batch.delete(data_key(
+                    key,
+                    meta.version,
+                    &zset_score_sub_key(old_score, member),
+                ))?;
+            }
+            batch.put(
+                data_key(key, meta.version, &zset_score_sub_key(score, member)),
+                Bytes::new(),
+            )?;
+            batch.put(member_key, Bytes::copy_from_slice(&score.to_be_bytes()))?;
+
+            if old_score.is_none() {
+                meta.size += 1;
+                self.put_meta(batch, key, &meta)?;
+            }
+            Ok(old_score.is_none())
+        })
+    }
+
+    /// 获取成员的分数
+    pub fn zscore(&self, key: &[u8], member: &[u8]) -> Result<Option<f64>> {
+        let meta = match self.find_typed_meta(key, DataType::ZSet)? {
+            Some(meta) => meta,
+            None => return Ok(None),
+        };
+        self.get_data(data_key(key, meta.version, &zset_member_sub_key(member)))?
+            .map(decode_score)
+            .transpose()
+    }
+
+    /// 删除成员，成员存在时返回 true
+    pub fn zrem(&self, key: &[u8], member: &[u8]) -> Result<bool> {
+        self.update(|batch| {
+            let mut meta = match self.find_typed_meta(key, DataType::ZSet)? {
+                Some(meta) => meta,
+                None => return Ok(false),
+            };
+            let member_key = data_key(key, meta.version, &zset_member_sub_key(member));
+            let score = match self.get_data(member_key.clone())? {
+                Some(value) => decode_score(value)?,
+                None => return Ok(false),
+            };
+
+            meta.size -= 1;
+            self.put_meta(batch, key, &meta)?;
+            batch.delete(member_key)?;
+            batch.delete(data_key(
+                key,
+                meta.version,
+                &zset_score_sub_key(score, member),
+            ))?;
+            Ok(true)
+        })
+    }
+
+    /// 有序集合中成员的个数
+    pub fn zcard(&self, key: &[u8]) -> Result<u32> {
+        let meta = self.find_typed_meta(key, DataType::ZSet)?;
+        Ok(meta.map_or(0, |meta| meta.size))
+    }
+
+    /// 按分数从小到大返回下标在 [start, stop] 之间的成员，负数下标从末尾开始计算
+    pub fn zrange(&self, key: &[u8], start: i64, stop: i64) -> Result<Vec<(Bytes, f64)>> {
+        let meta = match self.find_typed_meta(key, DataType::ZSet)? {
+            Some(meta) => meta,
+            None => return Ok(Vec::new()),
+        };
+        let size = meta.size as i64;
+        let start = if start < 0 {
+            (size + start).max(0)
+        } else {
+            start
+        };
+        let stop = if stop < 0 {
+            size + stop
+        } else {
+            stop.min(size - 1)
+        };
+        if start > stop || start >= size {
+            return Ok(Vec::new());
+        }
+
+        let prefix = data_key(key, meta.version, &[ZSET_SCORE_PREFIX]);
+        let iter = self.engine.iter(IteratorOptions {
+            prefix: prefix.to_vec(),
+            reverse: false,
+        });
+        let mut members = Vec::with_capacity((stop - start + 1) as usize);
+        let mut index = 0;
+        while let Some((score_key, _)) = iter.try_next()? {
+            if index > stop {
+                break;
+            }
+            if index >= start {
+                let sub_key = score_key.slice(prefix.len()..);
+                let score = decode_sortable_score(&sub_key[..8]);
+                members.push((sub_key.slice(8..), score));
+            }
+            index += 1;
+        }
+        Ok(members)
+    }
+}
+
+pub(crate) fn zset_member_sub_key(member: &[u8]) -> Vec<u8> {
+    let mut buf = Vec::with_capacity(member.len() + 1);
+    buf.push(ZSET_MEMBER_PREFIX);
+    buf.extend_from_slice(member);
+    buf
+}
+
+pub(crate) fn zset_score_sub_key(score: f64, member: &[u8]) -> Bytes {
+    let mut buf = BytesMut::with_capacity(1 + 8 + member.len());
+    buf.put_u8(ZSET_SCORE_PREFIX);
+    buf.put_u64(encode_sortable_score(score));
+    buf.extend_from_slice(member);
+    buf.freeze()
+}
+
+// 将 f64 编码为按字节序比较即为数值顺序的 u64
+fn encode_sortable_score(score: f64) -> u64 {
+    let bits = score.to_bits();
+    match bits >> 63 {
+        0 => bits | (1 << 63),
+        _ => !bits,
+    }
+}
+
+fn decode_sortable_score(buf: &[u8]) -> f64 {
+    let bits = u64::from_be_bytes(buf.try_into().unwrap());
+    match bits >> 63 {
+        1 => f64::from_bits(bits & !(1 << 63)),
+        _ => f64::from_bits(!bits),
+    }
+}
+
+fn decode_score(value: Bytes) -> Result<f64> {
+    let buf: [u8; 8] = value
+        .as_ref()
+        .try_into()
+        .map_err(|_| Errors::InvalidDataStructureMetadata)?;
+    Ok(f64::from_be_bytes(buf))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::structures::tests::open_structure;
+    use std::fs;
+
+    #[test]
+    fn test_sortable_score() {
+        let scores = [f64::NEG_INFINITY, -10.5, -1.0, -0.0, 0.0, 0.5, 3.0, 1e10];
+        for pair in scores.windows(2) {
+            assert!(encode_sortable_score(pair[0]) <= encode_sortable_score(pair[1]));
+        }
+        for score in scores {
+            let buf = encode_sortable_score(score).to_be_bytes();
+            assert_eq!(decode_sortable_score(&buf), score);
+        }
+    }
+
+    #[test]
+    fn test_zset_add_range_rem() {
+        let (rds, dir_path) = open_structure("fdb-structures-zset");
+        assert_eq!(rds.zscore(b"rank", b"a").unwrap(), None);
+
+        assert!(rds.zadd(b"rank", 3.0, b"c").unwrap());
+        assert!(rds.zadd(b"rank", -1.5, b"a").unwrap());
+        assert!(rds.zadd(b"rank", 2.0, b"b").unwrap());
+        // 更新分数
+        assert!(!rds.zadd(b"rank", 10.0, b"a").unwrap());
+        assert_eq!(rds.zscore(b"rank", b"a").unwrap(), Some(10.0));
+        assert_eq!(rds.zcard(b"rank").unwrap(), 3);
+
+        assert_eq!(
+            rds.zrange(b"rank", 0, -1).unwrap(),
+            vec![
+                (Bytes::from("b"), 2.0),
+                (Bytes::from("c"), 3.0),
+                (Bytes::from("a"), 10.0),
+            ]
+        );
+        assert_eq!(
+            rds.zrange(b"rank", -2, 100).unwrap(),
+            vec![(Bytes::from("c"), 3.0), (Bytes::from("a"), 10.0)]
+        );
+        assert!(rds.zrange(b"rank", 2, 1).unwrap().is_empty());
+
+        assert!(rds.zrem(b"rank", b"c").unwrap());
+        assert!(!rds.zrem(b"rank", b"c").unwrap());
+        assert_eq!(rds.zcard(b"rank").unwrap(), 2);
+        assert_eq!(
+            rds.zrange(b"rank", 0, -1).unwrap(),
+            vec![(Bytes::from("b"), 2.0), (Bytes::from("a"), 10.0)]
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
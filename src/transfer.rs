@@ -0,0 +1,394 @@
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::options::IteratorOptions;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine as _;
+use bytes::Bytes;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::str::FromStr;
+
+/// import 默认每个批量提交的数据条数
+pub const DEFAULT_IMPORT_BATCH_SIZE: usize = 1000;
+// CSV 的表头，encoding 为 base64 时 key 和 value 都使用 base64 编码
+const CSV_HEADER: [&str; 3] = ["key", "value", "encoding"];
+const BASE64_ENCODING: &str = "base64";
+
+/// 导入导出的数据格式
+///
+/// JSON Lines 每行一个对象，key 和 value 是文本时直接保存为字符串，
+/// 二进制数据保存到 key_base64、value_base64 字段中；
+/// CSV 的列为 key、value、encoding，key 或 value 是二进制数据时两者都使用 base64 编码。
+/// 合法的 UTF-8 并且不含换行和制表符以外的控制字符的数据视为文本。
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    JsonLines,
+    Csv,
+}
+
+impl FromStr for Format {
+    type Err = Errors;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "jsonl" | "json" => Ok(Format::JsonLines),
+            "csv" => Ok(Format::Csv),
+            _ => Err(Errors::UnsupportedFormat),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct JsonRecord {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    key_base64: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    value_base64: Option<String>,
+}
+
+impl Engine {
+    /// 把 key 以 prefix 开头的数据按 key 的顺序导出到 writer，返回导出的数据条数
+    pub fn export<W: Write>(&self, writer: W, format: Format, prefix: &[u8]) -> Result<usize> {
+        let iter = self.iter(IteratorOptions {
+            prefix: prefix.to_vec(),
+            reverse: false,
+        });
+        let mut exporter = Exporter::new(writer, format);
+        let mut count = 0;
+        while let Some((key, value)) = iter.try_next()? {
+            exporter.write(&key, &value)?;
+            count += 1;
+        }
+        exporter.finish()?;
+        Ok(count)
+    }
+
+    /// 从 reader 导入数据，返回导入的数据条数
+    pub fn import<R: Read>(&self, reader: R, format: Format) -> Result<usize> {
+        self.import_with_progress(reader, format, DEFAULT_IMPORT_BATCH_SIZE, |_| {})
+    }
+
+    /// 从 reader 导入数据，每 batch_size 条数据作为一个批量原子提交
+    ///
+    /// 每次提交之后用已经导入的数据条数调用 progress。
+    /// 遇到格式错误时返回 InvalidImportRecord，之前已经提交的批量仍然保留。
+    pub fn import_with_progress<R, F>(
+        &self,
+        reader: R,
+        format: Format,
+        batch_size: usize,
+        mut progress: F,
+    ) -> Result<usize>
+    where
+        R: Read,
+        F: FnMut(usize),
+    {
+        let mut importer = Importer::new(reader, format);
+        let mut batch = self.write_batch();
+        let mut imported = 0;
+        while let Some((key, value)) = importer.next_record()? {
+            batch.put(key, value)?;
+            if batch.len() >= batch_size.max(1) {
+                imported += batch.len();
+                batch.commit()?;
+                progress(imported);
+            }
+        }
+        if !batch.is_empty() {
+            imported += batch.len();
+            batch.commit()?;
+            progress(imported);
+        }
+        Ok(imported)
+    }
+}
+
+enum Exporter<W: Write> {
+    JsonLines(BufWriter<W>),
+    Csv(Box<csv::Writer<W>>, bool),
+}
+
+impl<W: Write> Exporter<W> {
+    fn new(writer: W, format: Format) -> Self {
+        match format {
+            Format::JsonLines => Exporter::JsonLines(BufWriter::new(writer)),
+            Format::Csv => Exporter::Csv(Box::new(csv::Writer::from_writer(writer)), false),
+        }
+    }
+
+    fn write(&mut self, key: &[u8], value: &[u8]) -> Result<()> {
+        let res = match self {
+            Exporter::JsonLines(writer) => {
+                let mut record = JsonRecord::default();
+                match as_text(key) {
+                    Some(key) => record.key = Some(key.to_string()),
+                    None => record.key_base64 = Some(BASE64.encode(key)),
+                }
+                match as_text(value) {
+                    Some(value) => record.value = Some(value.to_string()),
+                    None => record.value_base64 = Some(BASE64.encode(value)),
+                }
+                serde_json::to_writer(&mut *writer, &record)
+                    .map_err(std::io::Error::from)
+                    .and_then(|_| writer.write_all(b"\n"))
+            }
+            Exporter::Csv(writer, header_written) => {
+                if !*header_written {
+                    *header_written = true;
+                    if let Err(e) = writer.write_record(CSV_HEADER) {
+                        warn!("write export err:{}", e);
+                        return Err(Errors::FailedToWriteExport);
+                    }
+                }
+                let res = match (as_text(key), as_text(value)) {
+                    (Some(key), Some(value)) => writer.write_record([key, value, ""]),
+                    _ => writer.write_record([
+                        BASE64.encode(key).as_str(),
+                        BASE64.encode(value).as_str(),
+                        BASE64_ENCODING,
+                    ]),
+                };
+                res.map_err(std::io::Error::from)
+            }
+        };
+        if let Err(e) = res {
+            warn!("write export err:{}", e);
+            return Err(Errors::FailedToWriteExport);
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> Result<()> {
+        let res = match self {
+            Exporter::JsonLines(mut writer) => writer.flush(),
+            // 没有数据时也写入表头
+            Exporter::Csv(mut writer, header_written) => {
+                let res = match header_written {
+                    true => Ok(()),
+                    false => writer.write_record(CSV_HEADER),
+                };
+                res.map_err(std::io::Error::from)
+                    .and_then(|_| writer.flush())
+            }
+        };
+        if let Err(e) = res {
+            warn!("write export err:{}", e);
+            return Err(Errors::FailedToWriteExport);
+        }
+        Ok(())
+    }
+}
+
+enum Importer<R: Read> {
+    JsonLines(BufReader<R>, usize),
+    Csv(Box<csv::Reader<R>>),
+}
+
+impl<R: Read> Importer<R> {
+    fn new(reader: R, format: Format) -> Self {
+        match format {
+            Format::JsonLines => Importer::JsonLines(BufReader::new(reader), 0),
+            Format::Csv => Importer::Csv(Box::new(
+                csv::ReaderBuilder::new().flexible(true).from_reader(reader),
+            )),
+        }
+    }
+
+    // 读取下一条数据，读完时返回 None
+    fn next_record(&mut self) -> Result<Option<(Bytes, Bytes)>> {
+        match self {
+            Importer::JsonLines(reader, line_no) => loop {
+                let mut line = String::new();
+                match reader.read_line(&mut line) {
+                    Ok(0) => return Ok(None),
+                    Ok(_) => *line_no += 1,
+                    Err(e) => {
+                        warn!("read import err:{}", e);
+                        return Err(Errors::FailedToReadImport);
+                    }
+                }
+                // 跳过空行
+                if line.trim().is_empty() {
+                    continue;
+                }
+                return match decode_json_record(&line) {
+                    Some(record) => Ok(Some(record)),
+                    None => {
+                        warn!("invalid import record at line {}", line_no);
+                        Err(Errors::InvalidImportRecord)
+                    }
+                };
+            },
+            Importer::Csv(reader) => {
+                let mut record = csv::StringRecord::new();
+                match reader.read_record(&mut record) {
+                    Ok(false) => return Ok(None),
+                    Ok(true) => {}
+                    Err(e) => {
+                        warn!("read import err:{}", e);
+                        return Err(Errors::FailedToReadImport);
+                    }
+                }
+                match decode_csv_record(&record) {
+                    Some(record) => Ok(Some(record)),
+                    None => {
+                        let line = record.position().map_or(0, |pos| pos.line());
+                        warn!("invalid import record at line {}", line);
+                        Err(Errors::InvalidImportRecord)
+                    }
+                }
+            }
+        }
+    }
+}
+
+// 不含控制字符的 UTF-8 数据作为文本导出，其余的作为二进制数据使用 base64 编码
+fn as_text(data: &[u8]) -> Option<&str> {
+    let text = std::str::from_utf8(data).ok()?;
+    let binary = text
+        .chars()
+        .any(|c| c.is_control() && !matches!(c, '\t' | '\n' | '\r'));
+    (!binary).then_some(text)
+}
+
+fn decode_json_record(line: &str) -> Option<(Bytes, Bytes)> {
+    let record: JsonRecord = serde_json::from_str(line).ok()?;
+    let key = decode_json_field(record.key, record.key_base64)?;
+    let value = decode_json_field(record.value, record.value_base64)?;
+    Some((key, value))
+}
+
+// 字符串和 base64 两种形式必须有且只有一种
+fn decode_json_field(text: Option<String>, encoded: Option<String>) -> Option<Bytes> {
+    match (text, encoded) {
+        (Some(text), None) => Some(Bytes::from(text)),
+        (None, Some(encoded)) => BASE64.decode(encoded).ok().map(Bytes::from),
+        _ => None,
+    }
+}
+
+fn decode_csv_record(record: &csv::StringRecord) -> Option<(Bytes, Bytes)> {
+    let (key, value) = (record.get(0)?, record.get(1)?);
+    match record.get(2).unwrap_or("") {
+        "" => Some((Bytes::from(key.to_string()), Bytes::from(value.to_string()))),
+        BASE64_ENCODING => Some((
+            Bytes::from(BASE64.decode(key).ok()?),
+            Bytes::from(BASE64.decode(value).ok()?),
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+
+    fn open_engine(name: &str) -> (Engine, std::path::PathBuf) {
+        let dir_path = std::env::temp_dir().join(name);
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        (engine, dir_path)
+    }
+
+    #[test]
+    fn test_export_import() {
+        let (source, source_path) = open_engine("fdb-export-source");
+        source
+            .put(Bytes::from("user:1"), Bytes::from("alice, \"admin\""))
+            .unwrap();
+        source
+            .put(Bytes::from("user:2"), Bytes::from("bob\nsecond line"))
+            .unwrap();
+        source
+            .put(
+                Bytes::from(&b"user:\xff"[..]),
+                Bytes::from(&b"\x00\x01"[..]),
+            )
+            .unwrap();
+        source
+            .put(Bytes::from("order:1"), Bytes::from("book"))
+            .unwrap();
+
+        for format in [Format::JsonLines, Format::Csv] {
+            let mut buf = Vec::new();
+            assert_eq!(source.export(&mut buf, format, b"user:").unwrap(), 3);
+
+            let (target, target_path) = open_engine("fdb-export-target");
+            let mut reported = Vec::new();
+            let imported = target
+                .import_with_progress(buf.as_slice(), format, 2, |n| reported.push(n))
+                .unwrap();
+            assert_eq!(imported, 3);
+            assert_eq!(reported, vec![2, 3]);
+            for key in source.list_keys().unwrap() {
+                match key.starts_with(b"user:") {
+                    true => assert_eq!(target.get(key.clone()), source.get(key)),
+                    false => assert_eq!(target.get(key), Err(Errors::KeyNotFound)),
+                }
+            }
+            drop(target);
+            fs::remove_dir_all(target_path).unwrap();
+        }
+
+        let mut buf = Vec::new();
+        source
+            .export(&mut buf, Format::JsonLines, b"order:")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"key\":\"order:1\",\"value\":\"book\"}\n"
+        );
+        let mut buf = Vec::new();
+        source
+            .export(&mut buf, Format::JsonLines, b"user:\xff")
+            .unwrap();
+        assert_eq!(
+            String::from_utf8(buf).unwrap(),
+            "{\"key_base64\":\"dXNlcjr/\",\"value_base64\":\"AAE=\"}\n"
+        );
+        let mut buf = Vec::new();
+        source.export(&mut buf, Format::Csv, b"none:").unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), "key,value,encoding\n");
+
+        fs::remove_dir_all(source_path).unwrap();
+    }
+
+    #[test]
+    fn test_import_invalid_record() {
+        let (engine, dir_path) = open_engine("fdb-import-invalid");
+
+        // 出错之前已经提交的批量保留，出错的批量不会写入
+        let input = "{\"key\":\"a\",\"value\":\"1\"}\n\n{\"key\":\"b\",\"value\":\"2\"}\n\
+                     {\"key\":\"c\",\"value\":\"3\"}\n{\"key\":\"d\"}\n";
+        assert_eq!(
+            engine.import_with_progress(input.as_bytes(), Format::JsonLines, 2, |_| {}),
+            Err(Errors::InvalidImportRecord)
+        );
+        assert_eq!(engine.get(Bytes::from("b")).unwrap(), "2");
+        assert_eq!(engine.get(Bytes::from("c")), Err(Errors::KeyNotFound));
+
+        // 表格软件导出的 CSV 可以没有 encoding 列
+        let input = "key,value\nx,1\ny,2\n";
+        assert_eq!(engine.import(input.as_bytes(), Format::Csv).unwrap(), 2);
+        assert_eq!(engine.get(Bytes::from("y")).unwrap(), "2");
+        let input = "key,value,encoding\nz,1,hex\n";
+        assert_eq!(
+            engine.import(input.as_bytes(), Format::Csv),
+            Err(Errors::InvalidImportRecord)
+        );
+        assert_eq!("xml".parse::<Format>(), Err(Errors::UnsupportedFormat));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+use crate::data::blob_file::BlobRef;
+use crate::data::log_record::LogRecordType;
+use crate::db::Engine;
+use crate::errors::{Errors, Result};
+use crate::structures::now_millis;
+use bytes::Bytes;
+use std::io::{self, Read, Seek, SeekFrom};
+
+// value 所在的位置
+enum Source {
+    Data { file_id: u32, offset: u64 }, // 未压缩的 value，直接从数据文件中读取
+    Blob { file_id: u32, offset: u64 }, // 存放在 blob 文件中的 value
+    Memory(Bytes), // 压缩的 value 需要整体解压，合并操作数需要合并，读取时已经校验过
+}
+
+/// 流式读取 value，不需要把整个 value 读入内存
+///
+/// 从头顺序读取时增量计算 CRC，读到末尾时校验，校验失败返回 InvalidData 错误。
+/// 调用 seek 跳转到其他位置后不再校验 CRC。
+pub struct ValueReader<'a> {
+    engine: &'a Engine,
+    source: Source,
+    len: u64,
+    pos: u64,
+    hasher: Option<crc32fast::Hasher>,
+    crc: u32,
+}
+
+impl Engine {
+    /// 获取 key 对应 value 的流式读取器
+    pub fn get_reader(&self, key: Bytes) -> Result<ValueReader<'_>> {
+        if key.is_empty() {
+            return Err(Errors::KeyIsEmpty);
+        }
+        let Some(pos) = self.index.get(key.to_vec()) else {
+            return Err(Errors::KeyNotFound);
+        };
+        let header = self.with_data_file(pos.file_id, |data_file| {
+            data_file.read_log_record_header(pos.offset)
+        })?;
+        // 已过期的记录视为不存在
+        if header.expire != 0 && header.expire <= now_millis() {
+            return Err(Errors::KeyNotFound);
+        }
+
+        let reader = match header.rec_type() {
+            LogRecordType::DELETE | LogRecordType::BATCH => return Err(Errors::KeyNotFound),
+            LogRecordType::NORMAL if header.compression_type() == 0 => {
+                // 日志记录的 CRC 包含 header 和 key
+                let mut hasher = crc32fast::Hasher::new();
+                hasher.update(&header.header);
+                hasher.update(&header.key);
+                ValueReader {
+                    engine: self,
+                    source: Source::Data {
+                        file_id: pos.file_id,
+                        offset: header.value_offset,
+                    },
+                    len: header.value_size as u64,
+                    pos: 0,
+                    hasher: Some(hasher),
+                    crc: header.crc,
+                }
+            }
+            LogRecordType::NORMAL | LogRecordType::MERGE => {
+                let value = self.get_value_by_position(&pos)?;
+                ValueReader {
+                    engine: self,
+                    len: value.len() as u64,
+                    source: Source::Memory(value),
+                    pos: 0,
+                    hasher: None,
+                    crc: 0,
+                }
+            }
+            LogRecordType::BLOB => {
+                let record = self.read_log_record_at(&pos)?.record;
+                let blob_ref = BlobRef::decode(&record.value)?;
+                ValueReader {
+                    engine: self,
+                    source: Source::Blob {
+                        file_id: blob_ref.file_id,
+                        offset: blob_ref.offset,
+                    },
+                    len: blob_ref.len,
+                    pos: 0,
+                    hasher: Some(crc32fast::Hasher::new()),
+                    crc: blob_ref.crc,
+                }
+            }
+        };
+        Ok(reader)
+    }
+
+    /// 读取 value 中从 offset 开始、最多 len 个字节的数据，超出 value 末尾的部分被忽略
+    ///
+    /// 只有读取完整的 value 时才会校验 CRC，需要校验时请使用 get 或者 get_reader。
+    pub fn get_range(&self, key: Bytes, offset: u64, len: usize) -> Result<Bytes> {
+        let mut reader = self.get_reader(key)?;
+        if offset != 0 {
+            reader.pos = offset;
+            reader.hasher = None;
+        }
+        let len = len.min(reader.len.saturating_sub(offset) as usize);
+        let mut buf = vec![0u8; len];
+        let mut filled = 0;
+        while filled < len {
+            filled += reader.read_value(&mut buf[filled..])?;
+        }
+        Ok(Bytes::from(buf))
+    }
+}
+
+impl ValueReader<'_> {
+    /// value 的长度
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn read_value(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let remaining = self.len.saturating_sub(self.pos);
+        if remaining == 0 || buf.is_empty() {
+            return Ok(0);
+        }
+        let max = remaining.min(buf.len() as u64) as usize;
+        let buf = &mut buf[..max];
+        let n = match &self.source {
+            Source::Data { file_id, offset } => {
+                let offset = offset + self.pos;
+                self.engine
+                    .with_data_file(*file_id, |data_file| data_file.read_at(buf, offset))?
+            }
+            Source::Blob { file_id, offset } => {
+                self.engine
+                    .blobs()
+                    .read_at(*file_id, buf, offset + self.pos)?
+            }
+            Source::Memory(value) => {
+                let start = self.pos as usize;
+                buf.copy_from_slice(&value[start..start + buf.len()]);
+                buf.len()
+            }
+        };
+        // 文件比记录中的长度短，数据已经损坏
+        if n == 0 {
+            return Err(Errors::FailedReadFromDataFile);
+        }
+
+        self.pos += n as u64;
+        if let Some(hasher) = self.hasher.as_mut() {
+            hasher.update(&buf[..n]);
+        }
+        if self.pos == self.len {
+            if let Some(hasher) = self.hasher.take() {
+                if hasher.finalize() != self.crc {
+                    return Err(Errors::InvalidLogRecordCrc);
+                }
+            }
+        }
+        Ok(n)
+    }
+}
+
+impl Read for ValueReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.read_value(buf)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+impl Seek for ValueReader<'_> {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(n) => Some(n),
+            SeekFrom::End(n) => self.len.checked_add_signed(n),
+            SeekFrom::Current(n) => self.pos.checked_add_signed(n),
+        };
+        let Some(new_pos) = new_pos else {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            ));
+        };
+        // 不再是从头顺序读取，无法校验 CRC
+        if new_pos != self.pos {
+            self.hasher = None;
+        }
+        self.pos = new_pos;
+        Ok(new_pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_family::DEFAULT_CF_ID;
+    use crate::options::{Compression, Options};
+    use std::fs;
+    use std::os::unix::fs::FileExt;
+
+    fn value(len: usize) -> Bytes {
+        Bytes::from((0..len).map(|i| (i % 251) as u8).collect::<Vec<u8>>())
+    }
+
+    #[test]
+    fn test_get_reader_and_range() {
+        let dir_path = std::env::temp_dir().join("fdb-value-reader");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            compression: Compression::Lz4,
+            compression_threshold: 64,
+            blob_threshold: 512 * 1024,
+            ..Default::default()
+        })
+        .unwrap();
+        // 普通的 value、压缩的 value 以及 blob 中的 value
+        engine.put(Bytes::from("plain"), value(50)).unwrap();
+        engine
+            .put(Bytes::from("compressed"), value(100_000))
+            .unwrap();
+        engine.put(Bytes::from("blob"), value(1024 * 1024)).unwrap();
+
+        for (key, len) in [
+            ("plain", 50),
+            ("compressed", 100_000),
+            ("blob", 1024 * 1024),
+        ] {
+            let expected = value(len);
+            let mut reader = engine.get_reader(Bytes::from(key)).unwrap();
+            assert_eq!(reader.len(), len as u64);
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            assert_eq!(buf, expected);
+
+            reader.seek(SeekFrom::Start(10)).unwrap();
+            let mut buf = [0u8; 20];
+            reader.read_exact(&mut buf).unwrap();
+            assert_eq!(&buf[..], &expected[10..30]);
+            reader.seek(SeekFrom::End(-5)).unwrap();
+            let mut buf = Vec::new();
+            reader.read_to_end(&mut buf).unwrap();
+            assert_eq!(&buf[..], &expected[len - 5..]);
+
+            let range = engine.get_range(Bytes::from(key), 20, 10).unwrap();
+            assert_eq!(range, expected.slice(20..30));
+            // 超出末尾的部分被忽略
+            let range = engine
+                .get_range(Bytes::from(key), len as u64 - 3, 10)
+                .unwrap();
+            assert_eq!(range, expected.slice(len - 3..));
+            assert!(engine
+                .get_range(Bytes::from(key), len as u64 + 1, 10)
+                .unwrap()
+                .is_empty());
+        }
+        assert!(matches!(
+            engine.get_reader(Bytes::from("missing")),
+            Err(Errors::KeyNotFound)
+        ));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_get_reader_crc() {
+        let dir_path = std::env::temp_dir().join("fdb-value-reader-crc");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        engine.put(Bytes::from("key"), value(4096)).unwrap();
+        engine.sync().unwrap();
+
+        // 篡改 value 的最后一个字节
+        let file = fs::OpenOptions::new()
+            .write(true)
+            .open(dir_path.join("000000000.data"))
+            .unwrap();
+        let offset = engine.write_pos().offset() - 5;
+        file.write_at(&[0xff], offset).unwrap();
+
+        let mut buf = Vec::new();
+        let mut reader = engine.get_reader(Bytes::from("key")).unwrap();
+        let err = reader.read_to_end(&mut buf).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        // 部分读取不校验 CRC
+        let range = engine.get_range(Bytes::from("key"), 0, 100).unwrap();
+        assert_eq!(range, value(4096).slice(..100));
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+
+    #[test]
+    fn test_get_reader_expired() {
+        let dir_path = std::env::temp_dir().join("fdb-value-reader-expired");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+        let expire = now_millis() + 100;
+        engine
+            .put_entry(DEFAULT_CF_ID, &*engine.index, b"key", &value(100), expire)
+            .unwrap();
+        assert_eq!(engine.get_reader(Bytes::from("key")).unwrap().len(), 100);
+
+        // 过期之后与 get 一样返回 KeyNotFound
+        std::thread::sleep(std::time::Duration::from_millis(150));
+        assert_eq!(engine.get(Bytes::from("key")), Err(Errors::KeyNotFound));
+        assert!(matches!(
+            engine.get_reader(Bytes::from("key")),
+            Err(Errors::KeyNotFound)
+        ));
+        assert_eq!(
+            engine.get_range(Bytes::from("key"), 0, 10),
+            Err(Errors::KeyNotFound)
+        );
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
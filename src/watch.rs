@@ -0,0 +1,155 @@
+use crate::cdc::{ChangeEvent, ChangeTarget, Subscription, POLL_INTERVAL};
+use crate::data::log_record::LogRecordPos;
+use crate::db::Engine;
+use crate::errors::Result;
+use crate::glob::glob_match;
+use bytes::Bytes;
+use std::time::{Duration, Instant};
+
+/// 监听的 key 模式
+#[derive(Clone, Debug, PartialEq)]
+pub enum WatchPattern {
+    Key(Bytes),    // 精确匹配
+    Prefix(Bytes), // 前缀匹配
+    Glob(Bytes),   // Redis 风格的 glob 匹配
+}
+
+impl WatchPattern {
+    pub fn matches(&self, key: &[u8]) -> bool {
+        match self {
+            WatchPattern::Key(k) => key == k.as_ref(),
+            WatchPattern::Prefix(prefix) => key.starts_with(prefix),
+            WatchPattern::Glob(pattern) => glob_match(pattern, key),
+        }
+    }
+}
+
+/// key 变更的监听器，put 或者 delete 修改了匹配的 key 时返回对应的变更
+///
+/// 只监听默认列族中的 key，列族中的变更和列族的创建、删除不会返回。
+/// 每次变更的 ChangeEvent::next 可以作为版本号，传给 watch_after 等待该版本之后的下一次变更。
+pub struct Watcher<'a> {
+    sub: Subscription<'a>,
+    pattern: WatchPattern,
+}
+
+impl Engine {
+    /// 监听之后发生的变更
+    pub fn watch(&self, pattern: WatchPattern) -> Watcher<'_> {
+        Watcher {
+            sub: self.subscribe(),
+            pattern,
+        }
+    }
+
+    /// 监听 version 之后的变更，version 之后已经发生的变更会立即返回
+    pub fn watch_after(&self, pattern: WatchPattern, version: LogRecordPos) -> Result<Watcher<'_>> {
+        Ok(Watcher {
+            sub: self.subscribe_from(version)?,
+            pattern,
+        })
+    }
+}
+
+impl Watcher<'_> {
+    /// 当前的版本，即已检查过的变更之后的位置
+    pub fn version(&self) -> LogRecordPos {
+        self.sub.position()
+    }
+
+    /// 阻塞直到下一次匹配的变更
+    pub fn recv(&mut self) -> Result<ChangeEvent> {
+        loop {
+            if let Some(event) = self.recv_timeout(POLL_INTERVAL)? {
+                return Ok(event);
+            }
+        }
+    }
+
+    /// 等待下一次匹配的变更，超时返回 None
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Result<Option<ChangeEvent>> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.sub.next_timeout(timeout)? {
+                Some(event)
+                    if event.target == ChangeTarget::Default
+                        && self.pattern.matches(&event.key) =>
+                {
+                    return Ok(Some(event))
+                }
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::options::Options;
+    use std::fs;
+
+    #[test]
+    fn test_watch_pattern() {
+        let key = WatchPattern::Key(Bytes::from("config"));
+        assert!(key.matches(b"config"));
+        assert!(!key.matches(b"config/a"));
+        let prefix = WatchPattern::Prefix(Bytes::from("config/"));
+        assert!(prefix.matches(b"config/a"));
+        assert!(!prefix.matches(b"config"));
+        let glob = WatchPattern::Glob(Bytes::from("user:*:name"));
+        assert!(glob.matches(b"user:1:name"));
+        assert!(!glob.matches(b"user:1:age"));
+    }
+
+    #[test]
+    fn test_watch() {
+        let dir_path = std::env::temp_dir().join("fdb-watch");
+        let _ = fs::remove_dir_all(dir_path.clone());
+        let engine = Engine::open(Options {
+            dir_path: dir_path.clone(),
+            ..Default::default()
+        })
+        .unwrap();
+
+        let mut watcher = engine.watch(WatchPattern::Prefix(Bytes::from("config/")));
+        let version = watcher.version();
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                std::thread::sleep(Duration::from_millis(50));
+                engine.put(Bytes::from("other"), Bytes::from("1")).unwrap();
+                engine
+                    .put(Bytes::from("config/a"), Bytes::from("1"))
+                    .unwrap();
+            });
+            let event = watcher.recv().unwrap();
+            assert_eq!(event.key, Bytes::from("config/a"));
+            assert_eq!(event.value, Some(Bytes::from("1")));
+        });
+        assert_eq!(
+            watcher.recv_timeout(Duration::from_millis(10)).unwrap(),
+            None
+        );
+
+        // 从旧版本开始等待时，立即返回该版本之后的变更
+        engine.delete(Bytes::from("config/a")).unwrap();
+        let mut watcher = engine
+            .watch_after(WatchPattern::Key(Bytes::from("config/a")), version)
+            .unwrap();
+        let first = watcher.recv_timeout(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(first.value, Some(Bytes::from("1")));
+        let second = watcher.recv_timeout(Duration::ZERO).unwrap().unwrap();
+        assert_eq!(second.value, None);
+        assert_eq!(watcher.recv_timeout(Duration::ZERO).unwrap(), None);
+
+        // 从最新的版本开始等待时，没有变更
+        let mut watcher = engine
+            .watch_after(WatchPattern::Key(Bytes::from("config/a")), second.next)
+            .unwrap();
+        assert_eq!(watcher.recv_timeout(Duration::ZERO).unwrap(), None);
+
+        fs::remove_dir_all(dir_path).unwrap();
+    }
+}
@@ -0,0 +1,49 @@
+#!/bin/sh
+# 使用 redis-server 生成 RDB 导入测试用的 dump 文件：
+#
+#   sh tests/fixtures/generate_rdb.sh
+#
+# 需要 7.2 及以上版本的 redis-server 和 redis-cli，小的 set 从 7.2 开始使用 listpack 编码。
+# 生成之后运行 cargo test test_import_redis_dump -- --ignored 检查导入结果。
+set -e
+
+cd "$(dirname "$0")"
+PORT=${PORT:-16399}
+DIR=$(mktemp -d)
+
+redis-server --port "$PORT" --dir "$DIR" --dbfilename dump.rdb \
+    --save "" --appendonly no --rdbcompression yes --rdbchecksum yes \
+    --daemonize yes --pidfile "$DIR/redis.pid" --logfile "$DIR/redis.log"
+until redis-cli -p "$PORT" ping > /dev/null 2>&1; do
+    sleep 0.1
+done
+
+cli() {
+    redis-cli -p "$PORT" "$@" > /dev/null
+}
+
+cli FLUSHALL
+# string：整数编码、普通字符串、LZF 压缩、带过期时间
+cli SET int 12345
+cli SET name fdb
+cli SET lzf "$(printf 'a%.0s' $(seq 1 100))"
+cli SET session token EX 3600
+# list：quicklist 2，节点为 listpack
+cli RPUSH list a b 1 -300
+cli RPUSH list-large $(seq -f "e%g" 1 1000)
+# set：intset、listpack、hashtable
+cli SADD intset 1 2 300 -1
+cli SADD set-listpack s1 s2 7
+cli SADD set-large $(seq -f "m%g" 1 200)
+# hash：listpack、hashtable
+cli HSET hash-listpack f v n 42
+cli HSET hash-large $(for i in $(seq 1 200); do echo "f$i v$i"; done)
+# zset：listpack、skiplist
+cli ZADD zset-listpack 1 a 2.5 b
+cli ZADD zset-large $(for i in $(seq 1 200); do echo "$i m$i"; done)
+
+cli SAVE
+cp "$DIR/dump.rdb" redis-7.rdb
+cli SHUTDOWN NOSAVE || true
+rm -rf "$DIR"
+echo "generated $(pwd)/redis-7.rdb"